@@ -1,8 +1,5 @@
 use async_openai::config::AzureConfig;
-use async_openai::types::chat::{
-    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequest,
-};
-use composite_llm::{AzureBackend, ChatCompletionBackend};
+use composite_llm::{AzureBackend, ChatCompletionBackend, ChatCompletionResponseExt, ChatRequest};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -16,20 +13,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let backend = AzureBackend::new(config);
 
-    let req = CreateChatCompletionRequest {
-        model: "gpt-4o-mini".to_string(),
-        messages: vec![ChatCompletionRequestMessage::User(
-            ChatCompletionRequestUserMessageArgs::default()
-                .content("What is the capital of France?")
-                .build()?,
-        )],
-        ..Default::default()
-    };
+    let req = ChatRequest::new("gpt-4o-mini")
+        .user("What is the capital of France?")
+        .build();
 
     let response = backend.chat_completion(req).await?;
 
-    if let Some(choice) = response.choices.first() {
-        println!("{}", choice.message.content.as_deref().unwrap_or(""));
+    for text in response.texts() {
+        println!("{text}");
     }
 
     Ok(())
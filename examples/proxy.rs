@@ -0,0 +1,141 @@
+//! A minimal OpenAI-compatible proxy in front of any backend.
+//!
+//! Serves `POST /v1/chat/completions`, streaming (`"stream": true`) and
+//! non-streaming, from the backend named by `COMPOSITE_LLM_BACKEND` (default
+//! `openai`), configured from the environment as described in
+//! `CompositeClient::from_env_for`. `COMPOSITE_LLM_MODEL` optionally sets the
+//! Bedrock or Vertex AI model, and `PROXY_ADDR` the listen address (default
+//! `127.0.0.1:8080`).
+//!
+//! ```sh
+//! COMPOSITE_LLM_BACKEND=bedrock cargo run --example proxy --features backend-bedrock
+//! curl localhost:8080/v1/chat/completions \
+//!     -d '{"model": "", "messages": [{"role": "user", "content": "Hi"}], "stream": true}'
+//! ```
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use composite_llm::convert::openai::{SSE_DONE, to_openai_json, to_openai_sse_bytes};
+use composite_llm::{
+    BackendKind, ChatCompletionStream, CompositeClient, CompositeLlmError,
+    CreateChatCompletionRequest,
+};
+use futures_util::StreamExt;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let kind: BackendKind = std::env::var("COMPOSITE_LLM_BACKEND")
+        .unwrap_or_else(|_| "openai".to_string())
+        .parse()?;
+    let model = std::env::var("COMPOSITE_LLM_MODEL").ok();
+    let client = Arc::new(CompositeClient::from_env_for(kind, model.as_deref()).await?);
+
+    let addr = std::env::var("PROXY_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let listener = TcpListener::bind(&addr).await?;
+    println!("serving {kind} on http://{addr}/v1/chat/completions");
+
+    loop {
+        let (tcp, _) = listener.accept().await?;
+        let client = client.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(client.clone(), req));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(tcp), service)
+                .await
+            {
+                eprintln!("connection error: {e}");
+            }
+        });
+    }
+}
+
+async fn handle(
+    client: Arc<CompositeClient>,
+    req: Request<Incoming>,
+) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST || req.uri().path() != "/v1/chat/completions" {
+        return Ok(error_response(StatusCode::NOT_FOUND, "not found"));
+    }
+    let body = match req.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) => return Ok(error_response(StatusCode::BAD_REQUEST, &e.to_string())),
+    };
+    let chat_req: CreateChatCompletionRequest = match serde_json::from_slice(&body) {
+        Ok(chat_req) => chat_req,
+        Err(e) => return Ok(error_response(StatusCode::BAD_REQUEST, &e.to_string())),
+    };
+
+    let response = if chat_req.stream == Some(true) {
+        client
+            .chat_completion_stream(chat_req)
+            .await
+            .map(sse_response)
+    } else {
+        client
+            .chat_completion(chat_req)
+            .await
+            .and_then(|response| to_openai_json(&response))
+            .map(|body| json_response(StatusCode::OK, body))
+    };
+    Ok(response.unwrap_or_else(|e| error_response(StatusCode::BAD_GATEWAY, &e.to_string())))
+}
+
+/// Re-encodes the stream as OpenAI server-sent events. An error after the
+/// response has started is sent as a final `error` event instead of `[DONE]`.
+fn sse_response(stream: ChatCompletionStream) -> Response<Body> {
+    let mut failed = false;
+    let events = stream
+        .map(|item| {
+            item.and_then(|chunk| to_openai_sse_bytes(&chunk))
+                .map(Bytes::from)
+        })
+        .chain(futures_util::stream::once(async {
+            Ok(Bytes::from_static(SSE_DONE))
+        }))
+        .take_while(move |item| {
+            let keep = !failed;
+            failed |= item.is_err();
+            std::future::ready(keep)
+        })
+        .map(|item| {
+            let bytes = item.unwrap_or_else(|e: CompositeLlmError| {
+                Bytes::from(format!("data: {}\n\n", error_body(&e.to_string())))
+            });
+            Ok(Frame::data(bytes))
+        });
+
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .body(StreamBody::new(events).boxed_unsync())
+        .expect("the response is valid")
+}
+
+fn json_response(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)).boxed_unsync())
+        .expect("the response is valid")
+}
+
+/// An error in the OpenAI error body format.
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    json_response(status, error_body(message).to_string().into_bytes())
+}
+
+fn error_body(message: &str) -> serde_json::Value {
+    serde_json::json!({"error": {"message": message, "type": "composite_llm_error"}})
+}
@@ -1,7 +1,4 @@
-use async_openai::types::chat::{
-    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequest,
-};
-use composite_llm::{ChatCompletionBackend, VertexBackend};
+use composite_llm::{ChatCompletionBackend, ChatCompletionResponseExt, ChatRequest, VertexBackend};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -11,20 +8,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Uses Application Default Credentials (ADC) for authentication.
     let backend = VertexBackend::new(project_id, location, "gemini-2.0-flash").await?;
 
-    let req = CreateChatCompletionRequest {
-        model: "gemini-2.0-flash".to_string(),
-        messages: vec![ChatCompletionRequestMessage::User(
-            ChatCompletionRequestUserMessageArgs::default()
-                .content("What is the capital of France?")
-                .build()?,
-        )],
-        ..Default::default()
-    };
+    let req = ChatRequest::new("gemini-2.0-flash")
+        .user("What is the capital of France?")
+        .build();
 
     let response = backend.chat_completion(req).await?;
 
-    if let Some(choice) = response.choices.first() {
-        println!("{}", choice.message.content.as_deref().unwrap_or(""));
+    for text in response.texts() {
+        println!("{text}");
     }
 
     Ok(())
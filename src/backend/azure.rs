@@ -1,51 +1,445 @@
-use async_openai::{Client, config::AzureConfig};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_openai::config::{AzureConfig, Config};
+use async_openai::traits::RequestOptionsBuilder;
+use async_openai::{Chat, Client};
 use async_trait::async_trait;
+use http::HeaderMap;
+use secrecy::SecretString;
+use serde::Deserialize;
 use tokio_stream::StreamExt;
+use tracing::Instrument;
 
-use super::{ChatCompletionBackend, ChatCompletionStream};
-use crate::error::CompositeLlmError;
+use super::{ChatCompletionBackend, ChatCompletionStream, check_messages, request_span};
+use crate::error::{BoxError, CompositeLlmError};
+use crate::options::{Credentials, ProviderOptions};
 use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
 
+/// Supplies Microsoft Entra ID (Azure AD) access tokens for Azure OpenAI.
+///
+/// Called before every request, so implementations should cache tokens and
+/// refresh them shortly before they expire. With the `azure_identity` crate:
+///
+/// ```ignore
+/// struct Entra(Arc<dyn azure_core::credentials::TokenCredential>);
+///
+/// #[async_trait::async_trait]
+/// impl AzureTokenProvider for Entra {
+///     async fn token(&self) -> Result<String, BoxError> {
+///         let scopes = &["https://cognitiveservices.azure.com/.default"];
+///         Ok(self.0.get_token(scopes, None).await?.token.secret().to_string())
+///     }
+/// }
+///
+/// let credential = azure_identity::DeveloperToolsCredential::new(None)?;
+/// let backend = AzureBackend::new(config).with_token_provider(Entra(credential));
+/// ```
+#[async_trait]
+pub trait AzureTokenProvider: Send + Sync {
+    /// Returns a bearer token for the `https://cognitiveservices.azure.com` scope.
+    async fn token(&self) -> Result<String, BoxError>;
+}
+
 /// A backend implementation for Azure OpenAI.
 ///
-/// This backend uses the `async-openai` crate with `AzureConfig`.
+/// This backend uses the `async-openai` crate with `AzureConfig`. Requests
+/// go to the deployment of the config unless their model is mapped to another
+/// deployment with [`with_deployment`](Self::with_deployment).
+#[derive(Clone)]
 pub struct AzureBackend {
     client: Client<AzureConfig>,
+    /// Clients of the backend's own requests: one for the config's deployment,
+    /// and one per deployment added with `with_deployment`.
+    default_chat: Client<RequestConfig>,
+    deployments: HashMap<String, Client<RequestConfig>>,
+    /// Client of requests to the resource rather than a deployment, behind an
+    /// `Arc` to keep the backend small.
+    resource: Arc<Client<ResourceConfig>>,
+    token_provider: Option<Arc<dyn AzureTokenProvider>>,
+}
+
+/// An `AzureConfig` that leaves out the `api-key` header when no key is set,
+/// so that requests authenticated with a bearer token carry only that.
+#[derive(Debug, Clone)]
+struct RequestConfig(AzureConfig);
+
+impl Config for RequestConfig {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = self.0.headers();
+        if headers.get("api-key").is_some_and(|key| key.is_empty()) {
+            headers.remove("api-key");
+        }
+        headers
+    }
+
+    fn url(&self, path: &str) -> String {
+        self.0.url(path)
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        self.0.query()
+    }
+
+    fn api_base(&self) -> &str {
+        self.0.api_base()
+    }
+
+    fn api_key(&self) -> &SecretString {
+        self.0.api_key()
+    }
+}
+
+/// A [`RequestConfig`] addressing the resource, `{api_base}/openai`, instead
+/// of a deployment.
+#[derive(Debug, Clone)]
+struct ResourceConfig(RequestConfig);
+
+impl Config for ResourceConfig {
+    fn headers(&self) -> HeaderMap {
+        self.0.headers()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/openai{path}", self.0.api_base())
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        self.0.query()
+    }
+
+    fn api_base(&self) -> &str {
+        self.0.api_base()
+    }
+
+    fn api_key(&self) -> &SecretString {
+        self.0.api_key()
+    }
+}
+
+/// The body of `GET /openai/models`, of which only the IDs are kept.
+#[derive(Debug, Deserialize)]
+struct AzureModelList {
+    data: Vec<AzureModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AzureModel {
+    id: String,
 }
 
 impl AzureBackend {
     /// Creates a new `AzureBackend` with the given configuration.
+    ///
+    /// Unlike `OpenAIConfig`, `AzureConfig` has no way to add headers, so
+    /// requests carry the default `User-Agent` of `async-openai`.
     pub fn new(config: AzureConfig) -> Self {
         Self {
+            default_chat: Client::with_config(RequestConfig(config.clone())),
+            resource: Arc::new(Client::with_config(ResourceConfig(RequestConfig(
+                config.clone(),
+            )))),
             client: Client::with_config(config),
+            deployments: HashMap::new(),
+            token_provider: None,
+        }
+    }
+
+    /// Sends requests for `model` to `deployment_id` on the same resource.
+    ///
+    /// The mapped deployment shares the endpoint, API version and key of the
+    /// config passed to [`new`](Self::new); its client is created here, once.
+    /// Requests for models without a mapping, including an empty `model`, go
+    /// to the deployment set with `AzureConfig::with_deployment_id`.
+    pub fn with_deployment(
+        mut self,
+        model: impl Into<String>,
+        deployment_id: impl Into<String>,
+    ) -> Self {
+        let config = self
+            .client
+            .config()
+            .clone()
+            .with_deployment_id(deployment_id);
+        self.deployments
+            .insert(model.into(), Client::with_config(RequestConfig(config)));
+        self
+    }
+
+    /// Authenticates with Entra ID bearer tokens instead of an API key.
+    ///
+    /// Every request sends `Authorization: Bearer <token>` with a token from
+    /// `provider`; leave the API key of the `AzureConfig` unset, and no
+    /// `api-key` header is sent. Per-request [`Credentials`] still take
+    /// precedence. A failing provider fails the request with
+    /// [`CompositeLlmError::TokenProvider`].
+    pub fn with_token_provider(mut self, provider: impl AzureTokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Returns the underlying `async-openai` client, for endpoints other than
+    /// chat completions. It is bound to the deployment of the config passed to
+    /// [`new`](Self::new).
+    pub fn client(&self) -> &Client<AzureConfig> {
+        &self.client
+    }
+
+    /// Returns the client of the deployment serving `model`.
+    fn client_for(&self, model: &str) -> &Client<RequestConfig> {
+        self.deployments.get(model).unwrap_or(&self.default_chat)
+    }
+
+    /// Returns the request body `req` would be sent as, without sending it.
+    pub fn build_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        Ok(serde_json::to_value(req)?)
+    }
+
+    /// Returns the chat API of the deployment serving `model`, authenticated
+    /// with [`authenticate`](Self::authenticate).
+    async fn chat(
+        &self,
+        model: &str,
+        opts: &ProviderOptions,
+    ) -> Result<Chat<'_, RequestConfig>, CompositeLlmError> {
+        self.authenticate(self.client_for(model).chat(), opts).await
+    }
+
+    /// Authenticates `api` with per-request credentials if given, otherwise
+    /// with a token from the token provider, if any.
+    async fn authenticate<A: RequestOptionsBuilder>(
+        &self,
+        api: A,
+        opts: &ProviderOptions,
+    ) -> Result<A, CompositeLlmError> {
+        match opts.credentials {
+            None => match self.token_provider {
+                Some(ref provider) => {
+                    let token = provider
+                        .token()
+                        .await
+                        .map_err(CompositeLlmError::TokenProvider)?;
+                    Ok(api.header("authorization", format!("Bearer {token}"))?)
+                }
+                None => Ok(api),
+            },
+            Some(Credentials::ApiKey(ref key)) => Ok(api.header("api-key", key.as_str())?),
+            Some(Credentials::BearerToken(ref token)) => {
+                Ok(api.header("authorization", format!("Bearer {token}"))?)
+            }
+            Some(_) => Err(CompositeLlmError::Unsupported(
+                "Azure OpenAI backend only supports API key and bearer token credentials"
+                    .to_string(),
+            )),
         }
     }
 }
 
 #[async_trait]
 impl ChatCompletionBackend for AzureBackend {
+    fn name(&self) -> &str {
+        crate::BackendKind::Azure.as_str()
+    }
+
     async fn chat_completion(
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
-        self.client
-            .chat()
-            .create(req)
+        self.chat_completion_with(req, &ProviderOptions::default())
             .await
-            .map_err(CompositeLlmError::from)
     }
 
     async fn chat_completion_stream(
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionStream, CompositeLlmError> {
-        let stream = self
-            .client
-            .chat()
-            .create_stream(req)
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
             .await
-            .map_err(CompositeLlmError::from)?;
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        check_messages(&req)?;
+        let span = request_span(self.name(), &req, opts);
+        async move {
+            self.chat(&req.model, opts)
+                .await?
+                .create(req)
+                .await
+                .map_err(CompositeLlmError::from)
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        check_messages(&req)?;
+        let span = request_span(self.name(), &req, opts);
+        let stream = async move {
+            self.chat(&req.model, opts)
+                .await?
+                .create_stream(req)
+                .await
+                .map_err(CompositeLlmError::from)
+        }
+        .instrument(span)
+        .await?;
 
         Ok(Box::pin(stream.map(|r| r.map_err(CompositeLlmError::from))))
     }
+
+    /// Lists the models of the resource, which costs no tokens.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.list_models().await.map(|_| ())
+    }
+
+    /// Lists the models of the resource with `GET {api_base}/openai/models`.
+    ///
+    /// These are the models available to deploy, not the deployments; Azure
+    /// OpenAI lists deployments only through the management API.
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        let models: AzureModelList = self
+            .authenticate(self.resource.models(), &ProviderOptions::default())
+            .await?
+            .list_byot()
+            .await?;
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_server::serve;
+
+    struct StaticToken;
+
+    #[async_trait]
+    impl AzureTokenProvider for StaticToken {
+        async fn token(&self) -> Result<String, BoxError> {
+            Ok("entra-token".to_string())
+        }
+    }
+
+    struct FailingToken;
+
+    #[async_trait]
+    impl AzureTokenProvider for FailingToken {
+        async fn token(&self) -> Result<String, BoxError> {
+            Err("no credential".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_provider() {
+        let (base, requests) = serve(
+            "400 Bad Request",
+            r#"{"error":{"message":"stop","type":"invalid_request_error","param":null,"code":null}}"#,
+        )
+        .await;
+        let config = AzureConfig::new()
+            .with_api_base(base)
+            .with_api_key("")
+            .with_deployment_id("default");
+        let req = || -> CreateChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": "",
+                "messages": [{"role": "user", "content": "Hi"}]
+            }))
+            .unwrap()
+        };
+
+        let backend = AzureBackend::new(config.clone()).with_token_provider(StaticToken);
+        assert!(backend.chat_completion(req()).await.is_err());
+        let opts = ProviderOptions::default()
+            .with_credentials(Credentials::BearerToken("per-request".to_string()));
+        assert!(backend.chat_completion_with(req(), &opts).await.is_err());
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("authorization: bearer entra-token\r\n"));
+        assert!(!requests[0].contains("api-key"));
+        assert!(requests[1].contains("authorization: bearer per-request\r\n"));
+
+        let backend = AzureBackend::new(config).with_token_provider(FailingToken);
+        let err = backend.chat_completion(req()).await.unwrap_err();
+        assert!(matches!(err, CompositeLlmError::TokenProvider(_)));
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "no credential"
+        );
+    }
+
+    #[test]
+    fn test_deployment_by_model() {
+        let config = AzureConfig::new()
+            .with_api_base("https://example.openai.azure.com")
+            .with_api_key("key")
+            .with_deployment_id("default")
+            .with_api_version("2024-02-01");
+        let backend = AzureBackend::new(config).with_deployment("gpt-4o", "gpt4o-prod");
+
+        let url = |model| backend.client_for(model).config().url("/chat/completions");
+        assert_eq!(
+            url("gpt-4o"),
+            "https://example.openai.azure.com/openai/deployments/gpt4o-prod/chat/completions"
+        );
+        assert_eq!(
+            url("gpt-4o-mini"),
+            "https://example.openai.azure.com/openai/deployments/default/chat/completions"
+        );
+        assert_eq!(
+            backend.client_for("gpt-4o").config().query(),
+            [("api-version", "2024-02-01")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let (base, requests) = serve(
+            "200 OK",
+            r#"{"object":"list","data":[{"id":"gpt-4o","object":"model","created_at":1715558400},{"id":"o3-mini","object":"model","created_at":1738281600}]}"#,
+        )
+        .await;
+        let config = AzureConfig::new()
+            .with_api_base(base)
+            .with_api_key("key")
+            .with_deployment_id("default")
+            .with_api_version("2024-10-21");
+
+        let backend = AzureBackend::new(config);
+        let models = backend.list_models().await.unwrap();
+        assert_eq!(models, ["gpt-4o", "o3-mini"]);
+        assert!(backend.health_check().await.is_ok());
+
+        let requests = requests.lock().unwrap().clone();
+        assert!(requests[0].starts_with("get /openai/models?api-version=2024-10-21 "));
+        assert!(requests[0].contains("api-key: key\r\n"));
+        assert!(requests[1].starts_with("get /openai/models?"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_messages_are_rejected() {
+        let backend = AzureBackend::new(AzureConfig::new());
+        let req = CreateChatCompletionRequest::default;
+
+        assert!(matches!(
+            backend.chat_completion(req()).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(matches!(
+            backend.chat_completion_stream(req()).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
 }
@@ -1,13 +1,39 @@
+use std::pin::Pin;
+use std::time::Duration;
+
 use async_trait::async_trait;
+#[cfg(feature = "bedrock-list-models")]
+use aws_sdk_bedrock::Client as BedrockControlClient;
+#[cfg(feature = "bedrock-list-models")]
+use aws_sdk_bedrock::operation::list_foundation_models::ListFoundationModelsError;
 use aws_sdk_bedrockruntime::Client as BedrockClient;
+use aws_sdk_bedrockruntime::error::SdkError;
+use aws_sdk_bedrockruntime::operation::converse::ConverseError;
+use aws_sdk_bedrockruntime::operation::converse_stream::builders::ConverseStreamFluentBuilder;
+use aws_sdk_bedrockruntime::operation::converse_stream::{
+    ConverseStreamError, ConverseStreamOutput,
+};
+use aws_sdk_bedrockruntime::types::ConverseStreamOutput as ConverseStreamEvent;
+use aws_sdk_bedrockruntime::types::error::ConverseStreamOutputError;
+use aws_smithy_types::error::display::DisplayErrorContext;
+use aws_smithy_types::event_stream::RawMessage;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use futures_util::future::{Either, select};
+use tracing::Instrument;
 
-use super::{ChatCompletionBackend, ChatCompletionStream};
+use super::{ChatCompletionBackend, ChatCompletionStream, check_messages, request_span};
 use crate::convert::bedrock::{
-    build_inference_config, build_tool_config, convert_converse_response,
-    extract_system_and_messages, stream_event_to_response,
+    ConverseInput, MAX_STOP_SEQUENCES, StreamState, additional_model_response_fields,
+    build_additional_model_request_fields, build_guardrail_config, build_guardrail_stream_config,
+    build_inference_config, build_json_mode_system_block, build_parallel_tool_calls_system_block,
+    build_performance_config, build_prompt_variables, build_tool_config, check_unsupported_params,
+    convert_converse_response, extract_system_and_messages, raw_stop_reason,
+    stream_event_to_response,
 };
-use crate::convert::generate_chat_cmpl_id;
-use crate::error::CompositeLlmError;
+use crate::convert::{generate_chat_cmpl_id, include_usage, with_default_stop_sequences};
+use crate::error::{BedrockErrorKind, CompositeLlmError};
+use crate::options::{BedrockOptions, Credentials, LatencyMode, ProviderOptions};
 use async_openai::types::chat::{
     CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
 };
@@ -15,126 +41,1189 @@ use async_openai::types::chat::{
 /// A backend implementation for Amazon Bedrock.
 ///
 /// This backend uses the AWS SDK for Bedrock Runtime (Converse API).
+#[derive(Clone)]
 pub struct BedrockBackend {
     client: BedrockClient,
+    /// Control-plane client used by [`list_models`](ChatCompletionBackend::list_models).
+    #[cfg(feature = "bedrock-list-models")]
+    control_client: Option<BedrockControlClient>,
     model_id: String,
+    stream_retries: u32,
+    stream_task: bool,
+    stream_buffer: usize,
+    request_model: bool,
+    stop_sequences: Vec<String>,
+    latency: Option<LatencyMode>,
 }
 
+/// The raw events of a Converse stream, as returned by
+/// [`BedrockBackend::converse_stream_events`].
+pub type ConverseEventStream =
+    Pin<Box<dyn Stream<Item = Result<ConverseStreamEvent, CompositeLlmError>> + Send>>;
+
+/// A chat completion response with the Bedrock-specific parts kept.
+#[derive(Debug, Clone)]
+pub struct BedrockResponse {
+    /// The converted response.
+    pub response: CreateChatCompletionResponse,
+    /// The model-specific `additionalModelResponseFields`, if returned.
+    pub additional_model_response_fields: Option<serde_json::Value>,
+    /// The raw `stopReason`, e.g. `end_turn` or `stop_sequence`, which are
+    /// both [`FinishReason::Stop`](async_openai::types::chat::FinishReason::Stop)
+    /// in `response`.
+    pub stop_reason: String,
+}
+
+/// Default number of retries when establishing a stream.
+const DEFAULT_STREAM_RETRIES: u32 = 3;
+/// Default number of chunks buffered between the stream task and the consumer.
+const DEFAULT_STREAM_BUFFER: usize = 32;
+/// Backoff before the first stream retry; doubled on each further attempt.
+const STREAM_RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+/// Upper bound of the backoff between stream retries.
+const STREAM_RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
 impl BedrockBackend {
     /// Creates a new `BedrockBackend` with an existing client and model ID.
+    ///
+    /// `model_id` is called regardless of `req.model` unless
+    /// [`with_request_model`](Self::with_request_model) is enabled.
     pub fn new(client: BedrockClient, model_id: impl Into<String>) -> Self {
         Self {
             client,
+            #[cfg(feature = "bedrock-list-models")]
+            control_client: None,
             model_id: model_id.into(),
+            stream_retries: DEFAULT_STREAM_RETRIES,
+            stream_task: true,
+            stream_buffer: DEFAULT_STREAM_BUFFER,
+            request_model: false,
+            stop_sequences: Vec::new(),
+            latency: None,
         }
     }
 
     /// Creates a new `BedrockBackend` from the environment.
     ///
     /// This loads the AWS configuration from the environment (credentials, region)
-    /// and creates a default `BedrockClient`.
+    /// and creates a default `BedrockClient`, plus, with the
+    /// `bedrock-list-models` feature, the control-plane client used to list
+    /// models.
     pub async fn from_env(model_id: impl Into<String>) -> Self {
         let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        Self::from_sdk_config(&config, model_id)
+    }
+
+    /// Creates a new `BedrockBackend` from the environment, pinned to `region`.
+    ///
+    /// Credentials are loaded as in [`from_env`](Self::from_env), but the
+    /// region is set explicitly instead of being read from the environment.
+    pub async fn from_env_in_region(
+        region: impl Into<String>,
+        model_id: impl Into<String>,
+    ) -> Self {
+        let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(aws_config::Region::new(region.into()))
+            .load()
+            .await;
+        Self::from_sdk_config(&config, model_id)
+    }
+
+    /// Creates the clients of the `from_env` constructors from `config`.
+    fn from_sdk_config(config: &aws_config::SdkConfig, model_id: impl Into<String>) -> Self {
         Self {
-            client: BedrockClient::new(&config),
-            model_id: model_id.into(),
+            #[cfg(feature = "bedrock-list-models")]
+            control_client: Some(BedrockControlClient::new(config)),
+            ..Self::new(BedrockClient::new(config), model_id)
         }
     }
-}
 
-#[async_trait]
-impl ChatCompletionBackend for BedrockBackend {
-    async fn chat_completion(
+    /// Sets the Bedrock control-plane client used to list models through
+    /// `ListFoundationModels`.
+    ///
+    /// Backends created with [`new`](Self::new) have none and fail
+    /// [`list_models`](ChatCompletionBackend::list_models) with
+    /// [`CompositeLlmError::Unsupported`]; the `from_env` constructors set
+    /// one up from the same configuration as the runtime client.
+    #[cfg(feature = "bedrock-list-models")]
+    pub fn with_control_client(mut self, client: BedrockControlClient) -> Self {
+        self.control_client = Some(client);
+        self
+    }
+
+    /// Sets how many times establishing a stream is retried on retryable
+    /// errors such as throttling, server or connection errors (default 3).
+    /// Errors after the stream has started are never retried.
+    ///
+    /// These retries replace the SDK's own retry, which is turned off for
+    /// stream requests so that the attempts do not multiply; with `0`, the
+    /// stream is attempted exactly once.
+    pub fn with_stream_retries(mut self, retries: u32) -> Self {
+        self.stream_retries = retries;
+        self
+    }
+
+    /// Sets stop sequences added to every request's `stop`.
+    ///
+    /// Duplicates are skipped, and defaults that would exceed Bedrock's
+    /// limit of [`MAX_STOP_SEQUENCES`] are dropped in favor of the request's.
+    pub fn with_stop_sequences(
+        mut self,
+        stop_sequences: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.stop_sequences = stop_sequences.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the latency mode sent as `performanceConfig` with every request.
+    ///
+    /// By default none is sent and Bedrock uses standard inference. Only some
+    /// models support [`LatencyMode::Optimized`]; others reject the request.
+    pub fn with_latency(mut self, latency: LatencyMode) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Sets whether streams are read by a spawned background task (default).
+    ///
+    /// With `false`, the event stream is read directly when the returned
+    /// stream is polled, so nothing runs ahead of the consumer. Either way,
+    /// dropping the stream cancels the upstream read.
+    pub fn with_stream_task(mut self, stream_task: bool) -> Self {
+        self.stream_task = stream_task;
+        self
+    }
+
+    /// Sets how many chunks the stream task buffers ahead of the consumer
+    /// (default 32, at least 1).
+    ///
+    /// Once the buffer is full the task stops reading from Bedrock until the
+    /// consumer catches up, so memory stays bounded and no chunk is dropped.
+    /// A larger buffer lets a bursty producer run further ahead of a slow
+    /// consumer. Has no effect with [`with_stream_task(false)`](Self::with_stream_task).
+    pub fn with_stream_buffer(mut self, capacity: usize) -> Self {
+        self.stream_buffer = capacity;
+        self
+    }
+
+    /// Sets whether a non-empty `req.model` overrides the model ID the backend
+    /// was constructed with (default `false`).
+    ///
+    /// By default `req.model` only labels the response, so the same request
+    /// can be sent to several backends (e.g. with
+    /// [`RaceBackend`](crate::RaceBackend)) or to an OpenAI-named model; a
+    /// warning is logged when it differs from the bound model.
+    pub fn with_request_model(mut self, request_model: bool) -> Self {
+        self.request_model = request_model;
+        self
+    }
+
+    /// Returns the model ID to invoke: the managed prompt ARN if one is set,
+    /// otherwise `model` (see [`with_request_model`](Self::with_request_model))
+    /// or the bound model ID.
+    ///
+    /// `has_overrides` reports whether the caller's request sets a system
+    /// prompt, inference parameters, tools or additional model fields, which a
+    /// managed prompt has to define itself.
+    fn target_model_id<'a>(
+        &'a self,
+        model: &'a str,
+        opts: &'a BedrockOptions,
+        has_overrides: bool,
+    ) -> Result<&'a str, CompositeLlmError> {
+        match opts.managed_prompt {
+            Some(_) if has_overrides => Err(CompositeLlmError::Unsupported(
+                "system prompts, inference parameters, tools and additional model fields \
+                 must be defined in the Bedrock managed prompt"
+                    .to_string(),
+            )),
+            Some(ref prompt) => Ok(&prompt.arn),
+            None if self.request_model && !model.is_empty() => Ok(model),
+            None => {
+                if !model.is_empty() && model != self.model_id {
+                    tracing::warn!(
+                        requested = model,
+                        model = self.model_id,
+                        "ignoring the request model; enable with_request_model to use it"
+                    );
+                }
+                Ok(&self.model_id)
+            }
+        }
+    }
+
+    /// Sends a chat completion request and also returns the model-specific
+    /// `additionalModelResponseFields` and the raw `stopReason`, which have no
+    /// place in the OpenAI response.
+    pub async fn chat_completion_with_fields(
         &self,
         req: CreateChatCompletionRequest,
-    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
-        let model = req.model.clone();
-        let (system_blocks, messages) = extract_system_and_messages(req.messages.clone())?;
-        let inference_config = build_inference_config(&req);
-        let tool_config = build_tool_config(&req)?;
+        opts: &ProviderOptions,
+    ) -> Result<BedrockResponse, CompositeLlmError> {
+        let span = request_span(self.name(), &req, opts);
+        async move {
+            let model = req.model.clone();
+            let input = self.converse_input(&req, opts.bedrock.as_ref())?;
+            let mut builder = self
+                .client
+                .converse()
+                .model_id(input.model_id)
+                .set_messages((!input.messages.is_empty()).then_some(input.messages))
+                .set_system((!input.system.is_empty()).then_some(input.system))
+                .set_inference_config(input.inference_config)
+                .set_tool_config(input.tool_config)
+                .set_additional_model_request_fields(input.additional_model_request_fields)
+                .set_prompt_variables(input.prompt_variables)
+                .set_performance_config(input.performance_config);
+            if let Some(ref guardrail) = input.guardrail {
+                builder = builder.guardrail_config(build_guardrail_config(guardrail));
+            }
+
+            let output = match credentials_override(opts)? {
+                Some(config) => builder.customize().config_override(config).send().await,
+                None => builder.send().await,
+            }
+            .map_err(converse_error)?;
+
+            Ok(BedrockResponse {
+                response: convert_converse_response(&output, &model, &generate_chat_cmpl_id())?,
+                additional_model_response_fields: additional_model_response_fields(&output),
+                stop_reason: raw_stop_reason(&output).to_string(),
+            })
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Converts an OpenAI request into the parts of a Converse request.
+    fn converse_input(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: Option<&BedrockOptions>,
+    ) -> Result<ConverseInput, CompositeLlmError> {
+        check_unsupported_params(req)?;
+        let bedrock_opts = opts.cloned().unwrap_or_default();
+        // A managed prompt brings its own messages and settings, so the
+        // backend's defaults and cache points are left out, and only what the
+        // caller set counts as an override.
+        let managed = bedrock_opts.managed_prompt.is_some();
+        let (stop_sequences, cache_points) = if managed {
+            (&[][..], &[][..])
+        } else {
+            (&self.stop_sequences[..], &bedrock_opts.cache_points[..])
+        };
+        let req = &*with_default_stop_sequences(req, stop_sequences, MAX_STOP_SEQUENCES);
+        if !managed {
+            check_messages(req)?;
+        }
+        let (mut system, messages) =
+            extract_system_and_messages(req.messages.clone(), cache_points)?;
+        if messages.is_empty() && !managed {
+            return Err(CompositeLlmError::Unsupported(
+                "Bedrock requires at least one non-system message".to_string(),
+            ));
+        }
+        if let Some(block) = build_json_mode_system_block(req)? {
+            system.push(block);
+        }
+        if let Some(block) = build_parallel_tool_calls_system_block(req) {
+            system.push(block);
+        }
+        let inference_config = build_inference_config(req)?;
+        let tool_config = build_tool_config(req)?;
+        let additional_model_request_fields = build_additional_model_request_fields(&bedrock_opts)?;
+
+        let has_overrides = !system.is_empty()
+            || inference_config.is_some()
+            || tool_config.is_some()
+            || additional_model_request_fields.is_some();
+        let model_id = self
+            .target_model_id(&req.model, &bedrock_opts, has_overrides)?
+            .to_string();
+
+        Ok(ConverseInput {
+            model_id,
+            system,
+            messages,
+            inference_config,
+            tool_config,
+            additional_model_request_fields,
+            prompt_variables: bedrock_opts
+                .managed_prompt
+                .as_ref()
+                .map(build_prompt_variables),
+            guardrail: bedrock_opts.guardrail,
+            performance_config: self.latency.map(build_performance_config),
+        })
+    }
 
+    /// Returns the Converse request `req` would be sent as, without sending it.
+    ///
+    /// The request is rendered as the JSON body of the Converse REST API (the
+    /// `modelId` is included, as it is otherwise part of the URL).
+    pub fn build_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        self.build_request_with(req, &ProviderOptions::default())
+    }
+
+    /// Like [`build_request`](Self::build_request), with provider-specific options.
+    pub fn build_request_with(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        Ok(self.converse_input(req, opts.bedrock.as_ref())?.to_json())
+    }
+
+    /// Sends a streaming request and returns the raw Converse stream events,
+    /// for callers that need them rather than converted chunks, e.g. to feed a
+    /// [`BedrockStreamAccumulator`](crate::convert::bedrock::BedrockStreamAccumulator).
+    ///
+    /// Establishing the stream is retried as for chunked streams, but the
+    /// events are read on the caller's task: there is no stream task.
+    pub async fn converse_stream_events(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ConverseEventStream, CompositeLlmError> {
+        let input = self.converse_input(req, opts.bedrock.as_ref())?;
         let mut builder = self
             .client
-            .converse()
-            .model_id(&self.model_id)
-            .set_messages(Some(messages));
+            .converse_stream()
+            .model_id(input.model_id)
+            .set_messages((!input.messages.is_empty()).then_some(input.messages))
+            .set_system((!input.system.is_empty()).then_some(input.system))
+            .set_inference_config(input.inference_config)
+            .set_tool_config(input.tool_config)
+            .set_additional_model_request_fields(input.additional_model_request_fields)
+            .set_prompt_variables(input.prompt_variables)
+            .set_performance_config(input.performance_config);
+        if let Some(ref guardrail) = input.guardrail {
+            builder = builder.guardrail_config(build_guardrail_stream_config(guardrail));
+        }
+
+        let output =
+            send_stream_with_retry(builder, credentials_override(opts)?, self.stream_retries)
+                .instrument(request_span(self.name(), req, opts))
+                .await?;
 
-        if !system_blocks.is_empty() {
-            builder = builder.set_system(Some(system_blocks));
+        Ok(Box::pin(futures_util::stream::unfold(
+            Some(output),
+            |output| async move {
+                let mut output = output?;
+                match output.stream.recv().await {
+                    Ok(Some(event)) => Some((Ok(event), Some(output))),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(stream_output_error(e)), None)),
+                }
+            },
+        )))
+    }
+}
+
+/// Reads `chunks` on a spawned task, bridged to the returned stream by a
+/// channel holding up to `capacity` chunks.
+///
+/// When the channel is full the task waits for the consumer before reading
+/// further events, so a slow consumer never loses chunks and at most
+/// `capacity` of them are buffered. The task stops, dropping the upstream
+/// read, as soon as the returned stream is dropped.
+fn spawn_stream_task(
+    chunks: impl Stream<Item = Result<CreateChatCompletionStreamResponse, CompositeLlmError>>
+    + Send
+    + 'static,
+    capacity: usize,
+) -> ChatCompletionStream {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+    tokio::spawn(async move {
+        let mut chunks = std::pin::pin!(chunks);
+        loop {
+            let next = match select(std::pin::pin!(tx.closed()), chunks.next()).await {
+                Either::Left(_) => break,
+                Either::Right((next, _)) => next,
+            };
+            let Some(chunk) = next else { break };
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
         }
-        if let Some(config) = inference_config {
-            builder = builder.inference_config(config);
+    });
+    Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+}
+
+/// Sends a `ConverseStream` request, retrying throttling and 5xx errors with
+/// exponential backoff. The SDK's own retry is turned off for the request.
+async fn send_stream_with_retry(
+    builder: ConverseStreamFluentBuilder,
+    config_override: Option<aws_sdk_bedrockruntime::config::Builder>,
+    max_retries: u32,
+) -> Result<ConverseStreamOutput, CompositeLlmError> {
+    let config = config_override
+        .unwrap_or_default()
+        .retry_config(aws_sdk_bedrockruntime::config::retry::RetryConfig::disabled());
+    let mut attempt = 0;
+    loop {
+        let result = builder
+            .clone()
+            .customize()
+            .config_override(config.clone())
+            .send()
+            .await;
+        let err = match result {
+            Ok(output) => return Ok(output),
+            Err(e) => converse_stream_error(e),
+        };
+        match err {
+            CompositeLlmError::Bedrock {
+                retryable: true, ..
+            } if attempt < max_retries => {
+                tracing::debug!(attempt, error = %err, "retrying Bedrock stream request");
+                tokio::time::sleep(stream_retry_delay(attempt)).await;
+                attempt += 1;
+            }
+            err => return Err(err),
         }
-        if let Some(tc) = tool_config {
-            builder = builder.tool_config(tc);
+    }
+}
+
+/// The backoff before stream retry `attempt`: the base delay doubled per
+/// attempt up to [`STREAM_RETRY_MAX_DELAY`], of which the second half is
+/// random so that concurrent clients spread out.
+fn stream_retry_delay(attempt: u32) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let delay = STREAM_RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(STREAM_RETRY_MAX_DELAY);
+    let random = std::hash::RandomState::new().build_hasher().finish();
+    let half = delay / 2;
+    half + half.mul_f64((random >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+/// Maps a `Converse` error to a classified [`CompositeLlmError::Bedrock`].
+fn converse_error(err: SdkError<ConverseError>) -> CompositeLlmError {
+    let kind = err
+        .as_service_error()
+        .map_or(BedrockErrorKind::Other, |e| match e {
+            ConverseError::ResourceNotFoundException(_) => BedrockErrorKind::ResourceNotFound,
+            ConverseError::AccessDeniedException(_) => BedrockErrorKind::AccessDenied,
+            ConverseError::ValidationException(_) => BedrockErrorKind::Validation,
+            ConverseError::ThrottlingException(_) => BedrockErrorKind::Throttling,
+            ConverseError::InternalServerException(_)
+            | ConverseError::ServiceUnavailableException(_)
+            | ConverseError::ModelNotReadyException(_)
+            | ConverseError::ModelTimeoutException(_) => BedrockErrorKind::Unavailable,
+            _ => BedrockErrorKind::Other,
+        });
+    let server_error = err
+        .raw_response()
+        .is_some_and(|r| r.status().is_server_error());
+    classify(err, kind, server_error)
+}
+
+/// Maps a `ListFoundationModels` error to a classified [`CompositeLlmError::Bedrock`].
+#[cfg(feature = "bedrock-list-models")]
+fn list_foundation_models_error(
+    err: aws_sdk_bedrock::error::SdkError<ListFoundationModelsError>,
+) -> CompositeLlmError {
+    let kind = err
+        .as_service_error()
+        .map_or(BedrockErrorKind::Other, |e| match e {
+            ListFoundationModelsError::AccessDeniedException(_) => BedrockErrorKind::AccessDenied,
+            ListFoundationModelsError::ValidationException(_) => BedrockErrorKind::Validation,
+            ListFoundationModelsError::ThrottlingException(_) => BedrockErrorKind::Throttling,
+            ListFoundationModelsError::InternalServerException(_) => BedrockErrorKind::Unavailable,
+            _ => BedrockErrorKind::Other,
+        });
+    let server_error = err
+        .raw_response()
+        .is_some_and(|r| r.status().is_server_error());
+    classify(err, kind, server_error)
+}
+
+/// Maps a `ConverseStream` error to a classified [`CompositeLlmError::Bedrock`].
+fn converse_stream_error(err: SdkError<ConverseStreamError>) -> CompositeLlmError {
+    let kind = err
+        .as_service_error()
+        .map_or(BedrockErrorKind::Other, |e| match e {
+            ConverseStreamError::ResourceNotFoundException(_) => BedrockErrorKind::ResourceNotFound,
+            ConverseStreamError::AccessDeniedException(_) => BedrockErrorKind::AccessDenied,
+            ConverseStreamError::ValidationException(_) => BedrockErrorKind::Validation,
+            ConverseStreamError::ThrottlingException(_) => BedrockErrorKind::Throttling,
+            ConverseStreamError::InternalServerException(_)
+            | ConverseStreamError::ServiceUnavailableException(_)
+            | ConverseStreamError::ModelNotReadyException(_)
+            | ConverseStreamError::ModelTimeoutException(_) => BedrockErrorKind::Unavailable,
+            _ => BedrockErrorKind::Other,
+        });
+    let server_error = err
+        .raw_response()
+        .is_some_and(|r| r.status().is_server_error());
+    classify(err, kind, server_error)
+}
+
+/// Maps an error received mid-stream to a classified [`CompositeLlmError::Bedrock`].
+fn stream_output_error(err: SdkError<ConverseStreamOutputError, RawMessage>) -> CompositeLlmError {
+    let kind = err
+        .as_service_error()
+        .map_or(BedrockErrorKind::Other, |e| match e {
+            ConverseStreamOutputError::ValidationException(_) => BedrockErrorKind::Validation,
+            ConverseStreamOutputError::ThrottlingException(_) => BedrockErrorKind::Throttling,
+            ConverseStreamOutputError::InternalServerException(_)
+            | ConverseStreamOutputError::ServiceUnavailableException(_) => {
+                BedrockErrorKind::Unavailable
+            }
+            _ => BedrockErrorKind::Other,
+        });
+    classify(err, kind, false)
+}
+
+/// Builds the error; timeouts, connection failures and 5xx responses are
+/// retryable whatever their kind.
+///
+/// The message is the service's own, e.g. `ValidationException: ...`, as the
+/// `SdkError` itself only displays as `service error`.
+fn classify<E, R>(
+    err: SdkError<E, R>,
+    kind: BedrockErrorKind,
+    server_error: bool,
+) -> CompositeLlmError
+where
+    E: std::fmt::Display,
+    SdkError<E, R>: std::error::Error + Send + Sync + 'static,
+{
+    let retryable = kind.is_retryable()
+        || server_error
+        || matches!(
+            err,
+            SdkError::TimeoutError(_) | SdkError::DispatchFailure(_)
+        );
+    let message = match err.as_service_error() {
+        Some(e) => e.to_string(),
+        None => DisplayErrorContext(&err).to_string(),
+    };
+    CompositeLlmError::Bedrock {
+        message,
+        kind,
+        retryable,
+        source: Some(Box::new(err)),
+    }
+}
+
+/// Builds a config override carrying per-request credentials, if any.
+fn credentials_override(
+    opts: &ProviderOptions,
+) -> Result<Option<aws_sdk_bedrockruntime::config::Builder>, CompositeLlmError> {
+    match opts.credentials {
+        None => Ok(None),
+        Some(Credentials::Aws {
+            ref access_key_id,
+            ref secret_access_key,
+            ref session_token,
+        }) => {
+            let credentials = aws_sdk_bedrockruntime::config::Credentials::new(
+                access_key_id,
+                secret_access_key,
+                session_token.clone(),
+                None,
+                "composite-llm",
+            );
+            Ok(Some(
+                aws_sdk_bedrockruntime::config::Builder::default()
+                    .credentials_provider(credentials),
+            ))
         }
+        Some(_) => Err(CompositeLlmError::Unsupported(
+            "Bedrock backend only supports AWS credentials".to_string(),
+        )),
+    }
+}
 
-        let output = builder
-            .send()
-            .await
-            .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?;
+#[async_trait]
+impl ChatCompletionBackend for BedrockBackend {
+    fn name(&self) -> &str {
+        crate::BackendKind::Bedrock.as_str()
+    }
 
-        convert_converse_response(&output, &model)
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
     }
 
     async fn chat_completion_stream(
         &self,
         req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with_fields(req, opts)
+            .await
+            .map(|r| r.response)
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
     ) -> Result<ChatCompletionStream, CompositeLlmError> {
         let model = req.model.clone();
-        let (system_blocks, messages) = extract_system_and_messages(req.messages.clone())?;
-        let inference_config = build_inference_config(&req);
-        let tool_config = build_tool_config(&req)?;
+        let events = self.converse_stream_events(&req, opts).await?;
 
-        let mut builder = self
-            .client
-            .converse_stream()
-            .model_id(&self.model_id)
-            .set_messages(Some(messages));
+        let id = generate_chat_cmpl_id();
+        let mut state = StreamState::new(include_usage(&req));
 
-        if !system_blocks.is_empty() {
-            builder = builder.set_system(Some(system_blocks));
-        }
-        if let Some(config) = inference_config {
-            builder = builder.inference_config(config);
-        }
-        if let Some(tc) = tool_config {
-            builder = builder.tool_config(tc);
+        let chunks = events.filter_map(move |event| {
+            let chunk = match event {
+                Ok(event) => stream_event_to_response(&event, &model, &id, &mut state).map(Ok),
+                Err(e) => Some(Err(e)),
+            };
+            std::future::ready(chunk)
+        });
+
+        if self.stream_task {
+            Ok(spawn_stream_task(chunks, self.stream_buffer))
+        } else {
+            Ok(Box::pin(chunks))
         }
+    }
 
-        let mut output = builder
+    /// Lists the foundation models through the control-plane
+    /// `ListFoundationModels` API; needs the `bedrock-list-models` feature and
+    /// a control-plane client, see
+    /// [`with_control_client`](BedrockBackend::with_control_client).
+    #[cfg(feature = "bedrock-list-models")]
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        let Some(ref client) = self.control_client else {
+            return Err(CompositeLlmError::Unsupported(
+                "listing Bedrock models needs a control-plane client".to_string(),
+            ));
+        };
+        let output = client
+            .list_foundation_models()
             .send()
             .await
-            .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?;
+            .map_err(list_foundation_models_error)?;
+        Ok(output
+            .model_summaries()
+            .iter()
+            .map(|model| model.model_id().to_string())
+            .collect())
+    }
+}
 
-        let id = generate_chat_cmpl_id();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{BedrockCachePoint, BedrockManagedPrompt};
+    use async_openai::types::chat::{
+        ChatCompletionMessageToolCalls, FinishReason, StopConfiguration,
+    };
+    use aws_sdk_bedrockruntime::config::http::HttpResponse;
+    use aws_sdk_bedrockruntime::types::error::{
+        AccessDeniedException, ModelErrorException, ResourceNotFoundException, ThrottlingException,
+        ValidationException,
+    };
+    use aws_smithy_eventstream::frame::write_message_to;
+    use aws_smithy_types::body::SdkBody;
+    use aws_smithy_types::event_stream::{Header, HeaderValue, Message};
 
-        // Use a channel to bridge the async recv() loop into a Stream
-        let (tx, rx) = tokio::sync::mpsc::channel::<
-            Result<CreateChatCompletionStreamResponse, CompositeLlmError>,
-        >(32);
+    use crate::backend::test_server::serve_bytes;
+    use crate::convert::bedrock::BedrockStreamAccumulator;
+    use crate::request::ChatRequest;
 
-        tokio::spawn(async move {
-            loop {
-                match output.stream.recv().await {
-                    Ok(Some(event)) => {
-                        if let Some(resp) = stream_event_to_response(&event, &model, &id)
-                            && tx.send(Ok(resp)).await.is_err()
-                        {
-                            break;
-                        }
-                    }
-                    Ok(None) => break,
-                    Err(e) => {
-                        let _ = tx
-                            .send(Err(CompositeLlmError::Bedrock(e.to_string())))
-                            .await;
-                        break;
-                    }
+    fn service_error(err: ConverseStreamError, status: u16) -> SdkError<ConverseStreamError> {
+        SdkError::service_error(
+            err,
+            HttpResponse::new(status.try_into().unwrap(), SdkBody::empty()),
+        )
+    }
+
+    #[test]
+    fn test_stream_retry_delay_is_bounded() {
+        let first = stream_retry_delay(0);
+        assert!(first >= STREAM_RETRY_BASE_DELAY / 2 && first <= STREAM_RETRY_BASE_DELAY);
+        for attempt in [10, 31, 32, u32::MAX] {
+            let delay = stream_retry_delay(attempt);
+            assert!(delay >= STREAM_RETRY_MAX_DELAY / 2 && delay <= STREAM_RETRY_MAX_DELAY);
+        }
+    }
+
+    #[test]
+    fn test_converse_stream_error_kinds() {
+        let cases = [
+            (
+                ConverseStreamError::ThrottlingException(ThrottlingException::builder().build()),
+                429,
+                BedrockErrorKind::Throttling,
+                true,
+            ),
+            (
+                ConverseStreamError::ValidationException(ValidationException::builder().build()),
+                400,
+                BedrockErrorKind::Validation,
+                false,
+            ),
+            (
+                ConverseStreamError::ResourceNotFoundException(
+                    ResourceNotFoundException::builder().build(),
+                ),
+                404,
+                BedrockErrorKind::ResourceNotFound,
+                false,
+            ),
+            (
+                ConverseStreamError::AccessDeniedException(
+                    AccessDeniedException::builder().build(),
+                ),
+                403,
+                BedrockErrorKind::AccessDenied,
+                false,
+            ),
+            (
+                ConverseStreamError::ModelErrorException(ModelErrorException::builder().build()),
+                500,
+                BedrockErrorKind::Other,
+                true,
+            ),
+        ];
+
+        for (err, status, expected_kind, expected_retryable) in cases {
+            match converse_stream_error(service_error(err, status)) {
+                CompositeLlmError::Bedrock {
+                    kind, retryable, ..
+                } => {
+                    assert_eq!(kind, expected_kind);
+                    assert_eq!(retryable, expected_retryable);
                 }
+                other => panic!("unexpected error: {other}"),
             }
+        }
+    }
+
+    #[test]
+    fn test_converse_error_kind() {
+        let err = SdkError::service_error(
+            ConverseError::ResourceNotFoundException(ResourceNotFoundException::builder().build()),
+            HttpResponse::new(404.try_into().unwrap(), SdkBody::empty()),
+        );
+        assert!(matches!(
+            converse_error(err),
+            CompositeLlmError::Bedrock {
+                kind: BedrockErrorKind::ResourceNotFound,
+                retryable: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_error_message_from_service() {
+        let err = SdkError::service_error(
+            ConverseError::ValidationException(
+                ValidationException::builder()
+                    .message("maxTokens exceeds the model limit")
+                    .build(),
+            ),
+            HttpResponse::new(400.try_into().unwrap(), SdkBody::empty()),
+        );
+        match converse_error(err) {
+            CompositeLlmError::Bedrock { message, .. } => {
+                assert!(
+                    message.contains("maxTokens exceeds the model limit"),
+                    "{message}"
+                );
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_task_slow_consumer_keeps_every_chunk() {
+        let chunks = futures_util::stream::iter((0..100).map(|i| {
+            Ok(crate::convert::usage_chunk(
+                Default::default(),
+                "model",
+                &i.to_string(),
+            ))
+        }));
+        let mut stream = spawn_stream_task(chunks, 2);
+
+        let mut ids = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            if ids.len() % 10 == 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            ids.push(chunk.unwrap().id);
+        }
+        let expected: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_stream_task_stops_when_dropped() {
+        // Stands in for an upstream read that never yields; the sender is
+        // dropped with the upstream.
+        let (upstream, stopped) = tokio::sync::oneshot::channel::<()>();
+        let chunks = futures_util::stream::unfold(upstream, |upstream| async move {
+            std::future::pending::<()>().await;
+            Some((
+                Ok(crate::convert::usage_chunk(Default::default(), "", "")),
+                upstream,
+            ))
+        });
+
+        drop(spawn_stream_task(chunks, 1));
+        let stopped = tokio::time::timeout(Duration::from_secs(5), stopped).await;
+        assert!(matches!(stopped, Ok(Err(_))));
+    }
+
+    fn test_backend() -> BedrockBackend {
+        let config = aws_sdk_bedrockruntime::Config::builder()
+            .behavior_version(aws_sdk_bedrockruntime::config::BehaviorVersion::latest())
+            .build();
+        BedrockBackend::new(BedrockClient::from_conf(config), "model")
+    }
+
+    #[test]
+    fn test_build_request_keeps_system_and_tools() {
+        let backend = test_backend();
+        let req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "",
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "What's the weather?"}
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "parameters": {"type": "object", "properties": {}}
+                }
+            }]
+        }))
+        .unwrap();
+        let opts = ProviderOptions {
+            bedrock: Some(BedrockOptions {
+                cache_points: vec![BedrockCachePoint::AfterSystem],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let body = backend.build_request_with(&req, &opts).unwrap();
+        assert_eq!(
+            body["system"],
+            serde_json::json!([
+                {"text": "You are helpful."},
+                {"cachePoint": {"type": "default"}}
+            ])
+        );
+        assert_eq!(
+            body["toolConfig"]["tools"][0]["toolSpec"]["name"],
+            "get_weather"
+        );
+    }
+
+    #[test]
+    fn test_default_stop_sequences() {
+        let backend = test_backend().with_stop_sequences(["END", "Human:", "DONE"]);
+        let req = |stop: serde_json::Value| -> CreateChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": "",
+                "messages": [{"role": "user", "content": "Hi"}],
+                "stop": stop
+            }))
+            .unwrap()
+        };
+
+        let body = backend
+            .build_request(&req(serde_json::json!("END")))
+            .unwrap();
+        assert_eq!(
+            body["inferenceConfig"]["stopSequences"],
+            serde_json::json!(["END", "Human:", "DONE"])
+        );
+
+        // The request's sequences fill the limit first; extra defaults are dropped.
+        let body = backend
+            .build_request(&req(serde_json::json!(["A", "B", "C"])))
+            .unwrap();
+        assert_eq!(
+            body["inferenceConfig"]["stopSequences"],
+            serde_json::json!(["A", "B", "C", "END"])
+        );
+
+        // A request over the limit by itself is still rejected.
+        assert!(matches!(
+            backend.build_request(&req(serde_json::json!(["A", "B", "C", "D", "E"]))),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_latency_mode() {
+        let req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "",
+            "messages": [{"role": "user", "content": "Hi"}]
+        }))
+        .unwrap();
+
+        let body = test_backend().build_request(&req).unwrap();
+        assert!(body.get("performanceConfig").is_none());
+
+        let backend = test_backend().with_latency(LatencyMode::Optimized);
+        let body = backend.build_request(&req).unwrap();
+        assert_eq!(
+            body["performanceConfig"],
+            serde_json::json!({"latency": "optimized"})
+        );
+    }
+
+    #[test]
+    fn test_target_model_id() {
+        let backend = test_backend();
+        let arn = "arn:aws:bedrock:us-east-1:123456789012:prompt/PROMPT12345";
+
+        let opts = BedrockOptions::default();
+        assert_eq!(backend.target_model_id("", &opts, true).unwrap(), "model");
+        assert_eq!(
+            backend.target_model_id("other", &opts, true).unwrap(),
+            "model"
+        );
+        let per_request = backend.clone().with_request_model(true);
+        assert_eq!(
+            per_request.target_model_id("other", &opts, true).unwrap(),
+            "other"
+        );
+        assert_eq!(
+            per_request.target_model_id("", &opts, true).unwrap(),
+            "model"
+        );
+
+        let opts = BedrockOptions {
+            managed_prompt: Some(BedrockManagedPrompt::new(arn).with_variable("topic", "rust")),
+            ..Default::default()
+        };
+        assert_eq!(backend.target_model_id("other", &opts, false).unwrap(), arn);
+        assert!(matches!(
+            backend.target_model_id("other", &opts, true),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_empty_messages_are_rejected() {
+        let backend = test_backend();
+        let req = |messages: serde_json::Value| -> CreateChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({"model": "", "messages": messages})).unwrap()
+        };
+        let system_only = req(serde_json::json!([{"role": "system", "content": "Be brief."}]));
+
+        assert!(matches!(
+            backend.build_request(&req(serde_json::json!([]))),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(matches!(
+            backend.build_request(&system_only),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+
+        // A managed prompt brings its own messages.
+        let opts = ProviderOptions::default().with_bedrock(BedrockOptions {
+            managed_prompt: Some(BedrockManagedPrompt::new(
+                "arn:aws:bedrock:us-east-1:123456789012:prompt/PROMPT12345",
+            )),
+            ..Default::default()
         });
+        assert!(
+            backend
+                .build_request_with(&req(serde_json::json!([])), &opts)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_managed_prompt_ignores_backend_defaults() {
+        let backend = test_backend().with_stop_sequences(["END"]);
+        let arn = "arn:aws:bedrock:us-east-1:123456789012:prompt/PROMPT12345";
+        let opts = ProviderOptions::default().with_bedrock(BedrockOptions {
+            managed_prompt: Some(BedrockManagedPrompt::new(arn)),
+            cache_points: vec![BedrockCachePoint::AfterSystem],
+            ..Default::default()
+        });
+        let req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "",
+            "messages": [{"role": "user", "content": "Hi"}]
+        }))
+        .unwrap();
+
+        let json = backend.build_request_with(&req, &opts).unwrap();
+        assert_eq!(json["modelId"], arn);
+        assert!(json.get("inferenceConfig").is_none());
+        assert!(json.get("system").is_none());
+
+        // Without a managed prompt, the default stop sequence is sent.
+        let json = backend.build_request(&req).unwrap();
+        assert_eq!(
+            json["inferenceConfig"]["stopSequences"],
+            serde_json::json!(["END"])
+        );
+
+        // Stop sequences of the caller still conflict with the managed prompt.
+        let mut own_stop = req.clone();
+        own_stop.stop = Some(StopConfiguration::String("STOP".to_string()));
+        assert!(matches!(
+            backend.build_request_with(&own_stop, &opts),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    /// Encodes `(event type, payload)` pairs as a Converse stream body.
+    fn event_stream_body(events: &[(&str, serde_json::Value)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (event_type, payload) in events {
+            let message = Message::new(serde_json::to_vec(payload).unwrap())
+                .add_header(Header::new(
+                    ":message-type",
+                    HeaderValue::String("event".into()),
+                ))
+                .add_header(Header::new(
+                    ":event-type",
+                    HeaderValue::String(event_type.to_string().into()),
+                ))
+                .add_header(Header::new(
+                    ":content-type",
+                    HeaderValue::String("application/json".into()),
+                ));
+            write_message_to(&message, &mut body).unwrap();
+        }
+        body
+    }
+
+    #[tokio::test]
+    async fn test_stream_accumulator_through_backend() {
+        let body = event_stream_body(&[
+            ("messageStart", serde_json::json!({"role": "assistant"})),
+            (
+                "contentBlockDelta",
+                serde_json::json!({"contentBlockIndex": 0, "delta": {"text": "Checking."}}),
+            ),
+            (
+                "contentBlockStop",
+                serde_json::json!({"contentBlockIndex": 0}),
+            ),
+            (
+                "contentBlockStart",
+                serde_json::json!({
+                    "contentBlockIndex": 1,
+                    "start": {"toolUse": {"toolUseId": "tooluse_a", "name": "get_weather"}}
+                }),
+            ),
+            (
+                "contentBlockDelta",
+                serde_json::json!({"contentBlockIndex": 1, "delta": {"toolUse": {"input": "{\"city\": "}}}),
+            ),
+            (
+                "contentBlockDelta",
+                serde_json::json!({"contentBlockIndex": 1, "delta": {"toolUse": {"input": "\"Paris\"}"}}}),
+            ),
+            (
+                "contentBlockStop",
+                serde_json::json!({"contentBlockIndex": 1}),
+            ),
+            ("messageStop", serde_json::json!({"stopReason": "tool_use"})),
+            (
+                "metadata",
+                serde_json::json!({
+                    "usage": {"inputTokens": 10, "outputTokens": 5, "totalTokens": 15},
+                    "metrics": {"latencyMs": 100}
+                }),
+            ),
+        ]);
+        let (base, requests) =
+            serve_bytes("200 OK", "application/vnd.amazon.eventstream", body).await;
+        let config = aws_sdk_bedrockruntime::Config::builder()
+            .behavior_version(aws_sdk_bedrockruntime::config::BehaviorVersion::latest())
+            .region(aws_sdk_bedrockruntime::config::Region::new("us-east-1"))
+            .credentials_provider(aws_sdk_bedrockruntime::config::Credentials::new(
+                "akid", "secret", None, None, "test",
+            ))
+            .endpoint_url(base)
+            .build();
+        let backend = BedrockBackend::new(BedrockClient::from_conf(config), "model");
+        let req = ChatRequest::new("").user("Weather in Paris?").build();
+
+        let mut events = backend
+            .converse_stream_events(&req, &ProviderOptions::default())
+            .await
+            .unwrap();
+        let mut acc = BedrockStreamAccumulator::new();
+        while let Some(event) = events.next().await {
+            acc.push(&event.unwrap());
+        }
+        let resp = acc.into_openai_response("model", "chatcmpl-1");
+
+        let choice = &resp.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(choice.message.content.as_deref(), Some("Checking."));
+        match choice.message.tool_calls.as_deref() {
+            Some([ChatCompletionMessageToolCalls::Function(call)]) => {
+                assert_eq!(call.id, "tooluse_a");
+                assert_eq!(call.function.arguments, "{\"city\": \"Paris\"}");
+            }
+            other => panic!("unexpected tool calls: {other:?}"),
+        }
+        assert_eq!(resp.usage.unwrap().total_tokens, 15);
+
+        let requests = requests.lock().unwrap().clone();
+        assert!(requests[0].starts_with("post /model/model/converse-stream "));
+    }
+
+    #[cfg(feature = "bedrock-list-models")]
+    #[tokio::test]
+    async fn test_list_models() {
+        let (base, requests) = serve_bytes(
+            "200 OK",
+            "application/json",
+            serde_json::json!({
+                "modelSummaries": [
+                    {"modelArn": "arn:aws:bedrock:us-east-1::foundation-model/a", "modelId": "a"},
+                    {"modelArn": "arn:aws:bedrock:us-east-1::foundation-model/b", "modelId": "b"}
+                ]
+            })
+            .to_string()
+            .into_bytes(),
+        )
+        .await;
+        let config = aws_sdk_bedrock::Config::builder()
+            .behavior_version(aws_sdk_bedrock::config::BehaviorVersion::latest())
+            .region(aws_sdk_bedrock::config::Region::new("us-east-1"))
+            .credentials_provider(aws_sdk_bedrock::config::Credentials::new(
+                "akid", "secret", None, None, "test",
+            ))
+            .endpoint_url(base)
+            .build();
+        let backend = test_backend().with_control_client(BedrockControlClient::from_conf(config));
+
+        assert_eq!(backend.list_models().await.unwrap(), vec!["a", "b"]);
+        let requests = requests.lock().unwrap().clone();
+        assert!(requests[0].starts_with("get /foundation-models "));
 
-        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx)))
+        assert!(matches!(
+            test_backend().list_models().await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
     }
 }
@@ -0,0 +1,267 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::{ChatCompletionBackend, ChatCompletionStream};
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// A backend that stops calling a failing backend for a while.
+///
+/// After `failure_threshold` consecutive failures within `window`, the circuit
+/// opens and every call fails immediately with
+/// [`CompositeLlmError::CircuitOpen`]. Once `cooldown` has elapsed the circuit
+/// is half-open: a single probe request is let through, closing the circuit
+/// on success or reopening it for another cooldown on failure.
+///
+/// For streaming, only establishing the stream counts; errors yielded later
+/// by the stream are not tracked.
+pub struct CircuitBreakerBackend {
+    backend: Box<dyn ChatCompletionBackend>,
+    failure_threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    failures: u32,
+    first_failure: Option<Instant>,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+/// Marks a call admitted by the breaker; a probe that is dropped before
+/// completing releases the half-open slot.
+struct Permit<'a> {
+    breaker: &'a CircuitBreakerBackend,
+    probe: bool,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.lock().probing = false;
+        }
+    }
+}
+
+impl CircuitBreakerBackend {
+    /// Wraps `backend` with the default settings: open after 5 failures within
+    /// 60 seconds, probe again after 30 seconds.
+    pub fn new(backend: Box<dyn ChatCompletionBackend>) -> Self {
+        Self {
+            backend,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            window: DEFAULT_WINDOW,
+            cooldown: DEFAULT_COOLDOWN,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Sets the number of consecutive failures that opens the circuit.
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// Sets the window the consecutive failures must fall within.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets how long the circuit stays open before probing the backend.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn acquire(&self) -> Result<Permit<'_>, CompositeLlmError> {
+        let mut state = self.lock();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(Permit {
+                breaker: self,
+                probe: false,
+            });
+        };
+
+        if opened_at.elapsed() < self.cooldown || state.probing {
+            return Err(CompositeLlmError::CircuitOpen);
+        }
+        state.probing = true;
+        Ok(Permit {
+            breaker: self,
+            probe: true,
+        })
+    }
+
+    fn record<T>(&self, permit: Permit<'_>, result: &Result<T, CompositeLlmError>) {
+        let mut state = self.lock();
+        let now = Instant::now();
+        match result {
+            Ok(_) => *state = State::default(),
+            Err(_) if permit.probe => {
+                state.opened_at = Some(now);
+                tracing::debug!("circuit breaker probe failed; reopening");
+            }
+            Err(_) => {
+                match state.first_failure {
+                    Some(first) if now.duration_since(first) <= self.window => state.failures += 1,
+                    _ => {
+                        state.first_failure = Some(now);
+                        state.failures = 1;
+                    }
+                }
+                if state.failures >= self.failure_threshold && state.opened_at.is_none() {
+                    state.opened_at = Some(now);
+                    tracing::debug!(failures = state.failures, "circuit breaker opened");
+                }
+            }
+        }
+        drop(state);
+        drop(permit);
+    }
+}
+
+#[async_trait]
+impl ChatCompletionBackend for CircuitBreakerBackend {
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        let permit = self.acquire()?;
+        let result = self.backend.chat_completion_with(req, opts).await;
+        self.record(permit, &result);
+        result
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let permit = self.acquire()?;
+        let result = self.backend.chat_completion_stream_with(req, opts).await;
+        self.record(permit, &result);
+        result
+    }
+
+    /// Checks the wrapped backend directly; the result is not recorded and
+    /// an open circuit does not block it.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.backend.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        self.backend.list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    use super::*;
+
+    struct FlakyBackend {
+        healthy: Arc<AtomicBool>,
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl ChatCompletionBackend for FlakyBackend {
+        async fn chat_completion(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.healthy.load(Ordering::SeqCst) {
+                Ok(serde_json::from_value(serde_json::json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "created": 0,
+                    "model": "test",
+                    "choices": []
+                }))?)
+            } else {
+                Err(CompositeLlmError::Unsupported("down".to_string()))
+            }
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<ChatCompletionStream, CompositeLlmError> {
+            Err(CompositeLlmError::Unsupported("no streaming".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_opens_after_threshold_and_recovers() {
+        let healthy = Arc::new(AtomicBool::new(false));
+        let calls = Arc::new(AtomicU32::new(0));
+        let breaker = CircuitBreakerBackend::new(Box::new(FlakyBackend {
+            healthy: healthy.clone(),
+            calls: calls.clone(),
+        }))
+        .with_failure_threshold(2)
+        .with_cooldown(Duration::from_millis(20));
+        let req = CreateChatCompletionRequest::default;
+
+        for _ in 0..2 {
+            let err = breaker.chat_completion(req()).await.unwrap_err();
+            assert!(matches!(err, CompositeLlmError::Unsupported(_)));
+        }
+        let err = breaker.chat_completion(req()).await.unwrap_err();
+        assert!(matches!(err, CompositeLlmError::CircuitOpen));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // A failed probe reopens the circuit.
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(breaker.chat_completion(req()).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let err = breaker.chat_completion(req()).await.unwrap_err();
+        assert!(matches!(err, CompositeLlmError::CircuitOpen));
+
+        // A successful probe closes it.
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(breaker.chat_completion(req()).await.is_ok());
+        assert!(breaker.chat_completion(req()).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,289 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures_core::Stream;
+
+use super::{ChatCompletionBackend, ChatCompletionStream};
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+
+/// Counter of finished requests, labelled `backend`, `model` and `status`.
+pub const REQUESTS_TOTAL: &str = "composite_llm_requests_total";
+/// Histogram of request durations in seconds, with the same labels as
+/// [`REQUESTS_TOTAL`].
+pub const REQUEST_DURATION_SECONDS: &str = "composite_llm_request_duration_seconds";
+
+/// A backend that records metrics for every request to another backend.
+///
+/// Metrics go through the [`metrics`] facade, so they reach whichever
+/// recorder (e.g. a Prometheus exporter) the application installed; without
+/// one they are discarded. Each request increments [`REQUESTS_TOTAL`] and
+/// records its duration in [`REQUEST_DURATION_SECONDS`], labelled with:
+///
+/// - `backend`: [`ChatCompletionBackend::name`] of the wrapped backend,
+/// - `model`: the requested model, empty when the backend's default is used,
+/// - `status`: `ok`, `error`, or `cancelled` for a stream dropped before it
+///   ended.
+///
+/// A streaming request is recorded when its stream ends, so its duration
+/// covers the whole response and a mid-stream error counts as `error`.
+pub struct MeteredBackend {
+    backend: Box<dyn ChatCompletionBackend>,
+}
+
+impl MeteredBackend {
+    /// Wraps `backend`.
+    pub fn new(backend: Box<dyn ChatCompletionBackend>) -> Self {
+        Self { backend }
+    }
+}
+
+/// The labels and start time of one request.
+struct Measurement {
+    backend: String,
+    model: String,
+    start: Instant,
+}
+
+impl Measurement {
+    fn start(backend: &str, req: &CreateChatCompletionRequest) -> Self {
+        Self {
+            backend: backend.to_string(),
+            model: req.model.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    fn record(self, status: &'static str) {
+        let labels = [
+            ("backend", self.backend),
+            ("model", self.model),
+            ("status", status.to_string()),
+        ];
+        metrics::counter!(REQUESTS_TOTAL, &labels).increment(1);
+        metrics::histogram!(REQUEST_DURATION_SECONDS, &labels)
+            .record(self.start.elapsed().as_secs_f64());
+    }
+
+    fn record_result<T>(self, result: &Result<T, CompositeLlmError>) {
+        self.record(if result.is_ok() { "ok" } else { "error" });
+    }
+}
+
+#[async_trait]
+impl ChatCompletionBackend for MeteredBackend {
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        let measurement = Measurement::start(self.name(), &req);
+        let result = self.backend.chat_completion_with(req, opts).await;
+        measurement.record_result(&result);
+        result
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let measurement = Measurement::start(self.name(), &req);
+        match self.backend.chat_completion_stream_with(req, opts).await {
+            Ok(inner) => Ok(Box::pin(MeteredStream {
+                inner,
+                measurement: Some(measurement),
+                failed: false,
+            })),
+            Err(e) => {
+                measurement.record("error");
+                Err(e)
+            }
+        }
+    }
+
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.backend.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        self.backend.list_models().await
+    }
+}
+
+/// Records a streaming request once its stream ends or is dropped.
+struct MeteredStream {
+    inner: ChatCompletionStream,
+    measurement: Option<Measurement>,
+    failed: bool,
+}
+
+impl Stream for MeteredStream {
+    type Item = <ChatCompletionStream as Stream>::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let poll = this.inner.as_mut().poll_next(cx);
+        match poll {
+            Poll::Ready(Some(Err(_))) => this.failed = true,
+            Poll::Ready(None) => {
+                if let Some(measurement) = this.measurement.take() {
+                    measurement.record(if this.failed { "error" } else { "ok" });
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for MeteredStream {
+    fn drop(&mut self) {
+        if let Some(measurement) = self.measurement.take() {
+            measurement.record(if self.failed { "error" } else { "cancelled" });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use futures_util::StreamExt;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use super::*;
+
+    /// Keeps every counter by its name and labels.
+    #[derive(Default)]
+    struct TestRecorder {
+        counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    }
+
+    struct TestCounter(Arc<AtomicU64>);
+
+    impl CounterFn for TestCounter {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::Relaxed);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+    }
+
+    impl TestRecorder {
+        fn count(&self, key: &str) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .get(key)
+                .map_or(0, |c| c.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let labels: Vec<_> = key
+                .labels()
+                .map(|l| format!("{}={}", l.key(), l.value()))
+                .collect();
+            let name = format!("{}{{{}}}", key.name(), labels.join(","));
+            let counter = self
+                .counters
+                .lock()
+                .unwrap()
+                .entry(name)
+                .or_default()
+                .clone();
+            Counter::from_arc(Arc::new(TestCounter(counter)))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    struct StubBackend;
+
+    #[async_trait]
+    impl ChatCompletionBackend for StubBackend {
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+            Err(CompositeLlmError::Unsupported("stub".to_string()))
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<ChatCompletionStream, CompositeLlmError> {
+            Ok(Box::pin(futures_util::stream::empty()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_requests() {
+        let recorder = TestRecorder::default();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let backend = MeteredBackend::new(Box::new(StubBackend));
+        let req = || CreateChatCompletionRequest {
+            model: "m".to_string(),
+            ..Default::default()
+        };
+
+        assert!(backend.chat_completion(req()).await.is_err());
+
+        let mut stream = backend.chat_completion_stream(req()).await.unwrap();
+        assert!(stream.next().await.is_none());
+        drop(stream);
+
+        drop(backend.chat_completion_stream(req()).await.unwrap());
+
+        let key = |status| format!("{REQUESTS_TOTAL}{{backend=stub,model=m,status={status}}}");
+        assert_eq!(recorder.count(&key("error")), 1);
+        assert_eq!(recorder.count(&key("ok")), 1);
+        assert_eq!(recorder.count(&key("cancelled")), 1);
+    }
+}
@@ -1,12 +1,16 @@
 use std::pin::Pin;
 
 use async_openai::types::chat::{
-    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+    ChatCompletionRequestMessage, ChatCompletionRequestUserMessage, CreateChatCompletionRequest,
+    CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
 };
 use async_trait::async_trait;
 use futures_core::Stream;
+use futures_util::future::Either;
+use tokio_util::sync::CancellationToken;
 
 use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
 
 #[cfg(feature = "backend-openai")]
 pub mod openai;
@@ -20,9 +24,26 @@ pub mod bedrock;
 #[cfg(feature = "backend-vertex")]
 pub mod vertex;
 
+pub mod circuit_breaker;
+#[cfg(feature = "metrics")]
+pub mod metered;
+pub mod race;
+pub mod rate_limit;
+pub mod transform;
+
 /// A pinned, boxed stream of chat completion stream responses.
 ///
 /// This type aliases a `Stream` that yields `Result<CreateChatCompletionStreamResponse, CompositeLlmError>`.
+///
+/// Every chunk of a stream carries the same completion `id`, so it can be read
+/// from the first chunk. Bedrock and Vertex AI synthesize the id before the
+/// request is sent and Bedrock emits an opening role chunk as soon as the
+/// model starts responding. Their chunks also share one `created` time, taken
+/// when the stream starts; OpenAI and Azure report their own.
+///
+/// Chunks for different choices are distinguished by `choice.index`; with
+/// `n > 1` OpenAI, Azure and Vertex AI interleave them. Bedrock only ever
+/// produces choice index 0.
 pub type ChatCompletionStream = Pin<
     Box<dyn Stream<Item = Result<CreateChatCompletionStreamResponse, CompositeLlmError>> + Send>,
 >;
@@ -33,6 +54,14 @@ pub type ChatCompletionStream = Pin<
 /// to be usable with `CompositeClient`.
 #[async_trait]
 pub trait ChatCompletionBackend: Send + Sync {
+    /// A short name identifying the backend in logs and metrics, e.g.
+    /// `"bedrock"`. Wrappers of one backend report the backend they wrap;
+    /// wrappers of several, such as [`RaceBackend`](race::RaceBackend),
+    /// report their own name.
+    fn name(&self) -> &str {
+        "custom"
+    }
+
     /// Sends a chat completion request to the backend.
     async fn chat_completion(
         &self,
@@ -44,4 +73,295 @@ pub trait ChatCompletionBackend: Send + Sync {
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionStream, CompositeLlmError>;
+
+    /// Sends a chat completion request with provider-specific options.
+    ///
+    /// Backends that have no provider-specific options ignore `opts`.
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        let _ = opts;
+        self.chat_completion(req).await
+    }
+
+    /// Sends a streaming chat completion request with provider-specific options.
+    ///
+    /// Backends that have no provider-specific options ignore `opts`.
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let _ = opts;
+        self.chat_completion_stream(req).await
+    }
+
+    /// Sends a streaming chat completion request that ends as soon as `token`
+    /// is cancelled; see [`stream::cancellable`](crate::stream::cancellable).
+    ///
+    /// Cancelling while the stream is being established abandons the
+    /// request and returns an empty stream. This is deliberate, so that
+    /// cancellation ends the stream the same way whenever it happens; callers
+    /// that need to tell it apart from a model that produced nothing check
+    /// `token.is_cancelled()`.
+    async fn chat_completion_stream_cancellable(
+        &self,
+        req: CreateChatCompletionRequest,
+        token: CancellationToken,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let cancelled = Box::pin(token.clone().cancelled_owned());
+        match futures_util::future::select(cancelled, self.chat_completion_stream(req)).await {
+            Either::Left(_) => Ok(Box::pin(futures_util::stream::empty())),
+            Either::Right((stream, _)) => Ok(crate::stream::cancellable(stream?, token)),
+        }
+    }
+
+    /// Checks that the backend is reachable and its credentials are valid.
+    ///
+    /// The default implementation sends a one-token completion with the
+    /// backend's default model; backends override it with a cheaper call
+    /// where one exists.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.chat_completion(ping_request()).await.map(|_| ())
+    }
+
+    /// Lists the IDs of the models the backend offers, e.g. for a model
+    /// picker.
+    ///
+    /// Backends that cannot enumerate their models return
+    /// [`CompositeLlmError::Unsupported`], as the default implementation does.
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        Err(CompositeLlmError::Unsupported(format!(
+            "the {} backend cannot list models",
+            self.name()
+        )))
+    }
+}
+
+/// Rejects a request without any message, which every provider fails with
+/// its own, less clear error.
+pub(crate) fn check_messages(req: &CreateChatCompletionRequest) -> Result<(), CompositeLlmError> {
+    if req.messages.is_empty() {
+        return Err(CompositeLlmError::Unsupported(
+            "messages must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The span a backend runs a request in, recording the backend, the requested
+/// model and the caller's [`ProviderOptions::correlation_id`].
+pub(crate) fn request_span(
+    backend: &str,
+    req: &CreateChatCompletionRequest,
+    opts: &ProviderOptions,
+) -> tracing::Span {
+    tracing::info_span!(
+        "chat_completion",
+        backend,
+        model = %req.model,
+        correlation_id = opts.correlation_id.as_deref(),
+    )
+}
+
+/// The smallest useful request: a one-word user message limited to one token.
+pub(crate) fn ping_request() -> CreateChatCompletionRequest {
+    CreateChatCompletionRequest {
+        messages: vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessage::from("ping"),
+        )],
+        max_completion_tokens: Some(1),
+        ..Default::default()
+    }
+}
+
+/// Incrementally decodes a server-sent event stream.
+///
+/// Bytes are fed with [`push`](Self::push) as they arrive, split anywhere.
+/// Lines may end in LF or CRLF. When a blank line completes an event, its
+/// `data:` lines are joined with `\n` and handed to the parser; events it
+/// returns `None` for are skipped, as are comments and other fields. A
+/// `[DONE]` event ends the stream: later input is ignored and
+/// [`is_done`](Self::is_done) turns true.
+pub struct SseDecoder<T, F> {
+    parse: F,
+    /// The incomplete last line.
+    line: Vec<u8>,
+    /// Data of the event being read, if it has any `data:` line yet.
+    data: Option<String>,
+    done: bool,
+    _output: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, F> SseDecoder<T, F>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    /// Creates a decoder handing each event's data to `parse`.
+    pub fn new(parse: F) -> Self {
+        Self {
+            parse,
+            line: Vec::new(),
+            data: None,
+            done: false,
+            _output: std::marker::PhantomData,
+        }
+    }
+
+    /// Feeds `bytes` and returns the events they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<T> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            if self.done {
+                break;
+            }
+            if byte == b'\n' {
+                let mut line = std::mem::take(&mut self.line);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                self.process_line(&String::from_utf8_lossy(&line), &mut events);
+            } else {
+                self.line.push(byte);
+            }
+        }
+        events
+    }
+
+    /// Ends the input and returns the last event, which may lack its
+    /// terminating blank line.
+    pub fn finish(&mut self) -> Vec<T> {
+        let mut events = Vec::new();
+        if !self.done {
+            let line = std::mem::take(&mut self.line);
+            self.process_line(&String::from_utf8_lossy(&line), &mut events);
+            self.process_line("", &mut events);
+        }
+        events
+    }
+
+    /// Whether the `[DONE]` event has been received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn process_line(&mut self, line: &str, events: &mut Vec<T>) {
+        if line.is_empty() {
+            if let Some(data) = self.data.take() {
+                if data == "[DONE]" {
+                    self.done = true;
+                } else if let Some(event) = (self.parse)(&data) {
+                    events.push(event);
+                }
+            }
+            return;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        if field == "data" {
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match self.data {
+                Some(ref mut data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            }
+        }
+    }
+}
+
+/// A local HTTP server for tests of the HTTP backends.
+#[cfg(all(
+    test,
+    any(
+        feature = "backend-openai",
+        feature = "backend-azure",
+        feature = "backend-bedrock"
+    )
+))]
+mod test_server {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `status` and a JSON `body` to every connection on a local port,
+    /// and collects the head of each request, lowercased.
+    #[cfg(any(feature = "backend-openai", feature = "backend-azure"))]
+    pub(super) async fn serve(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        serve_bytes(status, "application/json", body.as_bytes().to_vec()).await
+    }
+
+    /// Like [`serve`], with a `body` of any content type.
+    pub(super) async fn serve_bytes(
+        status: &'static str,
+        content_type: &'static str,
+        body: Vec<u8>,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = String::from_utf8_lossy(&request).to_lowercase();
+                received.lock().unwrap().push(head);
+                let response = format!(
+                    "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        (base, requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder() -> SseDecoder<String, impl Fn(&str) -> Option<String>> {
+        SseDecoder::new(|data: &str| (data != "skip").then(|| data.to_string()))
+    }
+
+    #[test]
+    fn test_sse_decoder_split_input_and_crlf() {
+        let mut decoder = decoder();
+        assert!(decoder.push(b"data: one\r").is_empty());
+        assert_eq!(decoder.push(b"\n\r\ndata: tw"), vec!["one"]);
+        assert_eq!(
+            decoder.push(b"o\n\n: comment\nevent: x\ndata: skip\n\n"),
+            vec!["two"]
+        );
+        assert_eq!(decoder.push(b"data: a\ndata:b\n\n"), vec!["a\nb"]);
+        assert!(decoder.push(b"data: last").is_empty());
+        assert_eq!(decoder.finish(), vec!["last"]);
+    }
+
+    #[test]
+    fn test_sse_decoder_done() {
+        let mut decoder = decoder();
+        assert_eq!(
+            decoder.push(b"data: one\n\ndata: [DONE]\n\ndata: after\n\n"),
+            vec!["one"]
+        );
+        assert!(decoder.is_done());
+        assert!(decoder.finish().is_empty());
+    }
 }
@@ -1,23 +1,52 @@
-use async_openai::{Client, config::OpenAIConfig};
+use async_openai::traits::RequestOptionsBuilder;
+use async_openai::{Chat, Client, config::OpenAIConfig};
 use async_trait::async_trait;
-use tokio_stream::StreamExt;
+use futures_util::TryStreamExt;
+use tracing::Instrument;
 
-use super::{ChatCompletionBackend, ChatCompletionStream};
+use super::{ChatCompletionBackend, ChatCompletionStream, check_messages, request_span};
 use crate::error::CompositeLlmError;
-use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+use crate::options::{Credentials, ProviderOptions};
+use crate::stream::CompletionStream;
+use async_openai::error::OpenAIError;
+use async_openai::types::chat::{
+    ChatCompletionResponseStream, CreateChatCompletionRequest, CreateChatCompletionResponse,
+};
+
+/// The concrete stream type of [`OpenAIBackend::chat_completion_stream_typed`].
+pub type OpenAIStream = futures_util::stream::MapErr<
+    ChatCompletionResponseStream,
+    fn(OpenAIError) -> CompositeLlmError,
+>;
 
 /// A backend implementation for OpenAI.
 ///
 /// This backend uses the `async-openai` crate to communicate with the OpenAI API.
+#[derive(Clone)]
 pub struct OpenAIBackend {
     client: Client<OpenAIConfig>,
+    reasoning_model_prefixes: Vec<String>,
+}
+
+/// Model name prefixes of reasoning models that reject sampling parameters.
+const DEFAULT_REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4"];
+
+fn default_reasoning_model_prefixes() -> Vec<String> {
+    DEFAULT_REASONING_MODEL_PREFIXES
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
 }
 
 impl OpenAIBackend {
     /// Creates a new `OpenAIBackend` with the given configuration.
+    ///
+    /// Extra headers such as a custom `User-Agent` are set on the config with
+    /// [`OpenAIConfig::with_header`].
     pub fn new(config: OpenAIConfig) -> Self {
         Self {
             client: Client::with_config(config),
+            reasoning_model_prefixes: default_reasoning_model_prefixes(),
         }
     }
 
@@ -28,34 +57,286 @@ impl OpenAIBackend {
     pub fn from_env() -> Self {
         Self {
             client: Client::new(),
+            reasoning_model_prefixes: default_reasoning_model_prefixes(),
+        }
+    }
+
+    /// Returns the underlying `async-openai` client, for endpoints other than
+    /// chat completions (files, fine-tuning, batches).
+    pub fn client(&self) -> &Client<OpenAIConfig> {
+        &self.client
+    }
+
+    /// Sets the model name prefixes treated as reasoning models.
+    ///
+    /// `temperature`, `top_p`, `frequency_penalty` and `presence_penalty` are
+    /// stripped from requests whose model starts with one of these prefixes,
+    /// since those models reject them. Defaults to `o1`, `o3` and `o4`; pass
+    /// an empty list to send requests unchanged.
+    pub fn with_reasoning_model_prefixes<I, S>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.reasoning_model_prefixes = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Removes sampling parameters unsupported by reasoning models.
+    fn sanitize(&self, mut req: CreateChatCompletionRequest) -> CreateChatCompletionRequest {
+        if !self
+            .reasoning_model_prefixes
+            .iter()
+            .any(|p| req.model.starts_with(p.as_str()))
+        {
+            return req;
+        }
+
+        let mut stripped = Vec::new();
+        if req.temperature.take().is_some() {
+            stripped.push("temperature");
+        }
+        if req.top_p.take().is_some() {
+            stripped.push("top_p");
+        }
+        if req.frequency_penalty.take().is_some() {
+            stripped.push("frequency_penalty");
+        }
+        if req.presence_penalty.take().is_some() {
+            stripped.push("presence_penalty");
+        }
+        if !stripped.is_empty() {
+            tracing::debug!(
+                model = %req.model,
+                stripped = ?stripped,
+                "removed parameters unsupported by reasoning model"
+            );
+        }
+        req
+    }
+
+    /// Returns the request body `req` would be sent as, without sending it.
+    pub fn build_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        Ok(serde_json::to_value(self.sanitize(req.clone()))?)
+    }
+
+    /// Like [`chat_completion_stream_with`](ChatCompletionBackend::chat_completion_stream_with),
+    /// returning the stream without boxing it again.
+    pub async fn chat_completion_stream_typed(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CompletionStream<OpenAIStream>, CompositeLlmError> {
+        check_messages(&req)?;
+        let span = request_span(self.name(), &req, opts);
+        let stream = self
+            .chat(opts)?
+            .create_stream(self.sanitize(req))
+            .instrument(span)
+            .await
+            .map_err(CompositeLlmError::from)?;
+
+        let map_err: fn(OpenAIError) -> CompositeLlmError = CompositeLlmError::from;
+        Ok(CompletionStream::new(stream.map_err(map_err)))
+    }
+
+    /// Returns the chat API, authenticated with per-request credentials if given.
+    fn chat(&self, opts: &ProviderOptions) -> Result<Chat<'_, OpenAIConfig>, CompositeLlmError> {
+        let chat = self.client.chat();
+        match opts.credentials {
+            None => Ok(chat),
+            Some(Credentials::ApiKey(ref key)) => {
+                Ok(chat.header("authorization", format!("Bearer {key}"))?)
+            }
+            Some(_) => Err(CompositeLlmError::Unsupported(
+                "OpenAI backend only supports API key credentials".to_string(),
+            )),
         }
     }
 }
 
 #[async_trait]
 impl ChatCompletionBackend for OpenAIBackend {
+    fn name(&self) -> &str {
+        crate::BackendKind::OpenAI.as_str()
+    }
+
     async fn chat_completion(
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
-        self.client
-            .chat()
-            .create(req)
+        self.chat_completion_with(req, &ProviderOptions::default())
             .await
-            .map_err(CompositeLlmError::from)
     }
 
     async fn chat_completion_stream(
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionStream, CompositeLlmError> {
-        let stream = self
-            .client
-            .chat()
-            .create_stream(req)
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
             .await
-            .map_err(CompositeLlmError::from)?;
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        check_messages(&req)?;
+        let span = request_span(self.name(), &req, opts);
+        self.chat(opts)?
+            .create(self.sanitize(req))
+            .instrument(span)
+            .await
+            .map_err(CompositeLlmError::from)
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        Ok(self
+            .chat_completion_stream_typed(req, opts)
+            .await?
+            .into_boxed())
+    }
+
+    /// Lists the available models, which costs no tokens.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.client.models().list().await?;
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        let models = self.client.models().list().await?;
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_openai::types::chat::ReasoningEffort;
+
+    use super::*;
+    use crate::backend::test_server::serve;
+    use crate::request::ChatRequest;
+
+    #[test]
+    fn test_reasoning_effort_is_forwarded() {
+        let backend = OpenAIBackend::new(OpenAIConfig::new());
+        let req = CreateChatCompletionRequest {
+            model: "o3-mini".to_string(),
+            messages: vec![],
+            reasoning_effort: Some(ReasoningEffort::High),
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+
+        // Sanitizing for the reasoning model drops the temperature but keeps the effort.
+        let json = backend.build_request(&req).unwrap();
+        assert_eq!(json["reasoning_effort"], "high");
+        assert!(json.get("temperature").is_none());
+    }
+
+    #[test]
+    fn test_parallel_tool_calls_is_forwarded() {
+        let backend = OpenAIBackend::new(OpenAIConfig::new());
+        let req = CreateChatCompletionRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![],
+            parallel_tool_calls: Some(false),
+            ..Default::default()
+        };
+
+        let json = backend.build_request(&req).unwrap();
+        assert_eq!(json["parallel_tool_calls"], false);
+    }
+
+    #[test]
+    fn test_sanitize_strips_sampling_params_for_reasoning_models() {
+        let backend = OpenAIBackend::new(OpenAIConfig::new());
+        let req = |model: &str| CreateChatCompletionRequest {
+            model: model.to_string(),
+            messages: vec![],
+            temperature: Some(0.2),
+            top_p: Some(0.9),
+            frequency_penalty: Some(0.5),
+            presence_penalty: Some(0.5),
+            reasoning_effort: Some(ReasoningEffort::Low),
+            ..Default::default()
+        };
+
+        let sanitized = backend.sanitize(req("o3-mini"));
+        assert_eq!(sanitized.temperature, None);
+        assert_eq!(sanitized.top_p, None);
+        assert_eq!(sanitized.frequency_penalty, None);
+        assert_eq!(sanitized.presence_penalty, None);
+        assert_eq!(sanitized.reasoning_effort, Some(ReasoningEffort::Low));
+
+        let sanitized = backend.sanitize(req("o4-mini"));
+        assert_eq!(sanitized.temperature, None);
+        assert_eq!(sanitized.top_p, None);
+
+        let sanitized = backend.sanitize(req("gpt-4o"));
+        assert_eq!(sanitized.temperature, Some(0.2));
+
+        let backend = backend.with_reasoning_model_prefixes(Vec::<String>::new());
+        let sanitized = backend.sanitize(req("o1"));
+        assert_eq!(sanitized.temperature, Some(0.2));
+    }
+
+    #[tokio::test]
+    async fn test_per_request_api_key() {
+        let (base, requests) = serve(
+            "400 Bad Request",
+            r#"{"error":{"message":"stop","type":"invalid_request_error","param":null,"code":null}}"#,
+        )
+        .await;
+        let backend = OpenAIBackend::new(
+            OpenAIConfig::new()
+                .with_api_base(base)
+                .with_api_key("configured"),
+        );
+        let req = || ChatRequest::new("gpt-4o").user("Hi").build();
+
+        let opts = ProviderOptions::default()
+            .with_credentials(Credentials::ApiKey("per-request".to_string()));
+        assert!(backend.chat_completion_with(req(), &opts).await.is_err());
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("authorization: bearer per-request\r\n"));
+        assert_eq!(requests[0].matches("authorization:").count(), 1);
+        assert!(!requests[0].contains("configured"));
+
+        let opts = ProviderOptions::default()
+            .with_credentials(Credentials::BearerToken("token".to_string()));
+        assert!(matches!(
+            backend.chat_completion_with(req(), &opts).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(matches!(
+            backend.chat_completion_stream_with(req(), &opts).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_empty_messages_are_rejected() {
+        let backend = OpenAIBackend::new(OpenAIConfig::new());
+        let req = CreateChatCompletionRequest::default;
 
-        Ok(Box::pin(stream.map(|r| r.map_err(CompositeLlmError::from))))
+        assert!(matches!(
+            backend.chat_completion(req()).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(matches!(
+            backend.chat_completion_stream(req()).await,
+            Err(CompositeLlmError::Unsupported(_))
+        ));
     }
 }
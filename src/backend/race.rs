@@ -0,0 +1,346 @@
+use async_trait::async_trait;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+
+use super::{ChatCompletionBackend, ChatCompletionStream};
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+
+/// A backend that races several backends and returns the first success.
+///
+/// Every wrapped backend receives a copy of the request at the same time. The
+/// first successful response wins and the remaining in-flight requests are
+/// dropped (cancelled). If every backend fails, all errors are returned in
+/// [`CompositeLlmError::AllBackendsFailed`].
+///
+/// For streaming, the first backend to yield a chunk wins. A stream that ends
+/// without any chunk counts as a failure.
+pub struct RaceBackend {
+    backends: Vec<Box<dyn ChatCompletionBackend>>,
+}
+
+impl RaceBackend {
+    /// Creates a new `RaceBackend` over the given backends.
+    pub fn new(backends: Vec<Box<dyn ChatCompletionBackend>>) -> Self {
+        Self { backends }
+    }
+
+    fn ensure_not_empty(&self) -> Result<(), CompositeLlmError> {
+        if self.backends.is_empty() {
+            return Err(CompositeLlmError::Config(
+                "RaceBackend requires at least one backend".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChatCompletionBackend for RaceBackend {
+    /// Reports `"race"`, since the winning backend differs per request.
+    fn name(&self) -> &str {
+        "race"
+    }
+
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.ensure_not_empty()?;
+
+        let mut pending: FuturesUnordered<_> = self
+            .backends
+            .iter()
+            .map(|b| b.chat_completion_with(req.clone(), opts))
+            .collect();
+
+        let mut errors = Vec::new();
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok(resp) => return Ok(resp),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        Err(CompositeLlmError::AllBackendsFailed(errors))
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.ensure_not_empty()?;
+
+        let mut pending: FuturesUnordered<_> = self
+            .backends
+            .iter()
+            .map(|b| {
+                let req = req.clone();
+                async move {
+                    let mut stream = b.chat_completion_stream_with(req, opts).await?;
+                    match stream.next().await {
+                        Some(Ok(chunk)) => Ok((chunk, stream)),
+                        Some(Err(e)) => Err(e),
+                        None => Err(CompositeLlmError::Other(format!(
+                            "the {} stream ended without any chunk",
+                            b.name()
+                        ))),
+                    }
+                }
+            })
+            .collect();
+
+        let mut errors = Vec::new();
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok((first, rest)) => {
+                    let head = futures_util::stream::once(async { Ok(first) });
+                    return Ok(Box::pin(head.chain(rest)));
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        Err(CompositeLlmError::AllBackendsFailed(errors))
+    }
+
+    /// Succeeds as soon as one backend is healthy.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.ensure_not_empty()?;
+
+        let mut pending: FuturesUnordered<_> =
+            self.backends.iter().map(|b| b.health_check()).collect();
+
+        let mut errors = Vec::new();
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        Err(CompositeLlmError::AllBackendsFailed(errors))
+    }
+
+    /// Lists the models of every backend that can list them, without
+    /// duplicates.
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        self.ensure_not_empty()?;
+
+        let results = futures_util::future::join_all(self.backends.iter().map(|b| b.list_models()));
+
+        let mut models = Vec::new();
+        let mut errors = Vec::new();
+        for result in results.await {
+            match result {
+                Ok(listed) => {
+                    for model in listed {
+                        if !models.contains(&model) {
+                            models.push(model);
+                        }
+                    }
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.len() == self.backends.len() {
+            return Err(CompositeLlmError::AllBackendsFailed(errors));
+        }
+        Ok(models)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use async_openai::types::chat::{
+        ChatChoice, ChatChoiceStream, ChatCompletionResponseMessage,
+        ChatCompletionStreamResponseDelta, CreateChatCompletionStreamResponse, Role,
+    };
+
+    struct DelayedBackend {
+        delay: Duration,
+        reply: Option<&'static str>,
+    }
+
+    #[allow(deprecated)]
+    fn response(text: &str) -> CreateChatCompletionResponse {
+        CreateChatCompletionResponse {
+            id: "chatcmpl-test".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "test".to_string(),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatCompletionResponseMessage {
+                    content: Some(text.to_string()),
+                    refusal: None,
+                    tool_calls: None,
+                    annotations: None,
+                    role: Role::Assistant,
+                    function_call: None,
+                    audio: None,
+                },
+                finish_reason: None,
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn chunk(text: &str) -> CreateChatCompletionStreamResponse {
+        CreateChatCompletionStreamResponse {
+            id: "chatcmpl-test".to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created: 0,
+            model: "test".to_string(),
+            choices: vec![ChatChoiceStream {
+                index: 0,
+                delta: ChatCompletionStreamResponseDelta {
+                    content: Some(text.to_string()),
+                    tool_calls: None,
+                    role: None,
+                    function_call: None,
+                    refusal: None,
+                },
+                finish_reason: None,
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+
+    #[async_trait]
+    impl ChatCompletionBackend for DelayedBackend {
+        async fn chat_completion(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+            tokio::time::sleep(self.delay).await;
+            match self.reply {
+                Some(text) => Ok(response(text)),
+                None => Err(CompositeLlmError::Unsupported("failed".to_string())),
+            }
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<ChatCompletionStream, CompositeLlmError> {
+            // Streams one chunk after the delay, or none without a reply.
+            tokio::time::sleep(self.delay).await;
+            let chunks = self.reply.map(|text| Ok(chunk(text)));
+            Ok(Box::pin(futures_util::stream::iter(chunks)))
+        }
+    }
+
+    fn backend(delay_ms: u64, reply: Option<&'static str>) -> Box<dyn ChatCompletionBackend> {
+        Box::new(DelayedBackend {
+            delay: Duration::from_millis(delay_ms),
+            reply,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fastest_success_wins() {
+        let race = RaceBackend::new(vec![backend(200, Some("slow")), backend(10, Some("fast"))]);
+        let resp = race
+            .chat_completion(CreateChatCompletionRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(resp.choices[0].message.content.as_deref(), Some("fast"));
+    }
+
+    #[tokio::test]
+    async fn test_failure_does_not_win() {
+        let race = RaceBackend::new(vec![backend(1, None), backend(20, Some("ok"))]);
+        let resp = race
+            .chat_completion(CreateChatCompletionRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(resp.choices[0].message.content.as_deref(), Some("ok"));
+    }
+
+    #[tokio::test]
+    async fn test_all_failures_are_collected() {
+        let race = RaceBackend::new(vec![backend(1, None), backend(2, None)]);
+        let err = race
+            .chat_completion(CreateChatCompletionRequest::default())
+            .await
+            .unwrap_err();
+        match err {
+            CompositeLlmError::AllBackendsFailed(errors) => assert_eq!(errors.len(), 2),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_fastest_chunk_wins() {
+        let first_content = |race: RaceBackend| async move {
+            let stream = race
+                .chat_completion_stream(CreateChatCompletionRequest::default())
+                .await?;
+            let chunks: Vec<_> = stream.collect().await;
+            assert_eq!(chunks.len(), 1);
+            Ok::<_, CompositeLlmError>(chunks[0].as_ref().unwrap().choices[0].delta.content.clone())
+        };
+
+        let race = RaceBackend::new(vec![backend(200, Some("slow")), backend(10, Some("fast"))]);
+        assert_eq!(first_content(race).await.unwrap().as_deref(), Some("fast"));
+
+        // An empty stream loses even when it ends first.
+        let race = RaceBackend::new(vec![backend(1, None), backend(20, Some("ok"))]);
+        assert_eq!(first_content(race).await.unwrap().as_deref(), Some("ok"));
+
+        let race = RaceBackend::new(vec![backend(1, None), backend(2, None)]);
+        match first_content(race).await {
+            Err(CompositeLlmError::AllBackendsFailed(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_check_needs_one_healthy_backend() {
+        let race = RaceBackend::new(vec![backend(1, None), backend(2, Some("pong"))]);
+        assert!(race.health_check().await.is_ok());
+
+        let race = RaceBackend::new(vec![backend(1, None), backend(2, None)]);
+        assert!(matches!(
+            race.health_check().await,
+            Err(CompositeLlmError::AllBackendsFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_empty_race_is_a_config_error() {
+        let race = RaceBackend::new(Vec::new());
+        let result = race.chat_completion(CreateChatCompletionRequest::default());
+        assert!(matches!(result.await, Err(CompositeLlmError::Config(_))));
+    }
+}
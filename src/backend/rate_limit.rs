@@ -0,0 +1,305 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures_core::Stream;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{ChatCompletionBackend, ChatCompletionStream};
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+
+/// A backend that keeps requests to another backend within limits.
+///
+/// Two independent limits can be configured:
+///
+/// - a maximum number of requests in flight at once, and
+/// - a requests-per-minute rate, enforced with a token bucket that holds up
+///   to one minute's worth of requests, so short bursts are allowed.
+///
+/// By default a request over a limit waits until it can proceed. With
+/// [`with_reject_when_limited`](Self::with_reject_when_limited) it fails
+/// immediately with [`CompositeLlmError::RateLimited`] instead.
+///
+/// A streaming request stays in flight until its stream ends or is dropped.
+pub struct RateLimitedBackend {
+    backend: Box<dyn ChatCompletionBackend>,
+    concurrency: Option<Arc<Semaphore>>,
+    rate: Option<Mutex<TokenBucket>>,
+    reject: bool,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(requests: u32) -> Self {
+        let capacity = f64::from(requests.max(1));
+        Self {
+            capacity,
+            tokens: capacity,
+            per_second: capacity / 60.0,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    fn try_take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.per_second,
+            ))
+        }
+    }
+}
+
+impl RateLimitedBackend {
+    /// Wraps `backend` without any limit; add them with the `with_*` methods.
+    pub fn new(backend: Box<dyn ChatCompletionBackend>) -> Self {
+        Self {
+            backend,
+            concurrency: None,
+            rate: None,
+            reject: false,
+        }
+    }
+
+    /// Sets the maximum number of requests in flight at once.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.concurrency = Some(Arc::new(Semaphore::new(max_concurrency.max(1))));
+        self
+    }
+
+    /// Sets the maximum number of requests started per minute.
+    pub fn with_requests_per_minute(mut self, requests: u32) -> Self {
+        self.rate = Some(Mutex::new(TokenBucket::per_minute(requests)));
+        self
+    }
+
+    /// Sets whether a request over a limit fails with
+    /// [`CompositeLlmError::RateLimited`] instead of waiting (default `false`).
+    pub fn with_reject_when_limited(mut self, reject: bool) -> Self {
+        self.reject = reject;
+        self
+    }
+
+    /// Waits for, or rejects, a slot under both limits.
+    ///
+    /// The concurrency permit is taken first, so a request rejected for
+    /// concurrency does not use up a token of the rate limit.
+    async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>, CompositeLlmError> {
+        let permit = match self.concurrency {
+            Some(ref semaphore) if self.reject => Some(
+                semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| CompositeLlmError::RateLimited)?,
+            ),
+            Some(ref semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed"),
+            ),
+            None => None,
+        };
+
+        if let Some(ref rate) = self.rate {
+            loop {
+                let result = rate.lock().unwrap_or_else(|e| e.into_inner()).try_take();
+                match result {
+                    Ok(()) => break,
+                    Err(_) if self.reject => return Err(CompositeLlmError::RateLimited),
+                    Err(wait) => tokio::time::sleep(wait).await,
+                }
+            }
+        }
+        Ok(permit)
+    }
+}
+
+#[async_trait]
+impl ChatCompletionBackend for RateLimitedBackend {
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        let _permit = self.acquire().await?;
+        self.backend.chat_completion_with(req, opts).await
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let permit = self.acquire().await?;
+        let inner = self.backend.chat_completion_stream_with(req, opts).await?;
+        Ok(Box::pin(PermitStream { inner, permit }))
+    }
+
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.backend.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        self.backend.list_models().await
+    }
+}
+
+/// Holds a concurrency permit until the wrapped stream ends.
+struct PermitStream {
+    inner: ChatCompletionStream,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Stream for PermitStream {
+    type Item = <ChatCompletionStream as Stream>::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let poll = this.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            this.permit = None;
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    struct SlowBackend;
+
+    #[async_trait]
+    impl ChatCompletionBackend for SlowBackend {
+        async fn chat_completion(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err(CompositeLlmError::Unsupported("done".to_string()))
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            _req: CreateChatCompletionRequest,
+        ) -> Result<ChatCompletionStream, CompositeLlmError> {
+            Ok(Box::pin(futures_util::stream::empty()))
+        }
+    }
+
+    fn is_rate_limited<T>(result: Result<T, CompositeLlmError>) -> bool {
+        matches!(result, Err(CompositeLlmError::RateLimited))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_limit_rejects_or_waits() {
+        let req = CreateChatCompletionRequest::default;
+
+        let backend = RateLimitedBackend::new(Box::new(SlowBackend))
+            .with_max_concurrency(1)
+            .with_reject_when_limited(true);
+        let (first, second) = tokio::join!(
+            backend.chat_completion(req()),
+            backend.chat_completion(req())
+        );
+        assert!(!is_rate_limited(first));
+        assert!(is_rate_limited(second));
+
+        let backend = RateLimitedBackend::new(Box::new(SlowBackend)).with_max_concurrency(1);
+        let start = tokio::time::Instant::now();
+        let (first, second) = tokio::join!(
+            backend.chat_completion(req()),
+            backend.chat_completion(req())
+        );
+        assert!(!is_rate_limited(first));
+        assert!(!is_rate_limited(second));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_stream_holds_permit_until_end() {
+        let backend = RateLimitedBackend::new(Box::new(SlowBackend))
+            .with_max_concurrency(1)
+            .with_reject_when_limited(true);
+        let req = CreateChatCompletionRequest::default;
+
+        let mut stream = backend.chat_completion_stream(req()).await.unwrap();
+        assert!(is_rate_limited(backend.chat_completion_stream(req()).await));
+
+        assert!(stream.next().await.is_none());
+        assert!(backend.chat_completion_stream(req()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute_rejects_burst() {
+        let backend = RateLimitedBackend::new(Box::new(SlowBackend))
+            .with_requests_per_minute(2)
+            .with_reject_when_limited(true);
+        let req = CreateChatCompletionRequest::default;
+
+        assert!(!is_rate_limited(backend.chat_completion(req()).await));
+        assert!(!is_rate_limited(backend.chat_completion(req()).await));
+        assert!(is_rate_limited(backend.chat_completion(req()).await));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_rejection_keeps_rate_token() {
+        let backend = RateLimitedBackend::new(Box::new(SlowBackend))
+            .with_max_concurrency(1)
+            .with_requests_per_minute(2)
+            .with_reject_when_limited(true);
+        let req = CreateChatCompletionRequest::default;
+
+        let (first, second) = tokio::join!(
+            backend.chat_completion(req()),
+            backend.chat_completion(req())
+        );
+        assert!(!is_rate_limited(first));
+        assert!(is_rate_limited(second));
+        assert!(!is_rate_limited(backend.chat_completion(req()).await));
+        assert!(is_rate_limited(backend.chat_completion(req()).await));
+    }
+}
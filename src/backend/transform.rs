@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+
+use super::{ChatCompletionBackend, ChatCompletionStream};
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+
+type Transform = Box<dyn Fn(&mut CreateChatCompletionRequest) + Send + Sync>;
+
+/// A backend that rewrites every request before delegating it.
+///
+/// Useful for cross-cutting changes such as injecting an organization-wide
+/// system prompt or redacting user content, independent of the wrapped
+/// backend. The transform runs once per call, so wrapping a retrying or
+/// fallback backend transforms each request once rather than once per
+/// attempt.
+pub struct TransformBackend {
+    backend: Box<dyn ChatCompletionBackend>,
+    transform: Transform,
+}
+
+impl TransformBackend {
+    /// Wraps `backend`, applying `transform` to each request before it is sent.
+    pub fn new(
+        backend: Box<dyn ChatCompletionBackend>,
+        transform: impl Fn(&mut CreateChatCompletionRequest) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            backend,
+            transform: Box::new(transform),
+        }
+    }
+
+    fn apply(&self, mut req: CreateChatCompletionRequest) -> CreateChatCompletionRequest {
+        (self.transform)(&mut req);
+        req
+    }
+}
+
+#[async_trait]
+impl ChatCompletionBackend for TransformBackend {
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
+
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.backend
+            .chat_completion_with(self.apply(req), opts)
+            .await
+    }
+
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        self.backend
+            .chat_completion_stream_with(self.apply(req), opts)
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.backend.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        self.backend.list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_openai::types::chat::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+    };
+
+    use super::*;
+
+    struct RecordingBackend {
+        requests: Arc<Mutex<Vec<CreateChatCompletionRequest>>>,
+    }
+
+    #[async_trait]
+    impl ChatCompletionBackend for RecordingBackend {
+        async fn chat_completion(
+            &self,
+            req: CreateChatCompletionRequest,
+        ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+            self.requests.lock().unwrap().push(req);
+            Err(CompositeLlmError::Unsupported("recorded".to_string()))
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            req: CreateChatCompletionRequest,
+        ) -> Result<ChatCompletionStream, CompositeLlmError> {
+            self.requests.lock().unwrap().push(req);
+            Err(CompositeLlmError::Unsupported("recorded".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transform_applies_to_both_paths() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let backend = TransformBackend::new(
+            Box::new(RecordingBackend {
+                requests: requests.clone(),
+            }),
+            |req| {
+                req.messages.insert(
+                    0,
+                    ChatCompletionRequestMessage::System(
+                        ChatCompletionRequestSystemMessageArgs::default()
+                            .content("Follow the org policy.")
+                            .build()
+                            .unwrap(),
+                    ),
+                );
+            },
+        );
+
+        let _ = backend
+            .chat_completion(CreateChatCompletionRequest::default())
+            .await;
+        let _ = backend
+            .chat_completion_stream(CreateChatCompletionRequest::default())
+            .await;
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for req in requests.iter() {
+            assert_eq!(req.messages.len(), 1);
+            assert!(matches!(
+                req.messages[0],
+                ChatCompletionRequestMessage::System(_)
+            ));
+        }
+    }
+}
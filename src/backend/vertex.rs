@@ -1,35 +1,75 @@
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures_core::Stream;
 use gcp_auth::TokenProvider;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, RETRY_AFTER, USER_AGENT};
+use reqwest::{Client, RequestBuilder};
+use tracing::Instrument;
 
-use super::{ChatCompletionBackend, ChatCompletionStream};
-use crate::convert::generate_chat_cmpl_id;
+use super::{
+    ChatCompletionBackend, ChatCompletionStream, SseDecoder, check_messages, request_span,
+};
 use crate::convert::vertex::{
-    VertexResponse, convert_request, convert_vertex_response, convert_vertex_stream_chunk,
-    parse_sse_events,
+    FunctionCallAssembler, GenerationConfig, MAX_STOP_SEQUENCES, VertexBlob,
+    VertexCountTokensRequest, VertexCountTokensResponse, VertexListPublisherModelsResponse,
+    VertexRequest, VertexResponse, apply_vertex_options, avg_logprobs, clamp_thinking_budget,
+    convert_request, convert_vertex_response, convert_vertex_stream_chunk, convert_vertex_usage,
+    inline_data, parse_sse_event,
+};
+use crate::convert::{
+    generate_chat_cmpl_id, include_usage, unix_timestamp, usage_chunk, with_default_stop_sequences,
 };
 use crate::error::CompositeLlmError;
+use crate::options::{Credentials, ProviderOptions};
 use async_openai::types::chat::{
-    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+    CompletionUsage, CreateChatCompletionRequest, CreateChatCompletionResponse,
+    CreateChatCompletionStreamResponse, FinishReason,
 };
 
 /// A backend implementation for Google Vertex AI.
 ///
 /// This backend uses direct HTTP requests to the Vertex AI API, handling authentication
 /// via `gcp_auth`.
+///
+/// Generated images and audio have no place in OpenAI responses; they are
+/// available through [`chat_completion_with_details`](Self::chat_completion_with_details).
+/// Streams drop them, with a warning for each.
+#[derive(Clone)]
 pub struct VertexBackend {
     client: Client,
     auth: Arc<dyn TokenProvider>,
     project_id: String,
     location: String,
     model_id: String,
+    headers: HeaderMap,
+    user_agent: String,
+    request_model: bool,
+    generation_config: Option<GenerationConfig>,
+    stop_sequences: Vec<String>,
+    max_response_bytes: Option<usize>,
+}
+
+/// A chat completion response with the Vertex AI-specific parts kept.
+#[derive(Debug, Clone)]
+pub struct VertexChatResponse {
+    /// The converted response.
+    pub response: CreateChatCompletionResponse,
+    /// The `avgLogprobs` of each candidate, indexed like `response.choices`.
+    pub avg_logprobs: Vec<Option<f64>>,
+    /// Images or audio generated by each candidate, indexed like
+    /// `response.choices`; see [`VertexBlob::to_data_uri`].
+    pub inline_data: Vec<Vec<VertexBlob>>,
 }
 
+/// `User-Agent` sent unless overridden with
+/// [`VertexBackend::with_user_agent`].
+pub const DEFAULT_USER_AGENT: &str = concat!("composite-llm/", env!("CARGO_PKG_VERSION"));
+
 impl VertexBackend {
     /// Creates a new `VertexBackend`.
     ///
@@ -37,7 +77,9 @@ impl VertexBackend {
     ///
     /// * `project_id` - The Google Cloud project ID.
     /// * `location` - The region (e.g., "us-central1").
-    /// * `model_id` - The model ID (e.g., "gemini-pro").
+    /// * `model_id` - The model ID (e.g., "gemini-pro"), called regardless of
+    ///   `req.model` unless [`with_request_model`](Self::with_request_model)
+    ///   is enabled.
     pub async fn new(
         project_id: impl Into<String>,
         location: impl Into<String>,
@@ -45,7 +87,7 @@ impl VertexBackend {
     ) -> Result<Self, CompositeLlmError> {
         let auth = gcp_auth::provider()
             .await
-            .map_err(|e| CompositeLlmError::Vertex(e.to_string()))?;
+            .map_err(CompositeLlmError::vertex)?;
 
         Ok(Self {
             client: Client::new(),
@@ -53,13 +95,132 @@ impl VertexBackend {
             project_id: project_id.into(),
             location: location.into(),
             model_id: model_id.into(),
+            headers: HeaderMap::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_model: false,
+            generation_config: None,
+            stop_sequences: Vec::new(),
+            max_response_bytes: None,
         })
     }
 
-    fn base_url(&self) -> String {
+    /// Sets extra HTTP headers sent with every request.
+    ///
+    /// Useful for e.g. `X-Goog-User-Project` when the quota/billing project
+    /// differs from the project of the application default credentials.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Sets the `User-Agent` sent with every request (default
+    /// [`DEFAULT_USER_AGENT`]), e.g. `myapp/1.2 composite-llm`.
+    ///
+    /// A `User-Agent` in [`with_headers`](Self::with_headers) takes precedence.
+    /// An invalid value fails each request with a Vertex error.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sets a default `generationConfig` for every request.
+    ///
+    /// Parameters taken from the request (`temperature`, `max_completion_tokens`
+    /// and so on) override these defaults field by field, and
+    /// [`VertexOptions`](crate::VertexOptions) override both. Useful for
+    /// Gemini-only knobs such as `top_k`, `candidate_count` or `seed`.
+    pub fn with_generation_config(mut self, config: GenerationConfig) -> Self {
+        self.generation_config = Some(config);
+        self
+    }
+
+    /// Sets stop sequences added to every request's `stop`.
+    ///
+    /// Duplicates are skipped, and defaults that would exceed Gemini's limit
+    /// of [`MAX_STOP_SEQUENCES`] are dropped in favor of the request's. Unlike
+    /// `stop_sequences` in [`with_generation_config`](Self::with_generation_config),
+    /// these are kept when the request sets its own.
+    pub fn with_stop_sequences(
+        mut self,
+        stop_sequences: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.stop_sequences = stop_sequences.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the maximum number of bytes read from a streaming response
+    /// (default unlimited).
+    ///
+    /// A stream that exceeds it ends with
+    /// [`CompositeLlmError::Unsupported`], so a pathological response cannot
+    /// grow memory without bound.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Sets whether a non-empty `req.model` overrides the model ID the backend
+    /// was constructed with (default `false`).
+    ///
+    /// By default `req.model` only labels the response, so the same request
+    /// can be sent to several backends (e.g. with
+    /// [`RaceBackend`](crate::RaceBackend)) or to an OpenAI-named model; a
+    /// warning is logged when it differs from the bound model. A request
+    /// model is percent-encoded into the URL path.
+    pub fn with_request_model(mut self, request_model: bool) -> Self {
+        self.request_model = request_model;
+        self
+    }
+
+    /// Returns the model ID to call for `req`.
+    fn model_id<'a>(&'a self, req: &'a CreateChatCompletionRequest) -> &'a str {
+        if self.request_model && !req.model.is_empty() {
+            &req.model
+        } else {
+            &self.model_id
+        }
+    }
+
+    /// Converts `req`, fills in the default generation config and applies the
+    /// Vertex section of `opts`.
+    fn vertex_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<VertexRequest, CompositeLlmError> {
+        check_messages(req)?;
+        if !self.request_model && !req.model.is_empty() && req.model != self.model_id {
+            tracing::warn!(
+                requested = req.model,
+                model = self.model_id,
+                "ignoring the request model; enable with_request_model to use it"
+            );
+        }
+        let req = with_default_stop_sequences(req, &self.stop_sequences, MAX_STOP_SEQUENCES);
+        let mut vertex_req = convert_request(&req)?;
+        clamp_thinking_budget(&mut vertex_req, self.model_id(&req));
+        if vertex_req.contents.is_empty() {
+            return Err(CompositeLlmError::Unsupported(
+                "Vertex AI requires at least one non-system message".to_string(),
+            ));
+        }
+        if let Some(ref defaults) = self.generation_config {
+            let config = vertex_req.generation_config.take().unwrap_or_default();
+            vertex_req.generation_config = Some(config.or(defaults));
+        }
+        if let Some(ref vertex_opts) = opts.vertex {
+            apply_vertex_options(&mut vertex_req, vertex_opts);
+        }
+        Ok(vertex_req)
+    }
+
+    fn base_url(&self, model_id: &str) -> String {
         format!(
             "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}",
-            self.location, self.project_id, self.location, self.model_id
+            self.location,
+            self.project_id,
+            self.location,
+            encode_path_segment(model_id)
         )
     }
 
@@ -69,104 +230,399 @@ impl VertexBackend {
             .auth
             .token(scopes)
             .await
-            .map_err(|e| CompositeLlmError::Vertex(e.to_string()))?;
+            .map_err(CompositeLlmError::vertex)?;
         Ok(token.as_str().to_string())
     }
-}
 
-#[async_trait]
-impl ChatCompletionBackend for VertexBackend {
-    async fn chat_completion(
+    /// Returns the per-request bearer token if given, otherwise fetches one.
+    async fn resolve_token(&self, opts: &ProviderOptions) -> Result<String, CompositeLlmError> {
+        match opts.credentials {
+            None => self.get_token().await,
+            Some(Credentials::BearerToken(ref token)) => Ok(token.clone()),
+            Some(_) => Err(CompositeLlmError::Unsupported(
+                "Vertex backend only supports bearer token credentials".to_string(),
+            )),
+        }
+    }
+
+    fn post(&self, url: &str, token: &str) -> RequestBuilder {
+        self.authorize(self.client.post(url), token)
+    }
+
+    fn get(&self, url: &str, token: &str) -> RequestBuilder {
+        self.authorize(self.client.get(url), token)
+    }
+
+    fn authorize(&self, builder: RequestBuilder, token: &str) -> RequestBuilder {
+        builder
+            .bearer_auth(token)
+            .header(USER_AGENT, self.user_agent.as_str())
+            .headers(self.headers.clone())
+    }
+
+    /// Returns the `generateContent` body `req` would be sent as, without
+    /// sending it.
+    pub fn build_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        self.build_request_with(req, &ProviderOptions::default())
+    }
+
+    /// Like [`build_request`](Self::build_request), with provider-specific options.
+    pub fn build_request_with(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        Ok(serde_json::to_value(self.vertex_request(req, opts)?)?)
+    }
+
+    /// Sends a chat completion request and also returns the `avgLogprobs` and
+    /// generated inline data of each candidate, which have no place in the
+    /// OpenAI response.
+    pub async fn chat_completion_with_details(
         &self,
         req: CreateChatCompletionRequest,
-    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
-        let model = req.model.clone();
-        let vertex_req = convert_request(&req)?;
+        opts: &ProviderOptions,
+    ) -> Result<VertexChatResponse, CompositeLlmError> {
+        let span = request_span(self.name(), &req, opts);
+        async move {
+            let model = req.model.clone();
+            let vertex_req = self.vertex_request(&req, opts)?;
+            let token = self.resolve_token(opts).await?;
+
+            let url = format!("{}:generateContent", self.base_url(self.model_id(&req)));
+            let resp = correlate(self.post(&url, &token), opts)
+                .json(&vertex_req)
+                .send()
+                .await
+                .map_err(CompositeLlmError::vertex)?;
+            let resp = ensure_success(resp).await?;
+
+            let vertex_resp: VertexResponse =
+                resp.json().await.map_err(CompositeLlmError::vertex)?;
+
+            Ok(VertexChatResponse {
+                response: convert_vertex_response(&vertex_resp, &model, &generate_chat_cmpl_id())?,
+                avg_logprobs: avg_logprobs(&vertex_resp),
+                inline_data: inline_data(&vertex_resp),
+            })
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Counts the tokens of a request with the `:countTokens` endpoint.
+    ///
+    /// The request is converted as for a chat completion, so the count covers
+    /// the system instruction, messages and tool declarations the model would
+    /// see. Returns `totalTokens`.
+    pub async fn count_tokens(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<u32, CompositeLlmError> {
+        let count_req =
+            VertexCountTokensRequest::from(self.vertex_request(req, &ProviderOptions::default())?);
         let token = self.get_token().await?;
 
-        let url = format!("{}:generateContent", self.base_url());
+        let url = format!("{}:countTokens", self.base_url(self.model_id(req)));
         let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&token)
-            .json(&vertex_req)
+            .post(&url, &token)
+            .json(&count_req)
             .send()
             .await
-            .map_err(|e| CompositeLlmError::Vertex(e.to_string()))?;
+            .map_err(CompositeLlmError::vertex)?;
+        let resp = ensure_success(resp).await?;
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp
-                .text()
-                .await
-                .unwrap_or_else(|_| "unknown error".to_string());
-            return Err(CompositeLlmError::Vertex(format!(
-                "HTTP {}: {}",
-                status, body
-            )));
+        let count: VertexCountTokensResponse =
+            resp.json().await.map_err(CompositeLlmError::vertex)?;
+        Ok(count.total_tokens)
+    }
+}
+
+/// The header carrying [`ProviderOptions::correlation_id`].
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+/// Adds the request's correlation id header, if any.
+fn correlate(builder: RequestBuilder, opts: &ProviderOptions) -> RequestBuilder {
+    match opts.correlation_id {
+        Some(ref id) => builder.header(CORRELATION_ID_HEADER, id),
+        None => builder,
+    }
+}
+
+/// Percent-encodes `segment` for a URL path, so a model ID cannot add path
+/// segments, a query or a fragment. Unreserved characters and `@` (used in
+/// versioned model IDs) are kept, except dots in a `.` or `..` segment.
+fn encode_path_segment(segment: &str) -> String {
+    let keep_dots = !segment.bytes().all(|b| b == b'.');
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_~@".contains(&byte) || (byte == b'.' && keep_dots) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
         }
+    }
+    encoded
+}
 
-        let vertex_resp: VertexResponse = resp
-            .json()
-            .await
-            .map_err(|e| CompositeLlmError::Vertex(e.to_string()))?;
+/// Turns a non-2xx response into an error carrying the status and body.
+async fn ensure_success(resp: reqwest::Response) -> Result<reqwest::Response, CompositeLlmError> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let retry_after = retry_after(resp.headers());
+    let body = resp
+        .text()
+        .await
+        .unwrap_or_else(|_| "unknown error".to_string());
+    Err(CompositeLlmError::Vertex {
+        message: format!("HTTP {}: {}", status, body),
+        retry_after,
+        source: None,
+    })
+}
+
+/// Reads a `Retry-After` header in its delay-seconds form; the HTTP-date
+/// form is not used by Vertex AI and is ignored.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[async_trait]
+impl ChatCompletionBackend for VertexBackend {
+    fn name(&self) -> &str {
+        crate::BackendKind::Vertex.as_str()
+    }
 
-        convert_vertex_response(&vertex_resp, &model)
+    async fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with(req, &ProviderOptions::default())
+            .await
     }
 
     async fn chat_completion_stream(
         &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionStream, CompositeLlmError> {
-        let model = req.model.clone();
-        let vertex_req = convert_request(&req)?;
-        let token = self.get_token().await?;
+        self.chat_completion_stream_with(req, &ProviderOptions::default())
+            .await
+    }
 
-        let url = format!("{}:streamGenerateContent?alt=sse", self.base_url());
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&token)
-            .json(&vertex_req)
-            .send()
+    async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.chat_completion_with_details(req, opts)
             .await
-            .map_err(|e| CompositeLlmError::Vertex(e.to_string()))?;
+            .map(|details| details.response)
+    }
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp
-                .text()
+    async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        let model = req.model.clone();
+        let vertex_req = self.vertex_request(&req, opts)?;
+        let span = request_span(self.name(), &req, opts);
+        let resp = async {
+            let token = self.resolve_token(opts).await?;
+            let url = format!(
+                "{}:streamGenerateContent?alt=sse",
+                self.base_url(self.model_id(&req))
+            );
+            let resp = correlate(self.post(&url, &token), opts)
+                .json(&vertex_req)
+                .send()
                 .await
-                .unwrap_or_else(|_| "unknown error".to_string());
-            return Err(CompositeLlmError::Vertex(format!(
-                "HTTP {}: {}",
-                status, body
-            )));
+                .map_err(CompositeLlmError::vertex)?;
+            ensure_success(resp).await
         }
+        .instrument(span)
+        .await?;
 
         let id = generate_chat_cmpl_id();
         let byte_stream = resp.bytes_stream();
 
-        let stream = SseStream {
-            inner: Box::pin(byte_stream),
-            buffer: Vec::new(),
-            model,
-            id,
-            done: false,
-            pending: Vec::new(),
-        };
+        let stream = SseStream::new(Box::pin(byte_stream), model, id, include_usage(&req))
+            .with_max_bytes(self.max_response_bytes);
 
         Ok(Box::pin(stream))
     }
+
+    /// Counts the tokens of a one-word request, which costs no tokens.
+    async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        self.count_tokens(&super::ping_request()).await.map(|_| ())
+    }
+
+    /// Lists the Google publisher models, following every page.
+    async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        let token = self.get_token().await?;
+        let url = format!(
+            "https://{}-aiplatform.googleapis.com/v1beta1/publishers/google/models",
+            self.location
+        );
+
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let page_url = reqwest::Url::parse_with_params(
+                &url,
+                page_token.iter().map(|t| ("pageToken", t.as_str())),
+            )
+            .map_err(CompositeLlmError::vertex)?;
+            let resp = self
+                .get(page_url.as_str(), &token)
+                .send()
+                .await
+                .map_err(CompositeLlmError::vertex)?;
+            let page: VertexListPublisherModelsResponse = ensure_success(resp)
+                .await?
+                .json()
+                .await
+                .map_err(CompositeLlmError::vertex)?;
+
+            models.extend(page.publisher_models.iter().map(|m| m.id().to_string()));
+            match page.next_page_token {
+                Some(next) if !next.is_empty() => page_token = Some(next),
+                _ => return Ok(models),
+            }
+        }
+    }
 }
 
+type ByteStream = Pin<Box<dyn Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send>>;
+
+type VertexSseDecoder = SseDecoder<VertexResponse, fn(&str) -> Option<VertexResponse>>;
+
 struct SseStream {
-    inner: Pin<Box<dyn Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send>>,
-    buffer: Vec<u8>,
+    inner: ByteStream,
+    decoder: VertexSseDecoder,
     model: String,
     id: String,
     done: bool,
     pending: Vec<CreateChatCompletionStreamResponse>,
+    /// An error from the byte stream, yielded once `pending` is drained.
+    error: Option<CompositeLlmError>,
+    include_usage: bool,
+    usage: Option<CompletionUsage>,
+    /// Creation time shared by every chunk of the stream.
+    created: u32,
+    /// Choice indices that have already sent their first delta; only the
+    /// first delta of each choice carries the role.
+    started_choices: HashSet<u32>,
+    /// Tool calls sent so far per choice index; Gemini numbers calls from
+    /// zero in every event, so later calls are offset by this count.
+    tool_calls_sent: HashMap<u32, u32>,
+    function_calls: FunctionCallAssembler,
+    /// Bytes read so far, and the limit past which the stream fails.
+    received: usize,
+    max_bytes: Option<usize>,
+}
+
+impl SseStream {
+    fn new(inner: ByteStream, model: String, id: String, include_usage: bool) -> Self {
+        Self {
+            inner,
+            decoder: SseDecoder::new(parse_sse_event),
+            model,
+            id,
+            done: false,
+            pending: Vec::new(),
+            error: None,
+            include_usage,
+            usage: None,
+            created: unix_timestamp(),
+            started_choices: HashSet::new(),
+            tool_calls_sent: HashMap::new(),
+            function_calls: FunctionCallAssembler::default(),
+            received: 0,
+            max_bytes: None,
+        }
+    }
+
+    fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Ends the stream: decodes the last event and queues the usage chunk,
+    /// if requested.
+    fn finish(&mut self) {
+        self.done = true;
+        let responses = self.decoder.finish();
+        self.push_responses(responses);
+        if self.function_calls.is_pending() {
+            tracing::warn!("Vertex AI stream ended inside a function call; dropping it");
+        }
+        if self.include_usage
+            && let Some(usage) = self.usage.take()
+        {
+            let mut chunk = usage_chunk(usage, &self.model, &self.id);
+            chunk.created = self.created;
+            self.pending.push(chunk);
+        }
+    }
+
+    fn push_responses(&mut self, responses: Vec<VertexResponse>) {
+        for mut resp in responses {
+            // Every chunk carries cumulative usage; keep the latest.
+            if let Some(ref u) = resp.usage_metadata {
+                self.usage = Some(convert_vertex_usage(u));
+            }
+            let held_back = self.function_calls.assemble(&mut resp);
+            if let Some(mut chunk) = convert_vertex_stream_chunk(&resp, &self.model, &self.id) {
+                if held_back {
+                    // Drop the deltas left empty by a call still in progress.
+                    chunk.choices.retain(|c| {
+                        c.delta.content.is_some()
+                            || c.delta.tool_calls.is_some()
+                            || c.finish_reason.is_some()
+                            || c.logprobs.is_some()
+                    });
+                    if chunk.choices.is_empty() {
+                        continue;
+                    }
+                }
+                chunk.created = self.created;
+                // OpenAI sends the role only on the first chunk of each choice.
+                for choice in &mut chunk.choices {
+                    if choice.delta.role.is_some() && !self.started_choices.insert(choice.index) {
+                        choice.delta.role = None;
+                    }
+                    if let Some(ref mut calls) = choice.delta.tool_calls {
+                        let sent = self.tool_calls_sent.entry(choice.index).or_default();
+                        for call in calls.iter_mut() {
+                            call.index += *sent;
+                        }
+                        *sent += calls.len() as u32;
+                    }
+                    // Gemini often sends the finish reason in a later event
+                    // than the tool calls.
+                    if choice.finish_reason == Some(FinishReason::Stop)
+                        && self.tool_calls_sent.get(&choice.index) > Some(&0)
+                    {
+                        choice.finish_reason = Some(FinishReason::ToolCalls);
+                    }
+                }
+                self.pending.push(chunk);
+            }
+        }
+    }
 }
 
 impl Stream for SseStream {
@@ -181,52 +637,393 @@ impl Stream for SseStream {
         }
 
         if this.done {
-            return Poll::Ready(None);
+            return Poll::Ready(this.error.take().map(Err));
         }
 
         match this.inner.as_mut().poll_next(cx) {
             Poll::Ready(Some(Ok(bytes))) => {
-                this.buffer.extend_from_slice(&bytes);
-                let (responses, remaining) = parse_sse_events(&this.buffer);
-                this.buffer = remaining;
-
-                for resp in responses {
-                    if let Some(chunk) = convert_vertex_stream_chunk(&resp, &this.model, &this.id) {
-                        this.pending.push(chunk);
-                    }
+                this.received = this.received.saturating_add(bytes.len());
+                if this.max_bytes.is_some_and(|max| this.received > max) {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(CompositeLlmError::Unsupported(
+                        "response exceeded max size".to_string(),
+                    ))));
+                }
+                let responses = this.decoder.push(&bytes);
+                this.push_responses(responses);
+                if this.decoder.is_done() {
+                    this.finish();
                 }
 
-                if this.pending.is_empty() {
+                if !this.pending.is_empty() {
+                    Poll::Ready(Some(Ok(this.pending.remove(0))))
+                } else if this.done {
+                    Poll::Ready(None)
+                } else {
                     cx.waker().wake_by_ref();
                     Poll::Pending
-                } else {
-                    Poll::Ready(Some(Ok(this.pending.remove(0))))
                 }
             }
             Poll::Ready(Some(Err(e))) => {
+                // Deliver whatever events were received before the error,
+                // then the error itself.
+                let responses = this.decoder.finish();
+                this.push_responses(responses);
                 this.done = true;
-                Poll::Ready(Some(Err(CompositeLlmError::Vertex(e.to_string()))))
+                let error = CompositeLlmError::vertex(e);
+                if this.pending.is_empty() {
+                    Poll::Ready(Some(Err(error)))
+                } else {
+                    this.error = Some(error);
+                    Poll::Ready(Some(Ok(this.pending.remove(0))))
+                }
             }
             Poll::Ready(None) => {
-                this.done = true;
-                // Process any remaining buffer
-                if !this.buffer.is_empty() {
-                    let (responses, _) = parse_sse_events(&this.buffer);
-                    this.buffer.clear();
-                    for resp in responses {
-                        if let Some(chunk) =
-                            convert_vertex_stream_chunk(&resp, &this.model, &this.id)
-                        {
-                            this.pending.push(chunk);
-                        }
-                    }
-                    if !this.pending.is_empty() {
-                        return Poll::Ready(Some(Ok(this.pending.remove(0))));
-                    }
+                this.finish();
+                if this.pending.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(this.pending.remove(0))))
                 }
-                Poll::Ready(None)
             }
             Poll::Pending => Poll::Pending,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::chat::Role;
+    use futures_util::StreamExt;
+
+    const SSE: &[u8] = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Hi\"}]}}],\"usageMetadata\":{\"promptTokenCount\":3}}\n\n\
+data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"!\"}]},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":3,\"candidatesTokenCount\":2,\"totalTokenCount\":5}}\n\n";
+
+    async fn collect(include_usage: bool) -> Vec<CreateChatCompletionStreamResponse> {
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![Ok(
+            bytes::Bytes::from_static(SSE),
+        )]));
+        SseStream::new(inner, "gemini".to_string(), "id".to_string(), include_usage)
+            .map(Result::unwrap)
+            .collect()
+            .await
+    }
+
+    struct NoAuth;
+
+    #[async_trait]
+    impl TokenProvider for NoAuth {
+        async fn token(&self, _scopes: &[&str]) -> Result<Arc<gcp_auth::Token>, gcp_auth::Error> {
+            Err(gcp_auth::Error::Str("tests never fetch tokens"))
+        }
+
+        async fn project_id(&self) -> Result<Arc<str>, gcp_auth::Error> {
+            Err(gcp_auth::Error::Str("tests never fetch the project"))
+        }
+    }
+
+    fn test_backend() -> VertexBackend {
+        VertexBackend {
+            client: Client::new(),
+            auth: Arc::new(NoAuth),
+            project_id: "project".to_string(),
+            location: "us-central1".to_string(),
+            model_id: "gemini".to_string(),
+            headers: HeaderMap::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_model: false,
+            generation_config: None,
+            stop_sequences: Vec::new(),
+            max_response_bytes: None,
+        }
+    }
+
+    #[test]
+    fn test_model_id() {
+        let req = |model: &str| CreateChatCompletionRequest {
+            model: model.to_string(),
+            ..Default::default()
+        };
+
+        let backend = test_backend();
+        assert_eq!(backend.model_id(&req("gemini-1.5-pro")), "gemini");
+
+        let backend = backend.with_request_model(true);
+        assert_eq!(backend.model_id(&req("gemini-1.5-pro")), "gemini-1.5-pro");
+        assert_eq!(backend.model_id(&req("")), "gemini");
+    }
+
+    #[test]
+    fn test_base_url_encodes_model() {
+        let backend = test_backend();
+        assert!(
+            backend
+                .base_url("gemini-1.0-pro@001")
+                .ends_with("/models/gemini-1.0-pro@001")
+        );
+        assert!(
+            backend
+                .base_url("../other?x=1#frag")
+                .ends_with("/models/..%2Fother%3Fx%3D1%23frag")
+        );
+        assert!(backend.base_url("..").ends_with("/models/%2E%2E"));
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let user_agent = |backend: &VertexBackend| {
+            let req = backend
+                .post("https://example.com", "token")
+                .build()
+                .unwrap();
+            req.headers()[USER_AGENT].to_str().unwrap().to_string()
+        };
+
+        let backend = test_backend();
+        assert!(user_agent(&backend).starts_with("composite-llm/"));
+
+        let backend = backend.with_user_agent("myapp/1.2 composite-llm");
+        assert_eq!(user_agent(&backend), "myapp/1.2 composite-llm");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, "from-headers".parse().unwrap());
+        let backend = backend.with_headers(headers);
+        assert_eq!(user_agent(&backend), "from-headers");
+    }
+
+    #[test]
+    fn test_correlation_id_header() {
+        let backend = test_backend();
+        let build = |opts: &ProviderOptions| {
+            correlate(backend.post("https://example.com", "token"), opts)
+                .build()
+                .unwrap()
+        };
+
+        assert!(
+            build(&ProviderOptions::default())
+                .headers()
+                .get(CORRELATION_ID_HEADER)
+                .is_none()
+        );
+        let req = build(&ProviderOptions::default().with_correlation_id("trace-123"));
+        assert_eq!(req.headers()[CORRELATION_ID_HEADER], "trace-123");
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_final_usage_chunk() {
+        let chunks = collect(true).await;
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.id == "id"));
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.object == crate::convert::CHAT_COMPLETION_CHUNK_OBJECT)
+        );
+        assert!(chunks[..2].iter().all(|c| c.usage.is_none()));
+        assert!(chunks[2].choices.is_empty());
+        assert_eq!(chunks[2].usage.as_ref().unwrap().total_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_chunks_share_created() {
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![Ok(
+            bytes::Bytes::from_static(SSE),
+        )]));
+        let mut stream = SseStream::new(inner, "gemini".to_string(), "id".to_string(), true);
+        // A start time no chunk could read from the clock.
+        stream.created = 1;
+
+        let chunks: Vec<_> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.created == 1));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_success_reads_retry_after() {
+        let resp = http::Response::builder()
+            .status(429)
+            .header("Retry-After", "30")
+            .body("quota exceeded")
+            .unwrap();
+        let err = ensure_success(reqwest::Response::from(resp))
+            .await
+            .unwrap_err();
+        match err {
+            CompositeLlmError::Vertex {
+                message,
+                retry_after,
+                ..
+            } => {
+                assert_eq!(message, "HTTP 429 Too Many Requests: quota exceeded");
+                assert_eq!(retry_after, Some(Duration::from_secs(30)));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        let resp = http::Response::builder().status(500).body("").unwrap();
+        assert!(matches!(
+            ensure_success(reqwest::Response::from(resp)).await,
+            Err(CompositeLlmError::Vertex {
+                retry_after: None,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_error_after_buffered_chunk() {
+        // The event has no trailing blank line, so it is still buffered when
+        // the connection fails.
+        let event = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Hi\"}]}}]}\n";
+        let error = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(bytes::Bytes::from_static(event)),
+            Err(error),
+        ]));
+        let mut stream = SseStream::new(inner, "gemini".to_string(), "id".to_string(), false);
+
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Hi"));
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_max_bytes() {
+        let chunks = |max_bytes| {
+            let inner: ByteStream = Box::pin(futures_util::stream::iter(
+                SSE.chunks(64)
+                    .map(|c| Ok(bytes::Bytes::copy_from_slice(c)))
+                    .collect::<Vec<_>>(),
+            ));
+            SseStream::new(inner, "gemini".to_string(), "id".to_string(), false)
+                .with_max_bytes(max_bytes)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(chunks(Some(SSE.len())).await.iter().all(Result::is_ok));
+
+        let results = chunks(Some(SSE.len() - 1)).await;
+        assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+        assert!(matches!(
+            results.last(),
+            Some(Err(CompositeLlmError::Unsupported(message)))
+                if message == "response exceeded max size"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_role_only_on_first_chunk() {
+        let chunks = collect(false).await;
+        assert_eq!(chunks[0].choices[0].delta.role, Some(Role::Assistant));
+        assert!(
+            chunks[1..]
+                .iter()
+                .all(|c| c.choices[0].delta.role.is_none())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_function_call_split_across_events() {
+        const SSE: &[u8] = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"name\":\"get_weather\",\"partialArgs\":[{\"jsonPath\":\"$.location\",\"stringValue\":\"San \",\"willContinue\":true}],\"willContinue\":true}}]}}]}\n\n\
+data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"partialArgs\":[{\"jsonPath\":\"$.location\",\"stringValue\":\"Francisco\"},{\"jsonPath\":\"$.days\",\"numberValue\":3}]}}]},\"finishReason\":\"STOP\"}]}\n\n";
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![Ok(
+            bytes::Bytes::from_static(SSE),
+        )]));
+        let chunks: Vec<_> = SseStream::new(inner, "gemini".to_string(), "id".to_string(), false)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        // Nothing is emitted for the first, incomplete event.
+        assert_eq!(chunks.len(), 1);
+        let choice = &chunks[0].choices[0];
+        assert_eq!(choice.delta.role, Some(Role::Assistant));
+        let calls = choice.delta.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 1);
+        let function = calls[0].function.as_ref().unwrap();
+        assert_eq!(function.name.as_deref(), Some("get_weather"));
+        let args: serde_json::Value =
+            serde_json::from_str(function.arguments.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            args,
+            serde_json::json!({"location": "San Francisco", "days": 3})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_tool_call_indices_across_events() {
+        const SSE: &[u8] = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"name\":\"get_weather\",\"args\":{\"location\":\"Paris\"}}}]}}]}\n\n\
+data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"name\":\"get_time\",\"args\":{\"zone\":\"CET\"}}}]},\"finishReason\":\"STOP\"}]}\n\n";
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![Ok(
+            bytes::Bytes::from_static(SSE),
+        )]));
+        let chunks: Vec<_> = SseStream::new(inner, "gemini".to_string(), "id".to_string(), false)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let calls: Vec<_> = chunks
+            .iter()
+            .flat_map(|c| c.choices[0].delta.tool_calls.clone().unwrap_or_default())
+            .map(|call| (call.index, call.function.unwrap().name.unwrap()))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![(0, "get_weather".to_string()), (1, "get_time".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_finish_reason_after_tool_calls() {
+        const SSE: &[u8] = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"name\":\"get_weather\",\"args\":{\"location\":\"Paris\"}}}]}}]}\n\n\
+data: {\"candidates\":[{\"finishReason\":\"STOP\"}]}\n\n";
+        let inner: ByteStream = Box::pin(futures_util::stream::iter(vec![Ok(
+            bytes::Bytes::from_static(SSE),
+        )]));
+        let chunks: Vec<_> = SseStream::new(inner, "gemini".to_string(), "id".to_string(), false)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let last = chunks.last().unwrap();
+        assert!(last.choices[0].delta.tool_calls.is_none());
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::ToolCalls));
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_without_include_usage() {
+        let chunks = collect(false).await;
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|c| c.usage.is_none()));
+    }
+
+    #[test]
+    fn test_empty_messages_are_rejected() {
+        let backend = test_backend();
+        let req = |messages: serde_json::Value| -> CreateChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({"model": "", "messages": messages})).unwrap()
+        };
+
+        assert!(matches!(
+            backend.build_request(&req(serde_json::json!([]))),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(matches!(
+            backend.build_request(&req(serde_json::json!([
+                {"role": "system", "content": "Be brief."}
+            ]))),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+        assert!(
+            backend
+                .build_request(&req(serde_json::json!([
+                    {"role": "system", "content": "Be brief."},
+                    {"role": "user", "content": "Hi"}
+                ])))
+                .is_ok()
+        );
+    }
+}
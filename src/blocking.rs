@@ -0,0 +1,123 @@
+//! A synchronous wrapper around [`CompositeClient`].
+//!
+//! Mirrors `reqwest::blocking`: each client owns a current-thread Tokio
+//! runtime and blocks on it for every call. It must not be used from within
+//! an async runtime, where blocking would panic or deadlock.
+
+use std::sync::Arc;
+
+use async_openai::types::chat::{
+    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+};
+use futures_util::StreamExt;
+use tokio::runtime::Runtime;
+
+use crate::backend::ChatCompletionStream;
+use crate::error::CompositeLlmError;
+use crate::options::ProviderOptions;
+use crate::{BackendKind, CompositeClient};
+
+/// A blocking client wrapping a [`CompositeClient`].
+#[derive(Clone)]
+pub struct BlockingCompositeClient {
+    inner: CompositeClient,
+    runtime: Arc<Runtime>,
+}
+
+impl BlockingCompositeClient {
+    /// Wraps an existing client.
+    pub fn new(client: CompositeClient) -> Result<Self, CompositeLlmError> {
+        Ok(Self {
+            inner: client,
+            runtime: Arc::new(build_runtime()?),
+        })
+    }
+
+    /// Blocking version of [`CompositeClient::from_env_for`].
+    pub fn from_env_for(kind: BackendKind, model: Option<&str>) -> Result<Self, CompositeLlmError> {
+        let runtime = build_runtime()?;
+        let inner = runtime.block_on(CompositeClient::from_env_for(kind, model))?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Sends a chat completion request and blocks until the response arrives.
+    pub fn chat_completion(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.runtime.block_on(self.inner.chat_completion(req))
+    }
+
+    /// Sends a chat completion request with provider-specific options.
+    pub fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        self.runtime
+            .block_on(self.inner.chat_completion_with(req, opts))
+    }
+
+    /// Sends a streaming chat completion request, returning an iterator over
+    /// the chunks.
+    pub fn chat_completion_stream(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<BlockingStream, CompositeLlmError> {
+        let stream = self
+            .runtime
+            .block_on(self.inner.chat_completion_stream(req))?;
+        Ok(self.wrap(stream))
+    }
+
+    /// Sends a streaming chat completion request with provider-specific
+    /// options, returning an iterator over the chunks.
+    pub fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<BlockingStream, CompositeLlmError> {
+        let stream = self
+            .runtime
+            .block_on(self.inner.chat_completion_stream_with(req, opts))?;
+        Ok(self.wrap(stream))
+    }
+
+    /// Returns the wrapped async client.
+    pub fn inner(&self) -> &CompositeClient {
+        &self.inner
+    }
+
+    fn wrap(&self, stream: ChatCompletionStream) -> BlockingStream {
+        BlockingStream {
+            stream,
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+}
+
+/// An iterator over the chunks of a streaming response.
+///
+/// Each call to `next` blocks until the next chunk arrives.
+pub struct BlockingStream {
+    stream: ChatCompletionStream,
+    runtime: Arc<Runtime>,
+}
+
+impl Iterator for BlockingStream {
+    type Item = Result<CreateChatCompletionStreamResponse, CompositeLlmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+fn build_runtime() -> Result<Runtime, CompositeLlmError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CompositeLlmError::Config(format!("failed to build Tokio runtime: {e}")))
+}
@@ -1,25 +1,37 @@
+use std::collections::HashMap;
+
 use async_openai::types::chat::FunctionCall;
 use async_openai::types::chat::{
-    ChatChoice, ChatChoiceStream, ChatCompletionMessageToolCall, ChatCompletionMessageToolCalls,
-    ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestAssistantMessageContentPart,
-    ChatCompletionRequestDeveloperMessageContent, ChatCompletionRequestDeveloperMessageContentPart,
-    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageContent,
-    ChatCompletionRequestSystemMessageContentPart, ChatCompletionRequestToolMessageContent,
-    ChatCompletionRequestToolMessageContentPart, ChatCompletionRequestUserMessageContent,
+    ChatChoice, ChatChoiceStream, ChatCompletionMessageToolCall,
+    ChatCompletionMessageToolCallChunk, ChatCompletionMessageToolCalls,
+    ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPartFile,
     ChatCompletionRequestUserMessageContentPart, ChatCompletionResponseMessage,
     ChatCompletionStreamResponseDelta, ChatCompletionTools, CompletionUsage,
     CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
-    FinishReason, Role, StopConfiguration,
+    FinishReason, FunctionCallStream, FunctionType, PromptTokensDetails, ReasoningEffort,
+    ResponseFormat, ResponseModalities, Role, StopConfiguration,
 };
 use aws_sdk_bedrockruntime::types::{
-    ContentBlock, ContentBlockDelta, ConversationRole, ConverseStreamOutput,
-    InferenceConfiguration, Message, StopReason, SystemContentBlock, Tool, ToolConfiguration,
-    ToolInputSchema, ToolResultBlock, ToolResultContentBlock, ToolSpecification, ToolUseBlock,
+    CachePointBlock, CachePointType, ContentBlock, ContentBlockDelta, ContentBlockStart,
+    ConversationRole, ConverseStreamOutput, DocumentBlock, DocumentFormat, DocumentSource,
+    GuardrailConfiguration, GuardrailStreamConfiguration, GuardrailTrace, ImageBlock, ImageFormat,
+    ImageSource, InferenceConfiguration, Message, PerformanceConfigLatency,
+    PerformanceConfiguration, PromptVariableValues, ReasoningContentBlock,
+    ReasoningContentBlockDelta, StopReason, SystemContentBlock, TokenUsage, Tool, ToolChoice,
+    ToolConfiguration, ToolInputSchema, ToolResultBlock, ToolResultContentBlock, ToolSpecification,
+    ToolUseBlock,
 };
 
-use crate::error::CompositeLlmError;
+use crate::error::{BedrockErrorKind, CompositeLlmError};
+use crate::options::{
+    BedrockCachePoint, BedrockGuardrail, BedrockManagedPrompt, BedrockOptions, LatencyMode,
+};
 
-use super::{generate_chat_cmpl_id, unix_timestamp};
+use super::{
+    CHAT_COMPLETION_CHUNK_OBJECT, CHAT_COMPLETION_OBJECT, NativeResponse, UserContentPart,
+    assistant_text, developer_text, generate_chat_cmpl_id, max_tokens, system_text, tool_text,
+    unix_timestamp, unsupported_part, usage_chunk, user_parts,
+};
 
 /// Convert `serde_json::Value` to `aws_smithy_types::Document`.
 fn json_to_document(value: serde_json::Value) -> aws_smithy_types::Document {
@@ -27,8 +39,10 @@ fn json_to_document(value: serde_json::Value) -> aws_smithy_types::Document {
         serde_json::Value::Null => aws_smithy_types::Document::Null,
         serde_json::Value::Bool(b) => aws_smithy_types::Document::Bool(b),
         serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                aws_smithy_types::Document::Number(aws_smithy_types::Number::PosInt(i as u64))
+            if let Some(u) = n.as_u64() {
+                aws_smithy_types::Document::Number(aws_smithy_types::Number::PosInt(u))
+            } else if let Some(i) = n.as_i64() {
+                aws_smithy_types::Document::Number(aws_smithy_types::Number::NegInt(i))
             } else if let Some(f) = n.as_f64() {
                 aws_smithy_types::Document::Number(aws_smithy_types::Number::Float(f))
             } else {
@@ -69,72 +83,70 @@ fn document_to_json(doc: &aws_smithy_types::Document) -> serde_json::Value {
     }
 }
 
+/// Splits OpenAI messages into Converse system blocks and messages.
+///
+/// `cache_points` inserts prompt caching checkpoints; see [`BedrockCachePoint`].
+///
+/// A trailing assistant message is kept as the last Converse message, which
+/// Anthropic models treat as a prefill: the response continues from it.
+/// Trailing whitespace is trimmed from it since Anthropic rejects a final
+/// assistant turn ending in whitespace, and a whitespace-only one is dropped.
+/// (OpenAI and Azure treat a trailing
+/// assistant message as plain history and start a new message; Vertex AI
+/// sends it as a final `model` turn, which Gemini may continue or reject.)
+///
+/// An assistant message with tool calls but no text becomes a turn of only
+/// `toolUse` blocks. One with neither is dropped, since Converse rejects
+/// blank content; the user turns around it are then merged, so turns still
+/// alternate.
 pub fn extract_system_and_messages(
     messages: Vec<ChatCompletionRequestMessage>,
+    cache_points: &[BedrockCachePoint],
 ) -> Result<(Vec<SystemContentBlock>, Vec<Message>), CompositeLlmError> {
     let mut system_blocks = Vec::new();
-    let mut bedrock_messages = Vec::new();
+    let mut bedrock_messages: Vec<Message> = Vec::new();
 
-    for msg in messages {
+    for (index, msg) in messages.into_iter().enumerate() {
+        let is_system = matches!(
+            msg,
+            ChatCompletionRequestMessage::System(_) | ChatCompletionRequestMessage::Developer(_)
+        );
         match msg {
             ChatCompletionRequestMessage::System(s) => {
-                let text = match s.content {
-                    ChatCompletionRequestSystemMessageContent::Text(t) => t,
-                    ChatCompletionRequestSystemMessageContent::Array(parts) => parts
-                        .into_iter()
-                        .map(|ChatCompletionRequestSystemMessageContentPart::Text(t)| t.text)
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
-                system_blocks.push(SystemContentBlock::Text(text));
+                system_blocks.push(SystemContentBlock::Text(system_text(&s.content)));
             }
             ChatCompletionRequestMessage::Developer(d) => {
-                let text = match d.content {
-                    ChatCompletionRequestDeveloperMessageContent::Text(t) => t,
-                    ChatCompletionRequestDeveloperMessageContent::Array(parts) => parts
-                        .into_iter()
-                        .map(|ChatCompletionRequestDeveloperMessageContentPart::Text(t)| t.text)
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
-                system_blocks.push(SystemContentBlock::Text(text));
+                system_blocks.push(SystemContentBlock::Text(developer_text(&d.content)));
             }
             ChatCompletionRequestMessage::User(u) => {
-                let text = match u.content {
-                    ChatCompletionRequestUserMessageContent::Text(t) => t,
-                    ChatCompletionRequestUserMessageContent::Array(parts) => parts
-                        .into_iter()
-                        .filter_map(|p| match p {
-                            ChatCompletionRequestUserMessageContentPart::Text(t) => Some(t.text),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
+                let mut content = Vec::new();
+                for part in user_parts(&u.content) {
+                    match part {
+                        UserContentPart::Text(t) => content.push(ContentBlock::Text(t.to_string())),
+                        UserContentPart::Other(
+                            ChatCompletionRequestUserMessageContentPart::File(f),
+                        ) => content.push(convert_file_part(f)?),
+                        UserContentPart::Other(
+                            ChatCompletionRequestUserMessageContentPart::ImageUrl(img),
+                        ) => content
+                            .push(ContentBlock::Image(convert_image_url(&img.image_url.url)?)),
+                        UserContentPart::Other(other) => {
+                            return Err(unsupported_part(other, "Bedrock"));
+                        }
+                    }
+                }
                 bedrock_messages.push(
                     Message::builder()
                         .role(ConversationRole::User)
-                        .content(ContentBlock::Text(text))
+                        .set_content(Some(content))
                         .build()
-                        .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
+                        .map_err(CompositeLlmError::bedrock)?,
                 );
             }
             ChatCompletionRequestMessage::Assistant(a) => {
                 let mut contents = Vec::new();
-                if let Some(content) = a.content {
-                    let text = match content {
-                        ChatCompletionRequestAssistantMessageContent::Text(t) => t,
-                        ChatCompletionRequestAssistantMessageContent::Array(parts) => parts
-                            .into_iter()
-                            .filter_map(|p| match p {
-                                ChatCompletionRequestAssistantMessageContentPart::Text(t) => {
-                                    Some(t.text)
-                                }
-                                _ => None,
-                            })
-                            .collect::<Vec<_>>()
-                            .join("\n"),
-                    };
+                if let Some(ref content) = a.content {
+                    let text = assistant_text(content);
                     if !text.is_empty() {
                         contents.push(ContentBlock::Text(text));
                     }
@@ -151,60 +163,354 @@ pub fn extract_system_and_messages(
                                     .name(&func_call.function.name)
                                     .input(json_to_document(input))
                                     .build()
-                                    .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
+                                    .map_err(CompositeLlmError::bedrock)?,
                             ));
                         }
                     }
                 }
-                if !contents.is_empty() {
+                if contents.is_empty() {
+                    tracing::debug!(index, "dropping empty assistant message");
+                } else {
                     let mut builder = Message::builder().role(ConversationRole::Assistant);
                     for c in contents {
                         builder = builder.content(c);
                     }
-                    bedrock_messages.push(
-                        builder
-                            .build()
-                            .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
-                    );
+                    bedrock_messages.push(builder.build().map_err(CompositeLlmError::bedrock)?);
                 }
             }
             ChatCompletionRequestMessage::Tool(t) => {
-                let text = match t.content {
-                    ChatCompletionRequestToolMessageContent::Text(text) => text,
-                    ChatCompletionRequestToolMessageContent::Array(parts) => parts
-                        .into_iter()
-                        .map(|ChatCompletionRequestToolMessageContentPart::Text(t)| t.text)
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
+                let content = convert_tool_result(tool_text(&t.content)?);
                 let result = ToolResultBlock::builder()
                     .tool_use_id(&t.tool_call_id)
-                    .content(ToolResultContentBlock::Text(text))
+                    .content(content)
                     .build()
-                    .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?;
+                    .map_err(CompositeLlmError::bedrock)?;
                 bedrock_messages.push(
                     Message::builder()
                         .role(ConversationRole::User)
                         .content(ContentBlock::ToolResult(result))
                         .build()
-                        .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
+                        .map_err(CompositeLlmError::bedrock)?,
                 );
             }
             _ => {}
         }
+
+        if cache_points.contains(&BedrockCachePoint::AfterMessage(index)) {
+            if is_system {
+                system_blocks.push(SystemContentBlock::CachePoint(cache_point_block()?));
+            } else if let Some(last) = bedrock_messages.last_mut() {
+                last.content
+                    .push(ContentBlock::CachePoint(cache_point_block()?));
+            }
+        }
+    }
+
+    if cache_points.contains(&BedrockCachePoint::AfterSystem) && !system_blocks.is_empty() {
+        system_blocks.push(SystemContentBlock::CachePoint(cache_point_block()?));
+    }
+
+    let mut messages = merge_adjacent_roles(bedrock_messages)?;
+    if let Some(last) = messages.last_mut()
+        && last.role == ConversationRole::Assistant
+        && let Some(ContentBlock::Text(text)) = last.content.last_mut()
+    {
+        text.truncate(text.trim_end().len());
+        if text.is_empty() {
+            last.content.pop();
+            if last.content.is_empty() {
+                messages.pop();
+            }
+        }
+    }
+
+    Ok((system_blocks, messages))
+}
+
+/// Largest image Bedrock accepts, in decoded bytes.
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest document Bedrock accepts, in decoded bytes.
+pub const MAX_DOCUMENT_BYTES: usize = 4608 * 1024;
+
+/// Converts an OpenAI image URL to a Converse image block.
+///
+/// Converse does not fetch URLs, so only base64 `data:` URIs of PNG, JPEG,
+/// GIF and WebP images are supported. Images over [`MAX_IMAGE_BYTES`] are
+/// rejected before the request is sent; pixel dimensions are not checked.
+fn convert_image_url(url: &str) -> Result<ImageBlock, CompositeLlmError> {
+    let (mime_type, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .ok_or_else(|| {
+            CompositeLlmError::Unsupported(
+                "Bedrock images must be base64 data URIs; image URLs are not fetched".to_string(),
+            )
+        })?;
+
+    let format = match mime_type {
+        "image/png" => ImageFormat::Png,
+        "image/jpeg" | "image/jpg" => ImageFormat::Jpeg,
+        "image/gif" => ImageFormat::Gif,
+        "image/webp" => ImageFormat::Webp,
+        other => {
+            return Err(CompositeLlmError::Unsupported(format!(
+                "unsupported image format: {other}"
+            )));
+        }
+    };
+
+    let bytes = decode_base64(data, "image_url")?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(CompositeLlmError::Unsupported(
+            "image exceeds Bedrock 5MB limit".to_string(),
+        ));
+    }
+
+    ImageBlock::builder()
+        .format(format)
+        .source(ImageSource::Bytes(aws_smithy_types::Blob::new(bytes)))
+        .build()
+        .map_err(CompositeLlmError::bedrock)
+}
+
+/// Converts the text of a tool message to a Converse tool result block.
+///
+/// A base64 image `data:` URI, e.g. a chart drawn by the tool, becomes an
+/// image if it meets the rules for user images, and a JSON object becomes a
+/// `json` block. Anything else, including images Bedrock does not accept
+/// such as SVG, JSON arrays and scalars, is sent as text.
+fn convert_tool_result(text: String) -> ToolResultContentBlock {
+    let trimmed = text.trim();
+    if trimmed.starts_with("data:image/") {
+        match convert_image_url(trimmed) {
+            Ok(image) => return ToolResultContentBlock::Image(image),
+            Err(e) => tracing::debug!(error = %e, "sending a tool result image as text"),
+        }
+    }
+    if let Ok(value @ serde_json::Value::Object(_)) = serde_json::from_str(trimmed) {
+        return ToolResultContentBlock::Json(json_to_document(value));
+    }
+    ToolResultContentBlock::Text(text)
+}
+
+/// Decodes inline base64 content, reporting `field` on failure.
+fn decode_base64(data: &str, field: &str) -> Result<Vec<u8>, CompositeLlmError> {
+    aws_smithy_types::base64::decode(data).map_err(|e| CompositeLlmError::Bedrock {
+        message: format!("invalid base64 {field}: {e}"),
+        kind: BedrockErrorKind::Validation,
+        retryable: false,
+        source: Some(Box::new(e)),
+    })
+}
+
+/// Converts an OpenAI file content part to a Converse document block.
+///
+/// Only inline `file_data` is supported (a `data:<mime>;base64,` URI or raw
+/// base64 with a `filename` extension); uploaded `file_id` references are
+/// rejected. The document format is taken from the MIME type, falling back to
+/// the file extension. Documents over [`MAX_DOCUMENT_BYTES`] are rejected
+/// before the request is sent.
+fn convert_file_part(
+    part: &ChatCompletionRequestMessageContentPartFile,
+) -> Result<ContentBlock, CompositeLlmError> {
+    // `FileObject` fields are private; read them through serde.
+    let file = serde_json::to_value(&part.file)?;
+    let filename = file["filename"].as_str();
+    let file_data = file["file_data"].as_str().ok_or_else(|| {
+        CompositeLlmError::Unsupported(
+            "Bedrock documents require inline file_data; file_id is not supported".to_string(),
+        )
+    })?;
+
+    let (mime_type, data) = match file_data.strip_prefix("data:") {
+        Some(rest) => match rest.split_once(";base64,") {
+            Some((mime_type, data)) => (Some(mime_type), data),
+            None => {
+                return Err(CompositeLlmError::Unsupported(
+                    "only base64 data URIs are supported".to_string(),
+                ));
+            }
+        },
+        None => (None, file_data),
+    };
+
+    let format = mime_type
+        .and_then(document_format_from_mime)
+        .or_else(|| {
+            filename
+                .and_then(|n| n.rsplit_once('.'))
+                .and_then(|(_, ext)| document_format_from_extension(ext))
+        })
+        .ok_or_else(|| {
+            CompositeLlmError::Unsupported(format!(
+                "unsupported document format: {}",
+                mime_type.or(filename).unwrap_or("unknown")
+            ))
+        })?;
+
+    let bytes = decode_base64(data, "file_data")?;
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(CompositeLlmError::Unsupported(
+            "document exceeds Bedrock 4.5MB limit".to_string(),
+        ));
+    }
+
+    let document = DocumentBlock::builder()
+        .format(format)
+        .name(document_name(filename))
+        .source(DocumentSource::Bytes(aws_smithy_types::Blob::new(bytes)))
+        .build()
+        .map_err(CompositeLlmError::bedrock)?;
+    Ok(ContentBlock::Document(document))
+}
+
+fn document_format_from_mime(mime_type: &str) -> Option<DocumentFormat> {
+    match mime_type {
+        "application/pdf" => Some(DocumentFormat::Pdf),
+        "text/csv" => Some(DocumentFormat::Csv),
+        "text/html" => Some(DocumentFormat::Html),
+        "text/markdown" => Some(DocumentFormat::Md),
+        "text/plain" => Some(DocumentFormat::Txt),
+        "application/msword" => Some(DocumentFormat::Doc),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => {
+            Some(DocumentFormat::Docx)
+        }
+        "application/vnd.ms-excel" => Some(DocumentFormat::Xls),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => {
+            Some(DocumentFormat::Xlsx)
+        }
+        _ => None,
+    }
+}
+
+fn document_format_from_extension(ext: &str) -> Option<DocumentFormat> {
+    match ext.to_ascii_lowercase().as_str() {
+        "pdf" => Some(DocumentFormat::Pdf),
+        "csv" => Some(DocumentFormat::Csv),
+        "html" | "htm" => Some(DocumentFormat::Html),
+        "md" | "markdown" => Some(DocumentFormat::Md),
+        "txt" => Some(DocumentFormat::Txt),
+        "doc" => Some(DocumentFormat::Doc),
+        "docx" => Some(DocumentFormat::Docx),
+        "xls" => Some(DocumentFormat::Xls),
+        "xlsx" => Some(DocumentFormat::Xlsx),
+        _ => None,
+    }
+}
+
+/// Derives a document name Bedrock accepts: alphanumerics, single spaces,
+/// hyphens, parentheses and square brackets only.
+fn document_name(filename: Option<&str>) -> String {
+    let stem = filename
+        .map(|n| n.rsplit_once('.').map_or(n, |(stem, _)| stem))
+        .unwrap_or("");
+    let sanitized: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '(' | ')' | '[' | ']') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = sanitized.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        "document".to_string()
+    } else {
+        name
+    }
+}
+
+fn cache_point_block() -> Result<CachePointBlock, CompositeLlmError> {
+    CachePointBlock::builder()
+        .r#type(CachePointType::Default)
+        .build()
+        .map_err(CompositeLlmError::bedrock)
+}
+
+/// Merges consecutive messages with the same role into one message.
+///
+/// The Converse API requires user and assistant turns to alternate, while
+/// OpenAI conversations may contain several user (or tool result) messages in
+/// a row. Content blocks of adjacent same-role messages are concatenated.
+fn merge_adjacent_roles(messages: Vec<Message>) -> Result<Vec<Message>, CompositeLlmError> {
+    let mut merged: Vec<(ConversationRole, Vec<ContentBlock>)> = Vec::new();
+    for msg in messages {
+        match merged.last_mut() {
+            Some((role, content)) if *role == msg.role => content.extend(msg.content),
+            _ => merged.push((msg.role, msg.content)),
+        }
     }
 
-    Ok((system_blocks, bedrock_messages))
+    merged
+        .into_iter()
+        .map(|(role, content)| {
+            Message::builder()
+                .role(role)
+                .set_content(Some(content))
+                .build()
+                .map_err(CompositeLlmError::bedrock)
+        })
+        .collect()
+}
+
+/// Builds a system instruction enforcing `response_format`.
+///
+/// Converse has no native JSON mode, so `json_object` and `json_schema` are
+/// steered with an extra system prompt. Returns `None` for plain text.
+pub fn build_json_mode_system_block(
+    req: &CreateChatCompletionRequest,
+) -> Result<Option<SystemContentBlock>, CompositeLlmError> {
+    let instruction = match req.response_format {
+        None | Some(ResponseFormat::Text) => return Ok(None),
+        Some(ResponseFormat::JsonObject) => {
+            "Respond only with a valid JSON object. Do not include any other text.".to_string()
+        }
+        Some(ResponseFormat::JsonSchema { ref json_schema }) => match json_schema.schema {
+            Some(ref schema) => format!(
+                "Respond only with valid JSON that matches the following JSON Schema. \
+                 Do not include any other text.\n{}",
+                serde_json::to_string(schema)?
+            ),
+            None => "Respond only with valid JSON. Do not include any other text.".to_string(),
+        },
+    };
+    Ok(Some(SystemContentBlock::Text(instruction)))
+}
+
+/// Builds a system instruction for `parallel_tool_calls: false`.
+///
+/// Converse has no model-independent switch for parallel tool use, so the
+/// model is asked to call at most one tool per turn. This is steering, not a
+/// guarantee. Returns `None` unless tools are given and parallel calls are
+/// disabled.
+pub fn build_parallel_tool_calls_system_block(
+    req: &CreateChatCompletionRequest,
+) -> Option<SystemContentBlock> {
+    let has_tools = req.tools.as_ref().is_some_and(|t| !t.is_empty());
+    (has_tools && req.parallel_tool_calls == Some(false)).then(|| {
+        SystemContentBlock::Text(
+            "Call at most one tool per response. Wait for its result before calling another."
+                .to_string(),
+        )
+    })
 }
 
-pub fn build_inference_config(req: &CreateChatCompletionRequest) -> Option<InferenceConfiguration> {
+/// Maximum number of stop sequences accepted by Converse.
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
+pub fn build_inference_config(
+    req: &CreateChatCompletionRequest,
+) -> Result<Option<InferenceConfiguration>, CompositeLlmError> {
     let has_params = req.temperature.is_some()
         || req.top_p.is_some()
-        || req.max_completion_tokens.is_some()
+        || max_tokens(req).is_some()
         || req.stop.is_some();
 
     if !has_params {
-        return None;
+        return Ok(None);
     }
 
     let mut builder = InferenceConfiguration::builder();
@@ -215,7 +521,7 @@ pub fn build_inference_config(req: &CreateChatCompletionRequest) -> Option<Infer
     if let Some(top_p) = req.top_p {
         builder = builder.top_p(top_p);
     }
-    if let Some(max_tokens) = req.max_completion_tokens {
+    if let Some(max_tokens) = max_tokens(req) {
         builder = builder.max_tokens(max_tokens as i32);
     }
     if let Some(ref stop) = req.stop {
@@ -224,6 +530,12 @@ pub fn build_inference_config(req: &CreateChatCompletionRequest) -> Option<Infer
                 builder = builder.stop_sequences(s.clone());
             }
             StopConfiguration::StringArray(arr) => {
+                if arr.len() > MAX_STOP_SEQUENCES {
+                    return Err(CompositeLlmError::Unsupported(format!(
+                        "Bedrock accepts at most {MAX_STOP_SEQUENCES} stop sequences, got {}",
+                        arr.len()
+                    )));
+                }
                 for s in arr {
                     builder = builder.stop_sequences(s.clone());
                 }
@@ -231,7 +543,7 @@ pub fn build_inference_config(req: &CreateChatCompletionRequest) -> Option<Infer
         }
     }
 
-    Some(builder.build())
+    Ok(Some(builder.build()))
 }
 
 pub fn build_tool_config(
@@ -266,9 +578,7 @@ pub fn build_tool_config(
         }
 
         tool_list.push(Tool::ToolSpec(
-            spec_builder
-                .build()
-                .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
+            spec_builder.build().map_err(CompositeLlmError::bedrock)?,
         ));
     }
 
@@ -278,12 +588,332 @@ pub fn build_tool_config(
     }
 
     Ok(Some(
-        config_builder
-            .build()
-            .map_err(|e| CompositeLlmError::Bedrock(e.to_string()))?,
+        config_builder.build().map_err(CompositeLlmError::bedrock)?,
     ))
 }
 
+/// Converts managed prompt variables to Converse `promptVariables`.
+pub fn build_prompt_variables(
+    prompt: &BedrockManagedPrompt,
+) -> HashMap<String, PromptVariableValues> {
+    prompt
+        .variables
+        .iter()
+        .map(|(name, value)| (name.clone(), PromptVariableValues::Text(value.clone())))
+        .collect()
+}
+
+/// Rejects request parameters Converse cannot honor.
+///
+/// - `reasoning_effort` has no model-independent equivalent;
+///   `ReasoningEffort::None` asks for no reasoning and is accepted. Extended
+///   thinking is requested with [`BedrockOptions::thinking_budget`] instead.
+/// - `logprobs`/`top_logprobs`: Converse does not return log probabilities.
+pub fn check_unsupported_params(
+    req: &CreateChatCompletionRequest,
+) -> Result<(), CompositeLlmError> {
+    if !matches!(req.reasoning_effort, None | Some(ReasoningEffort::None)) {
+        return Err(CompositeLlmError::Unsupported(
+            "reasoning_effort is not supported by Bedrock; use BedrockOptions::thinking_budget"
+                .to_string(),
+        ));
+    }
+    if req.logprobs == Some(true) || req.top_logprobs.is_some() {
+        return Err(CompositeLlmError::Unsupported(
+            "logprobs are not supported by Bedrock".to_string(),
+        ));
+    }
+    if let Some(ref modalities) = req.modalities
+        && modalities.iter().any(|m| *m != ResponseModalities::Text)
+    {
+        return Err(CompositeLlmError::Unsupported(
+            "output modalities other than text are not supported by Bedrock".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Build `additionalModelRequestFields` from the raw fields plus `top_k`/`thinking_budget`.
+pub fn build_additional_model_request_fields(
+    opts: &BedrockOptions,
+) -> Result<Option<aws_smithy_types::Document>, CompositeLlmError> {
+    let mut fields = match opts.additional_model_request_fields {
+        Some(serde_json::Value::Object(ref map)) => map.clone(),
+        Some(_) => {
+            return Err(CompositeLlmError::Unsupported(
+                "additional_model_request_fields must be a JSON object".to_string(),
+            ));
+        }
+        None => serde_json::Map::new(),
+    };
+
+    if let Some(top_k) = opts.top_k {
+        fields.insert("top_k".to_string(), serde_json::json!(top_k));
+    }
+    if let Some(budget) = opts.thinking_budget {
+        fields.insert(
+            "thinking".to_string(),
+            serde_json::json!({"type": "enabled", "budget_tokens": budget}),
+        );
+    }
+
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(json_to_document(serde_json::Value::Object(fields))))
+}
+
+fn guardrail_trace(guardrail: &BedrockGuardrail) -> GuardrailTrace {
+    if guardrail.trace {
+        GuardrailTrace::Enabled
+    } else {
+        GuardrailTrace::Disabled
+    }
+}
+
+pub fn build_performance_config(latency: LatencyMode) -> PerformanceConfiguration {
+    let latency = match latency {
+        LatencyMode::Standard => PerformanceConfigLatency::Standard,
+        LatencyMode::Optimized => PerformanceConfigLatency::Optimized,
+    };
+    PerformanceConfiguration::builder().latency(latency).build()
+}
+
+pub fn build_guardrail_config(guardrail: &BedrockGuardrail) -> GuardrailConfiguration {
+    GuardrailConfiguration::builder()
+        .guardrail_identifier(&guardrail.identifier)
+        .guardrail_version(&guardrail.version)
+        .trace(guardrail_trace(guardrail))
+        .build()
+}
+
+pub fn build_guardrail_stream_config(guardrail: &BedrockGuardrail) -> GuardrailStreamConfiguration {
+    GuardrailStreamConfiguration::builder()
+        .guardrail_identifier(&guardrail.identifier)
+        .guardrail_version(&guardrail.version)
+        .trace(guardrail_trace(guardrail))
+        .build()
+}
+
+/// The parts of a Converse request shared by `Converse` and `ConverseStream`.
+#[derive(Debug, Clone)]
+pub struct ConverseInput {
+    /// The model ID, or managed prompt ARN, to invoke.
+    pub model_id: String,
+    pub system: Vec<SystemContentBlock>,
+    pub messages: Vec<Message>,
+    pub inference_config: Option<InferenceConfiguration>,
+    pub tool_config: Option<ToolConfiguration>,
+    pub additional_model_request_fields: Option<aws_smithy_types::Document>,
+    pub prompt_variables: Option<HashMap<String, PromptVariableValues>>,
+    pub guardrail: Option<BedrockGuardrail>,
+    pub performance_config: Option<PerformanceConfiguration>,
+}
+
+impl ConverseInput {
+    /// Renders the request as the JSON body of the Converse REST API.
+    ///
+    /// The SDK types are not serializable, so this mirrors the wire format
+    /// for inspection and fixtures; binary sources are base64 encoded.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut body = serde_json::Map::new();
+        body.insert("modelId".to_string(), self.model_id.clone().into());
+        if !self.messages.is_empty() {
+            let messages = self
+                .messages
+                .iter()
+                .map(|m| {
+                    serde_json::json!({
+                        "role": m.role().as_str(),
+                        "content": m.content().iter().map(content_block_to_json).collect::<Vec<_>>(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            body.insert("messages".to_string(), messages.into());
+        }
+        if !self.system.is_empty() {
+            let system = self
+                .system
+                .iter()
+                .map(|block| match block {
+                    SystemContentBlock::Text(text) => serde_json::json!({"text": text}),
+                    SystemContentBlock::CachePoint(cp) => cache_point_to_json(cp),
+                    other => unknown_to_json(other),
+                })
+                .collect::<Vec<_>>();
+            body.insert("system".to_string(), system.into());
+        }
+        if let Some(ref config) = self.inference_config {
+            let mut value = serde_json::Map::new();
+            if let Some(max_tokens) = config.max_tokens() {
+                value.insert("maxTokens".to_string(), max_tokens.into());
+            }
+            if let Some(temperature) = config.temperature() {
+                value.insert("temperature".to_string(), temperature.into());
+            }
+            if let Some(top_p) = config.top_p() {
+                value.insert("topP".to_string(), top_p.into());
+            }
+            if !config.stop_sequences().is_empty() {
+                value.insert("stopSequences".to_string(), config.stop_sequences().into());
+            }
+            body.insert("inferenceConfig".to_string(), value.into());
+        }
+        if let Some(ref config) = self.tool_config {
+            body.insert("toolConfig".to_string(), tool_config_to_json(config));
+        }
+        if let Some(ref guardrail) = self.guardrail {
+            body.insert(
+                "guardrailConfig".to_string(),
+                serde_json::json!({
+                    "guardrailIdentifier": guardrail.identifier,
+                    "guardrailVersion": guardrail.version,
+                    "trace": guardrail_trace(guardrail).as_str(),
+                }),
+            );
+        }
+        if let Some(ref config) = self.performance_config {
+            body.insert(
+                "performanceConfig".to_string(),
+                serde_json::json!({"latency": config.latency().as_str()}),
+            );
+        }
+        if let Some(ref fields) = self.additional_model_request_fields {
+            body.insert(
+                "additionalModelRequestFields".to_string(),
+                document_to_json(fields),
+            );
+        }
+        if let Some(ref variables) = self.prompt_variables {
+            let variables = variables
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        PromptVariableValues::Text(text) => serde_json::json!({"text": text}),
+                        other => unknown_to_json(other),
+                    };
+                    (name.clone(), value)
+                })
+                .collect::<serde_json::Map<_, _>>();
+            body.insert("promptVariables".to_string(), variables.into());
+        }
+        serde_json::Value::Object(body)
+    }
+}
+
+fn content_block_to_json(block: &ContentBlock) -> serde_json::Value {
+    match block {
+        ContentBlock::Text(text) => serde_json::json!({"text": text}),
+        ContentBlock::ToolUse(tu) => serde_json::json!({
+            "toolUse": {
+                "toolUseId": tu.tool_use_id(),
+                "name": tu.name(),
+                "input": document_to_json(tu.input()),
+            }
+        }),
+        ContentBlock::ToolResult(tr) => {
+            let content = tr
+                .content()
+                .iter()
+                .map(|c| match c {
+                    ToolResultContentBlock::Text(text) => serde_json::json!({"text": text}),
+                    ToolResultContentBlock::Json(doc) => {
+                        serde_json::json!({"json": document_to_json(doc)})
+                    }
+                    ToolResultContentBlock::Image(image) => image_to_json(image),
+                    other => unknown_to_json(other),
+                })
+                .collect::<Vec<_>>();
+            let mut value = serde_json::json!({
+                "toolUseId": tr.tool_use_id(),
+                "content": content,
+            });
+            if let Some(status) = tr.status() {
+                value["status"] = status.as_str().into();
+            }
+            serde_json::json!({"toolResult": value})
+        }
+        ContentBlock::Document(doc) => {
+            let source = match doc.source() {
+                Some(DocumentSource::Bytes(blob)) => {
+                    serde_json::json!({"bytes": aws_smithy_types::base64::encode(blob.as_ref())})
+                }
+                Some(other) => unknown_to_json(other),
+                None => serde_json::Value::Null,
+            };
+            serde_json::json!({
+                "document": {
+                    "format": doc.format().as_str(),
+                    "name": doc.name(),
+                    "source": source,
+                }
+            })
+        }
+        ContentBlock::Image(image) => image_to_json(image),
+        ContentBlock::CachePoint(cp) => cache_point_to_json(cp),
+        other => unknown_to_json(other),
+    }
+}
+
+fn image_to_json(image: &ImageBlock) -> serde_json::Value {
+    let source = match image.source() {
+        Some(ImageSource::Bytes(blob)) => {
+            serde_json::json!({"bytes": aws_smithy_types::base64::encode(blob.as_ref())})
+        }
+        Some(other) => unknown_to_json(other),
+        None => serde_json::Value::Null,
+    };
+    serde_json::json!({
+        "image": {
+            "format": image.format().as_str(),
+            "source": source,
+        }
+    })
+}
+
+fn cache_point_to_json(cp: &CachePointBlock) -> serde_json::Value {
+    serde_json::json!({"cachePoint": {"type": cp.r#type().as_str()}})
+}
+
+fn tool_config_to_json(config: &ToolConfiguration) -> serde_json::Value {
+    let tools = config
+        .tools()
+        .iter()
+        .map(|tool| match tool {
+            Tool::ToolSpec(spec) => {
+                let mut value = serde_json::json!({"name": spec.name()});
+                if let Some(description) = spec.description() {
+                    value["description"] = description.into();
+                }
+                if let Some(ToolInputSchema::Json(schema)) = spec.input_schema() {
+                    value["inputSchema"] = serde_json::json!({"json": document_to_json(schema)});
+                }
+                serde_json::json!({"toolSpec": value})
+            }
+            Tool::CachePoint(cp) => cache_point_to_json(cp),
+            other => unknown_to_json(other),
+        })
+        .collect::<Vec<_>>();
+
+    let mut value = serde_json::json!({"tools": tools});
+    if let Some(choice) = config.tool_choice() {
+        value["toolChoice"] = match choice {
+            ToolChoice::Auto(_) => serde_json::json!({"auto": {}}),
+            ToolChoice::Any(_) => serde_json::json!({"any": {}}),
+            ToolChoice::Tool(tool) => serde_json::json!({"tool": {"name": tool.name()}}),
+            other => unknown_to_json(other),
+        };
+    }
+    value
+}
+
+/// Renders content this crate never produces by its debug representation, so
+/// a preview does not silently drop it.
+fn unknown_to_json(value: &impl std::fmt::Debug) -> serde_json::Value {
+    serde_json::json!({"unknown": format!("{value:?}")})
+}
+
 pub fn convert_stop_reason(reason: &StopReason) -> FinishReason {
     match reason {
         StopReason::EndTurn | StopReason::StopSequence => FinishReason::Stop,
@@ -293,12 +923,87 @@ pub fn convert_stop_reason(reason: &StopReason) -> FinishReason {
     }
 }
 
+/// Converts Converse token usage to OpenAI usage.
+///
+/// Bedrock reports prompt cache reads and writes separately from
+/// `inputTokens`, whereas OpenAI counts cached tokens as part of
+/// `prompt_tokens`. Both are therefore added to `prompt_tokens`, and cache
+/// reads are reported as `prompt_tokens_details.cached_tokens`.
+pub fn convert_token_usage(usage: &TokenUsage) -> CompletionUsage {
+    let cache_read = usage.cache_read_input_tokens().unwrap_or(0).max(0) as u32;
+    let cache_write = usage.cache_write_input_tokens().unwrap_or(0).max(0) as u32;
+    let prompt_tokens = usage.input_tokens() as u32 + cache_read + cache_write;
+    let completion_tokens = usage.output_tokens() as u32;
+
+    let prompt_tokens_details = (usage.cache_read_input_tokens().is_some()
+        || usage.cache_write_input_tokens().is_some())
+    .then_some(PromptTokensDetails {
+        audio_tokens: None,
+        cached_tokens: Some(cache_read),
+    });
+
+    CompletionUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        prompt_tokens_details,
+        completion_tokens_details: None,
+    }
+}
+
+/// Opening tag wrapping reasoning (extended thinking) text in message content.
+pub const THINKING_OPEN_TAG: &str = "<thinking>";
+/// Closing tag wrapping reasoning (extended thinking) text in message content.
+pub const THINKING_CLOSE_TAG: &str = "</thinking>";
+
+/// Returns the model-specific `additionalModelResponseFields` of a response,
+/// such as the `stop_sequence` Anthropic models report.
+pub fn additional_model_response_fields(
+    output: &aws_sdk_bedrockruntime::operation::converse::ConverseOutput,
+) -> Option<serde_json::Value> {
+    output
+        .additional_model_response_fields()
+        .map(document_to_json)
+}
+
+/// Returns the raw `stopReason` of a response, e.g. `end_turn` or
+/// `stop_sequence`, which [`convert_stop_reason`] maps to the same
+/// [`FinishReason::Stop`].
+pub fn raw_stop_reason(
+    output: &aws_sdk_bedrockruntime::operation::converse::ConverseOutput,
+) -> &str {
+    output.stop_reason().as_str()
+}
+
+impl TryFrom<NativeResponse<'_, aws_sdk_bedrockruntime::operation::converse::ConverseOutput>>
+    for CreateChatCompletionResponse
+{
+    type Error = CompositeLlmError;
+
+    /// Converts with [`convert_converse_response`].
+    fn try_from(
+        native: NativeResponse<'_, aws_sdk_bedrockruntime::operation::converse::ConverseOutput>,
+    ) -> Result<Self, Self::Error> {
+        convert_converse_response(&native.response, native.model, &generate_chat_cmpl_id())
+    }
+}
+
+/// Converts a Converse response to an OpenAI response.
+///
+/// Reasoning (extended thinking) text has no dedicated field in the OpenAI
+/// message, so it is prepended to the content wrapped in
+/// [`THINKING_OPEN_TAG`]/[`THINKING_CLOSE_TAG`]. Redacted reasoning is dropped.
+/// `additionalModelResponseFields` are logged at debug level.
+/// The response carries the given `id`, e.g. one from
+/// [`generate_chat_cmpl_id`](super::generate_chat_cmpl_id).
 #[allow(deprecated)]
 pub fn convert_converse_response(
     output: &aws_sdk_bedrockruntime::operation::converse::ConverseOutput,
     model: &str,
+    id: &str,
 ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
     let mut text_content = String::new();
+    let mut reasoning = String::new();
     let mut tool_calls: Vec<ChatCompletionMessageToolCalls> = Vec::new();
 
     if let Some(aws_sdk_bedrockruntime::types::ConverseOutput::Message(ref msg)) = output.output {
@@ -307,6 +1012,9 @@ pub fn convert_converse_response(
                 ContentBlock::Text(t) => {
                     text_content.push_str(t);
                 }
+                ContentBlock::ReasoningContent(ReasoningContentBlock::ReasoningText(r)) => {
+                    reasoning.push_str(r.text());
+                }
                 ContentBlock::ToolUse(tu) => {
                     let args = serde_json::to_string(&document_to_json(tu.input()))
                         .unwrap_or_else(|_| "{}".to_string());
@@ -327,6 +1035,14 @@ pub fn convert_converse_response(
 
     let finish_reason = convert_stop_reason(output.stop_reason());
 
+    if let Some(fields) = additional_model_response_fields(output) {
+        tracing::debug!(%fields, "Bedrock additionalModelResponseFields");
+    }
+
+    if !reasoning.is_empty() {
+        text_content = format!("{THINKING_OPEN_TAG}{reasoning}{THINKING_CLOSE_TAG}{text_content}");
+    }
+
     let message = ChatCompletionResponseMessage {
         content: if text_content.is_empty() {
             None
@@ -345,17 +1061,11 @@ pub fn convert_converse_response(
         annotations: None,
     };
 
-    let usage = output.usage().map(|u| CompletionUsage {
-        prompt_tokens: u.input_tokens() as u32,
-        completion_tokens: u.output_tokens() as u32,
-        total_tokens: (u.input_tokens() + u.output_tokens()) as u32,
-        prompt_tokens_details: None,
-        completion_tokens_details: None,
-    });
+    let usage = output.usage().map(convert_token_usage);
 
     Ok(CreateChatCompletionResponse {
-        id: generate_chat_cmpl_id(),
-        object: "chat.completion".to_string(),
+        id: id.to_string(),
+        object: CHAT_COMPLETION_OBJECT.to_string(),
         created: unix_timestamp(),
         model: model.to_string(),
         choices: vec![ChatChoice {
@@ -370,47 +1080,175 @@ pub fn convert_converse_response(
     })
 }
 
+/// State carried across the events of a single Converse stream.
+#[derive(Debug)]
+pub struct StreamState {
+    reasoning_open: bool,
+    include_usage: bool,
+    /// OpenAI tool call index by Converse content block index.
+    tool_calls: HashMap<i32, u32>,
+    /// Creation time shared by every chunk of the stream.
+    created: u32,
+}
+
+impl StreamState {
+    /// Creates the state for a new stream, starting now.
+    ///
+    /// `include_usage` mirrors `stream_options.include_usage`: when false the
+    /// final usage chunk is suppressed.
+    pub fn new(include_usage: bool) -> Self {
+        Self {
+            reasoning_open: false,
+            include_usage,
+            tool_calls: HashMap::new(),
+            created: unix_timestamp(),
+        }
+    }
+}
+
+impl Default for StreamState {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+#[allow(deprecated)]
+fn tool_call_chunk(
+    call: ChatCompletionMessageToolCallChunk,
+    model: &str,
+    id: &str,
+) -> CreateChatCompletionStreamResponse {
+    let mut chunk = content_chunk(String::new(), model, id);
+    chunk.choices[0].delta.content = None;
+    chunk.choices[0].delta.tool_calls = Some(vec![call]);
+    chunk
+}
+
 #[allow(deprecated)]
+fn content_chunk(text: String, model: &str, id: &str) -> CreateChatCompletionStreamResponse {
+    CreateChatCompletionStreamResponse {
+        id: id.to_string(),
+        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+        created: unix_timestamp(),
+        model: model.to_string(),
+        choices: vec![ChatChoiceStream {
+            index: 0,
+            delta: ChatCompletionStreamResponseDelta {
+                content: Some(text),
+                tool_calls: None,
+                role: None,
+                function_call: None,
+                refusal: None,
+            },
+            finish_reason: None,
+            logprobs: None,
+        }],
+        usage: None,
+        system_fingerprint: None,
+        service_tier: None,
+    }
+}
+
+/// Converts a Converse stream event to an OpenAI stream chunk.
+///
+/// `messageStart` yields an OpenAI-style opening chunk (`role: assistant`,
+/// empty content), so the stream `id` is available before any content.
+/// Reasoning deltas are emitted as content wrapped in
+/// [`THINKING_OPEN_TAG`]/[`THINKING_CLOSE_TAG`], matching
+/// [`convert_converse_response`].
+///
+/// A tool use block becomes a tool call: its start carries the id and name,
+/// and each partial JSON input delta is emitted as an `arguments` fragment
+/// under the same tool call index.
+///
+/// Every chunk carries the creation time of `state`, i.e. when the stream
+/// started.
 pub fn stream_event_to_response(
     event: &ConverseStreamOutput,
     model: &str,
     id: &str,
+    state: &mut StreamState,
+) -> Option<CreateChatCompletionStreamResponse> {
+    let created = state.created;
+    let mut chunk = convert_stream_event(event, model, id, state)?;
+    chunk.created = created;
+    Some(chunk)
+}
+
+#[allow(deprecated)]
+fn convert_stream_event(
+    event: &ConverseStreamOutput,
+    model: &str,
+    id: &str,
+    state: &mut StreamState,
 ) -> Option<CreateChatCompletionStreamResponse> {
     match event {
+        ConverseStreamOutput::ContentBlockStart(start) => {
+            let Some(ContentBlockStart::ToolUse(tool_use)) = start.start() else {
+                return None;
+            };
+            let index = state.tool_calls.len() as u32;
+            state.tool_calls.insert(start.content_block_index(), index);
+            let call = ChatCompletionMessageToolCallChunk {
+                index,
+                id: Some(tool_use.tool_use_id().to_string()),
+                r#type: Some(FunctionType::Function),
+                function: Some(FunctionCallStream {
+                    name: Some(tool_use.name().to_string()),
+                    arguments: Some(String::new()),
+                }),
+            };
+            Some(tool_call_chunk(call, model, id))
+        }
         ConverseStreamOutput::ContentBlockDelta(delta) => {
+            if let Some(ContentBlockDelta::ToolUse(tool_use)) = delta.delta() {
+                let index = *state.tool_calls.get(&delta.content_block_index())?;
+                let call = ChatCompletionMessageToolCallChunk {
+                    index,
+                    id: None,
+                    r#type: None,
+                    function: Some(FunctionCallStream {
+                        name: None,
+                        arguments: Some(tool_use.input().to_string()),
+                    }),
+                };
+                return Some(tool_call_chunk(call, model, id));
+            }
+
             let content = delta.delta().and_then(|d| match d {
+                ContentBlockDelta::Text(t) if state.reasoning_open => {
+                    state.reasoning_open = false;
+                    Some(format!("{THINKING_CLOSE_TAG}{t}"))
+                }
                 ContentBlockDelta::Text(t) => Some(t.to_string()),
+                ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Text(t)) => {
+                    if state.reasoning_open {
+                        Some(t.to_string())
+                    } else {
+                        state.reasoning_open = true;
+                        Some(format!("{THINKING_OPEN_TAG}{t}"))
+                    }
+                }
                 _ => None,
             });
 
-            content.map(|text| CreateChatCompletionStreamResponse {
-                id: id.to_string(),
-                object: "chat.completion.chunk".to_string(),
-                created: unix_timestamp(),
-                model: model.to_string(),
-                choices: vec![ChatChoiceStream {
-                    index: 0,
-                    delta: ChatCompletionStreamResponseDelta {
-                        content: Some(text),
-                        tool_calls: None,
-                        role: None,
-                        function_call: None,
-                        refusal: None,
-                    },
-                    finish_reason: None,
-                    logprobs: None,
-                }],
-                usage: None,
-                system_fingerprint: None,
-                service_tier: None,
-            })
+            content.map(|text| content_chunk(text, model, id))
+        }
+        ConverseStreamOutput::MessageStart(_) => {
+            let mut chunk = content_chunk(String::new(), model, id);
+            chunk.choices[0].delta.role = Some(Role::Assistant);
+            Some(chunk)
+        }
+        ConverseStreamOutput::ContentBlockStop(_) if state.reasoning_open => {
+            state.reasoning_open = false;
+            Some(content_chunk(THINKING_CLOSE_TAG.to_string(), model, id))
         }
         ConverseStreamOutput::MessageStop(stop) => {
             let finish_reason = convert_stop_reason(stop.stop_reason());
 
             Some(CreateChatCompletionStreamResponse {
                 id: id.to_string(),
-                object: "chat.completion.chunk".to_string(),
+                object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
                 created: unix_timestamp(),
                 model: model.to_string(),
                 choices: vec![ChatChoiceStream {
@@ -430,27 +1268,137 @@ pub fn stream_event_to_response(
                 service_tier: None,
             })
         }
-        ConverseStreamOutput::Metadata(meta) => {
-            let usage = meta.usage().map(|u| CompletionUsage {
-                prompt_tokens: u.input_tokens() as u32,
-                completion_tokens: u.output_tokens() as u32,
-                total_tokens: (u.input_tokens() + u.output_tokens()) as u32,
-                prompt_tokens_details: None,
-                completion_tokens_details: None,
-            });
+        ConverseStreamOutput::Metadata(meta) if state.include_usage => meta
+            .usage()
+            .map(|u| usage_chunk(convert_token_usage(u), model, id)),
+        _ => None,
+    }
+}
 
-            usage.map(|u| CreateChatCompletionStreamResponse {
-                id: id.to_string(),
-                object: "chat.completion.chunk".to_string(),
-                created: unix_timestamp(),
-                model: model.to_string(),
-                choices: vec![],
-                usage: Some(u),
-                system_fingerprint: None,
-                service_tier: None,
+/// Assembles the events of a Converse stream into a single response.
+///
+/// Unlike [`collect_stream`](crate::stream::collect_stream), which works on
+/// converted OpenAI chunks, this consumes the raw [`ConverseStreamOutput`]
+/// events, as returned by
+/// [`BedrockBackend::converse_stream_events`](crate::backend::bedrock::BedrockBackend::converse_stream_events):
+/// tool use input arriving as partial JSON fragments is joined per content
+/// block, so every tool call ends up with its complete `arguments`.
+/// Text and reasoning are combined like [`convert_converse_response`] does.
+#[derive(Debug, Default)]
+pub struct BedrockStreamAccumulator {
+    text: String,
+    reasoning: String,
+    /// `(id, name, input)` by content block index.
+    tool_calls: std::collections::BTreeMap<i32, (String, String, String)>,
+    stop_reason: Option<StopReason>,
+    usage: Option<CompletionUsage>,
+}
+
+impl BedrockStreamAccumulator {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one stream event.
+    pub fn push(&mut self, event: &ConverseStreamOutput) {
+        match event {
+            ConverseStreamOutput::ContentBlockStart(start) => {
+                if let Some(ContentBlockStart::ToolUse(tool_use)) = start.start() {
+                    let call = self
+                        .tool_calls
+                        .entry(start.content_block_index())
+                        .or_default();
+                    call.0 = tool_use.tool_use_id().to_string();
+                    call.1 = tool_use.name().to_string();
+                }
+            }
+            ConverseStreamOutput::ContentBlockDelta(delta) => match delta.delta() {
+                Some(ContentBlockDelta::Text(t)) => self.text.push_str(t),
+                Some(ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Text(t))) => {
+                    self.reasoning.push_str(t)
+                }
+                Some(ContentBlockDelta::ToolUse(tool_use)) => self
+                    .tool_calls
+                    .entry(delta.content_block_index())
+                    .or_default()
+                    .2
+                    .push_str(tool_use.input()),
+                _ => {}
+            },
+            ConverseStreamOutput::MessageStop(stop) => {
+                self.stop_reason = Some(stop.stop_reason().clone());
+            }
+            ConverseStreamOutput::Metadata(meta) => {
+                if let Some(usage) = meta.usage() {
+                    self.usage = Some(convert_token_usage(usage));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Builds the response carrying the given `model` and `id`.
+    ///
+    /// A tool call whose input never arrived gets `{}` as its arguments.
+    #[allow(deprecated)]
+    pub fn into_openai_response(self, model: &str, id: &str) -> CreateChatCompletionResponse {
+        let mut content = self.text;
+        if !self.reasoning.is_empty() {
+            content = format!(
+                "{THINKING_OPEN_TAG}{}{THINKING_CLOSE_TAG}{content}",
+                self.reasoning
+            );
+        }
+        let tool_calls: Vec<_> = self
+            .tool_calls
+            .into_values()
+            .map(|(id, name, input)| {
+                ChatCompletionMessageToolCalls::Function(ChatCompletionMessageToolCall {
+                    id,
+                    function: FunctionCall {
+                        name,
+                        arguments: if input.is_empty() {
+                            "{}".to_string()
+                        } else {
+                            input
+                        },
+                    },
+                })
             })
+            .collect();
+
+        CreateChatCompletionResponse {
+            id: id.to_string(),
+            object: CHAT_COMPLETION_OBJECT.to_string(),
+            created: unix_timestamp(),
+            model: model.to_string(),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatCompletionResponseMessage {
+                    content: if content.is_empty() {
+                        None
+                    } else {
+                        Some(content)
+                    },
+                    tool_calls: if tool_calls.is_empty() {
+                        None
+                    } else {
+                        Some(tool_calls)
+                    },
+                    role: Role::Assistant,
+                    function_call: None,
+                    refusal: None,
+                    audio: None,
+                    annotations: None,
+                },
+                finish_reason: self.stop_reason.as_ref().map(convert_stop_reason),
+                logprobs: None,
+            }],
+            usage: self.usage,
+            system_fingerprint: None,
+            service_tier: None,
         }
-        _ => None,
     }
 }
 
@@ -461,6 +1409,23 @@ mod tests {
         ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
     };
 
+    #[test]
+    fn test_json_document_round_trip_integers() {
+        let value = serde_json::json!({
+            "negative": -5,
+            "min": i64::MIN,
+            "max": u64::MAX,
+            "float": -1.5,
+            "nested": [0, -1, 1],
+        });
+        let doc = json_to_document(value.clone());
+        assert_eq!(
+            doc.as_object().unwrap()["negative"],
+            aws_smithy_types::Document::Number(aws_smithy_types::Number::NegInt(-5))
+        );
+        assert_eq!(document_to_json(&doc), value);
+    }
+
     #[test]
     fn test_extract_system_and_messages() {
         let messages = vec![
@@ -478,11 +1443,780 @@ mod tests {
             ),
         ];
 
-        let (system, msgs) = extract_system_and_messages(messages).unwrap();
+        let (system, msgs) = extract_system_and_messages(messages, &[]).unwrap();
         assert_eq!(system.len(), 1);
         assert_eq!(msgs.len(), 1);
     }
 
+    #[test]
+    fn test_extract_with_cache_points() {
+        let messages = vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content("You are helpful.")
+                    .build()
+                    .unwrap(),
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Long context")
+                    .build()
+                    .unwrap(),
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Question")
+                    .build()
+                    .unwrap(),
+            ),
+        ];
+
+        let (system, msgs) = extract_system_and_messages(
+            messages,
+            &[
+                BedrockCachePoint::AfterSystem,
+                BedrockCachePoint::AfterMessage(1),
+            ],
+        )
+        .unwrap();
+        assert_eq!(system.len(), 2);
+        assert!(system[1].is_cache_point());
+        assert_eq!(msgs.len(), 1);
+        let content = msgs[0].content();
+        assert_eq!(content.len(), 3);
+        assert!(content[1].is_cache_point());
+        assert_eq!(content[2], ContentBlock::Text("Question".to_string()));
+    }
+
+    #[test]
+    fn test_extract_user_document() {
+        use async_openai::types::chat::ChatCompletionRequestMessageContentPartText;
+
+        let file = |file: serde_json::Value| {
+            ChatCompletionRequestUserMessageContentPart::File(
+                ChatCompletionRequestMessageContentPartFile {
+                    file: serde_json::from_value(file).unwrap(),
+                },
+            )
+        };
+        let messages = vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(vec![
+                    file(serde_json::json!({
+                        "filename": "Q3 report.v2.pdf",
+                        "file_data": "data:application/pdf;base64,JVBERi0=",
+                    })),
+                    ChatCompletionRequestUserMessageContentPart::Text(
+                        ChatCompletionRequestMessageContentPartText {
+                            text: "Summarize this.".to_string(),
+                        },
+                    ),
+                ])
+                .build()
+                .unwrap(),
+        )];
+
+        let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+        let content = msgs[0].content();
+        assert_eq!(content.len(), 2);
+        let doc = content[0].as_document().unwrap();
+        assert_eq!(*doc.format(), DocumentFormat::Pdf);
+        assert_eq!(doc.name(), "Q3 report-v2");
+        assert_eq!(doc.source().unwrap().as_bytes().unwrap().as_ref(), b"%PDF-");
+        assert_eq!(
+            content[1],
+            ContentBlock::Text("Summarize this.".to_string())
+        );
+
+        let messages = vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(vec![file(serde_json::json!({
+                    "filename": "archive.zip",
+                    "file_data": "data:application/zip;base64,UEsDBA==",
+                }))])
+                .build()
+                .unwrap(),
+        )];
+        assert!(matches!(
+            extract_system_and_messages(messages, &[]),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+
+        let messages = vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(vec![file(serde_json::json!({
+                    "filename": "report.pdf",
+                    "file_data": "not base64!",
+                }))])
+                .build()
+                .unwrap(),
+        )];
+        let err = extract_system_and_messages(messages, &[]).unwrap_err();
+        assert!(matches!(err, CompositeLlmError::Bedrock { .. }));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_extract_user_image() {
+        use async_openai::types::chat::{ChatCompletionRequestMessageContentPartImage, ImageUrl};
+
+        let messages = |url: String| {
+            vec![ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(vec![ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                        ChatCompletionRequestMessageContentPartImage {
+                            image_url: ImageUrl { url, detail: None },
+                        },
+                    )])
+                    .build()
+                    .unwrap(),
+            )]
+        };
+        let data_uri = |bytes: &[u8]| {
+            format!(
+                "data:image/png;base64,{}",
+                aws_smithy_types::base64::encode(bytes)
+            )
+        };
+
+        let (_, msgs) = extract_system_and_messages(messages(data_uri(b"\x89PNG")), &[]).unwrap();
+        let image = msgs[0].content()[0].as_image().unwrap();
+        assert_eq!(*image.format(), ImageFormat::Png);
+        assert_eq!(
+            image.source().unwrap().as_bytes().unwrap().as_ref(),
+            b"\x89PNG"
+        );
+
+        let oversized = vec![0; MAX_IMAGE_BYTES + 1];
+        match extract_system_and_messages(messages(data_uri(&oversized)), &[]) {
+            Err(CompositeLlmError::Unsupported(message)) => {
+                assert_eq!(message, "image exceeds Bedrock 5MB limit")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        assert!(matches!(
+            extract_system_and_messages(messages("https://example.com/a.png".to_string()), &[]),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_user_part_is_rejected() {
+        let messages: Vec<ChatCompletionRequestMessage> =
+            serde_json::from_value(serde_json::json!([{
+                "role": "user",
+                "content": [{"type": "input_audio", "input_audio": {"data": "UklGRg==", "format": "wav"}}]
+            }]))
+            .unwrap();
+        match extract_system_and_messages(messages, &[]) {
+            Err(CompositeLlmError::Unsupported(message)) => {
+                assert_eq!(message, "input_audio content is not supported by Bedrock")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_consecutive_user_messages_are_merged() {
+        let messages = vec![
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Hello")
+                    .build()
+                    .unwrap(),
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Are you there?")
+                    .build()
+                    .unwrap(),
+            ),
+        ];
+
+        let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(*msgs[0].role(), ConversationRole::User);
+        assert_eq!(
+            msgs[0].content(),
+            &[
+                ContentBlock::Text("Hello".to_string()),
+                ContentBlock::Text("Are you there?".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing_assistant_message_is_prefill() {
+        use async_openai::types::chat::ChatCompletionRequestAssistantMessageArgs;
+
+        let messages = vec![
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("List three colors as JSON.")
+                    .build()
+                    .unwrap(),
+            ),
+            ChatCompletionRequestMessage::Assistant(
+                ChatCompletionRequestAssistantMessageArgs::default()
+                    .content("{\"colors\": [ \n")
+                    .build()
+                    .unwrap(),
+            ),
+        ];
+
+        let (_, msgs) = extract_system_and_messages(messages.clone(), &[]).unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(*msgs[1].role(), ConversationRole::Assistant);
+        assert_eq!(
+            msgs[1].content(),
+            &[ContentBlock::Text("{\"colors\": [".to_string())]
+        );
+
+        // A whitespace-only prefill is dropped rather than sent empty.
+        let mut blank = messages;
+        blank[1] = ChatCompletionRequestMessage::Assistant(
+            ChatCompletionRequestAssistantMessageArgs::default()
+                .content("   ")
+                .build()
+                .unwrap(),
+        );
+        let (_, msgs) = extract_system_and_messages(blank, &[]).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(*msgs[0].role(), ConversationRole::User);
+    }
+
+    #[test]
+    fn test_tool_calls_only_assistant_turn() {
+        let messages: Vec<ChatCompletionRequestMessage> =
+            serde_json::from_value(serde_json::json!([
+                {"role": "user", "content": "Weather?"},
+                {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{}"}
+                    }]
+                },
+                {"role": "tool", "tool_call_id": "call_1", "content": "sunny"}
+            ]))
+            .unwrap();
+
+        let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(*msgs[1].role(), ConversationRole::Assistant);
+        assert_eq!(msgs[1].content().len(), 1);
+        assert!(msgs[1].content()[0].is_tool_use());
+    }
+
+    #[test]
+    fn test_empty_assistant_turn_keeps_alternation() {
+        let messages: Vec<ChatCompletionRequestMessage> =
+            serde_json::from_value(serde_json::json!([
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": ""},
+                {"role": "user", "content": "Anyone there?"},
+                {"role": "assistant", "content": "Yes."},
+                {"role": "user", "content": "Good."}
+            ]))
+            .unwrap();
+
+        let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+        let roles: Vec<_> = msgs.iter().map(|m| m.role().clone()).collect();
+        assert_eq!(
+            roles,
+            vec![
+                ConversationRole::User,
+                ConversationRole::Assistant,
+                ConversationRole::User,
+            ]
+        );
+        assert_eq!(
+            msgs[0].content(),
+            &[
+                ContentBlock::Text("Hello".to_string()),
+                ContentBlock::Text("Anyone there?".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_tool_call_ids_across_turns() {
+        let mut messages = Vec::new();
+        for (name, result) in [("get_weather", "sunny"), ("get_time", "noon")] {
+            messages.extend([
+                serde_json::json!({"role": "user", "content": "next"}),
+                serde_json::json!({
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": name, "arguments": "{}"}
+                    }]
+                }),
+                serde_json::json!({"role": "tool", "tool_call_id": "call_1", "content": result}),
+            ]);
+        }
+        let messages: Vec<ChatCompletionRequestMessage> =
+            serde_json::from_value(serde_json::Value::Array(messages)).unwrap();
+
+        let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+        // user, assistant(toolUse), user(toolResult + next), assistant(toolUse), user(toolResult)
+        assert_eq!(msgs.len(), 5);
+        let pairs: Vec<_> = msgs
+            .windows(2)
+            .filter_map(|w| match (&w[0].content()[0], &w[1].content()[0]) {
+                (ContentBlock::ToolUse(tu), ContentBlock::ToolResult(tr)) => Some((
+                    tu.name().to_string(),
+                    tr.tool_use_id().to_string(),
+                    tr.content()[0].clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (
+                    "get_weather".to_string(),
+                    "call_1".to_string(),
+                    ToolResultContentBlock::Text("sunny".to_string()),
+                ),
+                (
+                    "get_time".to_string(),
+                    "call_1".to_string(),
+                    ToolResultContentBlock::Text("noon".to_string()),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tool_result_content() {
+        let result = |content: &str| {
+            let messages: Vec<ChatCompletionRequestMessage> =
+                serde_json::from_value(serde_json::json!([
+                    {"role": "tool", "tool_call_id": "call_1", "content": content},
+                ]))
+                .unwrap();
+            let (_, msgs) = extract_system_and_messages(messages, &[])?;
+            let tr = msgs[0].content()[0].as_tool_result().unwrap();
+            Ok::<_, CompositeLlmError>(tr.content()[0].clone())
+        };
+
+        assert_eq!(
+            result("sunny").unwrap(),
+            ToolResultContentBlock::Text("sunny".to_string())
+        );
+        assert_eq!(
+            result("[1, 2]").unwrap(),
+            ToolResultContentBlock::Text("[1, 2]".to_string())
+        );
+        assert_eq!(
+            result(r#"{"temperature": 21}"#).unwrap(),
+            ToolResultContentBlock::Json(json_to_document(serde_json::json!({"temperature": 21})))
+        );
+
+        let data_uri = format!(
+            "data:image/png;base64,{}",
+            aws_smithy_types::base64::encode(b"\x89PNG")
+        );
+        let block = result(&data_uri).unwrap();
+        let image = block.as_image().unwrap();
+        assert_eq!(*image.format(), ImageFormat::Png);
+        assert_eq!(
+            image.source().unwrap().as_bytes().unwrap().as_ref(),
+            b"\x89PNG"
+        );
+    }
+
+    #[test]
+    fn test_tool_result_unsupported_image_falls_back_to_text() {
+        let result = |content: &str| {
+            let messages: Vec<ChatCompletionRequestMessage> =
+                serde_json::from_value(serde_json::json!([
+                    {"role": "tool", "tool_call_id": "call_1", "content": content},
+                ]))
+                .unwrap();
+            let (_, msgs) = extract_system_and_messages(messages, &[]).unwrap();
+            msgs[0].content()[0].as_tool_result().unwrap().content()[0].clone()
+        };
+
+        for content in [
+            "data:image/bmp;base64,Qk0=",
+            "data:image/svg+xml,<svg xmlns='http://www.w3.org/2000/svg'/>",
+            "data:image/png;base64,not base64!",
+        ] {
+            assert_eq!(
+                result(content),
+                ToolResultContentBlock::Text(content.to_string()),
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_additional_model_response_fields() {
+        use aws_sdk_bedrockruntime::operation::converse::ConverseOutput;
+
+        let output = ConverseOutput::builder()
+            .stop_reason(StopReason::StopSequence)
+            .additional_model_response_fields(json_to_document(
+                serde_json::json!({"stop_sequence": "END"}),
+            ))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            additional_model_response_fields(&output),
+            Some(serde_json::json!({"stop_sequence": "END"}))
+        );
+        assert_eq!(raw_stop_reason(&output), "stop_sequence");
+        let response = convert_converse_response(&output, "m", "id").unwrap();
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+
+        let response =
+            CreateChatCompletionResponse::try_from(NativeResponse::new(output, "m")).unwrap();
+        assert_eq!(response.model, "m");
+        assert!(response.id.starts_with("chatcmpl-"));
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[test]
+    fn test_convert_converse_response_with_reasoning() {
+        use aws_sdk_bedrockruntime::operation::converse::ConverseOutput;
+        use aws_sdk_bedrockruntime::types::ReasoningTextBlock;
+
+        let message = Message::builder()
+            .role(ConversationRole::Assistant)
+            .content(ContentBlock::ReasoningContent(
+                ReasoningContentBlock::ReasoningText(
+                    ReasoningTextBlock::builder()
+                        .text("Let me think.")
+                        .build()
+                        .unwrap(),
+                ),
+            ))
+            .content(ContentBlock::Text("Answer".to_string()))
+            .build()
+            .unwrap();
+        let output = ConverseOutput::builder()
+            .output(aws_sdk_bedrockruntime::types::ConverseOutput::Message(
+                message,
+            ))
+            .stop_reason(StopReason::EndTurn)
+            .build()
+            .unwrap();
+
+        let resp = convert_converse_response(&output, "claude", "chatcmpl-test").unwrap();
+        assert_eq!(resp.id, "chatcmpl-test");
+        assert_eq!(resp.object, CHAT_COMPLETION_OBJECT);
+        assert_eq!(
+            resp.choices[0].message.content.as_deref(),
+            Some("<thinking>Let me think.</thinking>Answer")
+        );
+    }
+
+    #[test]
+    fn test_stream_reasoning_is_wrapped() {
+        use aws_sdk_bedrockruntime::types::{ContentBlockDeltaEvent, ContentBlockStopEvent};
+
+        let delta = |index, d| {
+            ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(index)
+                    .delta(d)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let events = [
+            delta(
+                0,
+                ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Text(
+                    "Hmm".to_string(),
+                )),
+            ),
+            delta(
+                0,
+                ContentBlockDelta::ReasoningContent(ReasoningContentBlockDelta::Text(
+                    "...".to_string(),
+                )),
+            ),
+            ConverseStreamOutput::ContentBlockStop(
+                ContentBlockStopEvent::builder()
+                    .content_block_index(0)
+                    .build()
+                    .unwrap(),
+            ),
+            delta(1, ContentBlockDelta::Text("Hi".to_string())),
+        ];
+
+        let mut state = StreamState::default();
+        let text: String = events
+            .iter()
+            .filter_map(|e| stream_event_to_response(e, "claude", "id", &mut state))
+            .filter_map(|c| c.choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "<thinking>Hmm...</thinking>Hi");
+    }
+
+    fn tool_use_events() -> Vec<ConverseStreamOutput> {
+        use aws_sdk_bedrockruntime::types::{
+            ContentBlockDeltaEvent, ContentBlockStartEvent, MessageStopEvent, ToolUseBlockDelta,
+            ToolUseBlockStart,
+        };
+
+        let start = |index, id: &str, name: &str| {
+            ConverseStreamOutput::ContentBlockStart(
+                ContentBlockStartEvent::builder()
+                    .content_block_index(index)
+                    .start(ContentBlockStart::ToolUse(
+                        ToolUseBlockStart::builder()
+                            .tool_use_id(id)
+                            .name(name)
+                            .build()
+                            .unwrap(),
+                    ))
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let delta = |index, d| {
+            ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(index)
+                    .delta(d)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let input = |s: &str| {
+            ContentBlockDelta::ToolUse(ToolUseBlockDelta::builder().input(s).build().unwrap())
+        };
+        vec![
+            delta(0, ContentBlockDelta::Text("Checking.".to_string())),
+            start(1, "tooluse_a", "get_weather"),
+            delta(1, input("{\"city\": ")),
+            delta(1, input("\"Paris\"}")),
+            start(2, "tooluse_b", "get_time"),
+            ConverseStreamOutput::MessageStop(
+                MessageStopEvent::builder()
+                    .stop_reason(StopReason::ToolUse)
+                    .build()
+                    .unwrap(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_stream_tool_use_deltas() {
+        let mut state = StreamState::default();
+        let calls: Vec<_> = tool_use_events()
+            .iter()
+            .filter_map(|e| stream_event_to_response(e, "claude", "id", &mut state))
+            .filter_map(|c| c.choices[0].delta.tool_calls.clone())
+            .flatten()
+            .map(|c| {
+                let function = c.function.unwrap();
+                (c.index, c.id, function.name, function.arguments.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    0,
+                    Some("tooluse_a".to_string()),
+                    Some("get_weather".to_string()),
+                    String::new()
+                ),
+                (0, None, None, "{\"city\": ".to_string()),
+                (0, None, None, "\"Paris\"}".to_string()),
+                (
+                    1,
+                    Some("tooluse_b".to_string()),
+                    Some("get_time".to_string()),
+                    String::new()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_accumulator_joins_tool_input() {
+        let mut acc = BedrockStreamAccumulator::new();
+        for event in &tool_use_events() {
+            acc.push(event);
+        }
+        let resp = acc.into_openai_response("claude", "chatcmpl-1");
+
+        assert_eq!(resp.id, "chatcmpl-1");
+        let choice = &resp.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(choice.message.content.as_deref(), Some("Checking."));
+        let calls: Vec<_> = choice
+            .message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|c| match c {
+                ChatCompletionMessageToolCalls::Function(c) => (
+                    c.id.as_str(),
+                    c.function.name.as_str(),
+                    c.function.arguments.as_str(),
+                ),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                ("tooluse_a", "get_weather", "{\"city\": \"Paris\"}"),
+                ("tooluse_b", "get_time", "{}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_token_usage_with_cache() {
+        let usage = TokenUsage::builder()
+            .input_tokens(10)
+            .output_tokens(5)
+            .total_tokens(115)
+            .cache_read_input_tokens(80)
+            .cache_write_input_tokens(20)
+            .build()
+            .unwrap();
+
+        let converted = convert_token_usage(&usage);
+        assert_eq!(converted.prompt_tokens, 110);
+        assert_eq!(converted.completion_tokens, 5);
+        assert_eq!(converted.total_tokens, 115);
+        assert_eq!(
+            converted.prompt_tokens_details.unwrap().cached_tokens,
+            Some(80)
+        );
+
+        let usage = TokenUsage::builder()
+            .input_tokens(10)
+            .output_tokens(5)
+            .total_tokens(15)
+            .build()
+            .unwrap();
+        let converted = convert_token_usage(&usage);
+        assert_eq!(converted.prompt_tokens, 10);
+        assert!(converted.prompt_tokens_details.is_none());
+    }
+
+    #[test]
+    fn test_stream_usage_chunk_follows_include_usage() {
+        use aws_sdk_bedrockruntime::types::ConverseStreamMetadataEvent;
+
+        let event = ConverseStreamOutput::Metadata(
+            ConverseStreamMetadataEvent::builder()
+                .usage(
+                    TokenUsage::builder()
+                        .input_tokens(3)
+                        .output_tokens(4)
+                        .total_tokens(7)
+                        .build()
+                        .unwrap(),
+                )
+                .build(),
+        );
+
+        let chunk =
+            stream_event_to_response(&event, "claude", "id", &mut StreamState::new(true)).unwrap();
+        assert!(chunk.choices.is_empty());
+        assert_eq!(chunk.usage.unwrap().total_tokens, 7);
+
+        assert!(
+            stream_event_to_response(&event, "claude", "id", &mut StreamState::new(false))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_stream_message_start_carries_id() {
+        use aws_sdk_bedrockruntime::types::{ContentBlockDeltaEvent, MessageStartEvent};
+
+        let events = [
+            ConverseStreamOutput::MessageStart(
+                MessageStartEvent::builder()
+                    .role(ConversationRole::Assistant)
+                    .build()
+                    .unwrap(),
+            ),
+            ConverseStreamOutput::ContentBlockDelta(
+                ContentBlockDeltaEvent::builder()
+                    .content_block_index(0)
+                    .delta(ContentBlockDelta::Text("Hi".to_string()))
+                    .build()
+                    .unwrap(),
+            ),
+        ];
+
+        let mut state = StreamState::default();
+        let chunks: Vec<_> = events
+            .iter()
+            .filter_map(|e| stream_event_to_response(e, "claude", "chatcmpl-1", &mut state))
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].id, "chatcmpl-1");
+        assert_eq!(chunks[0].choices[0].delta.role, Some(Role::Assistant));
+        assert_eq!(chunks[0].choices[0].delta.content.as_deref(), Some(""));
+        assert!(chunks.iter().all(|c| c.id == "chatcmpl-1"));
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.object == CHAT_COMPLETION_CHUNK_OBJECT)
+        );
+    }
+
+    #[test]
+    fn test_build_json_mode_system_block() {
+        use async_openai::types::chat::ResponseFormatJsonSchema;
+
+        let mut req = CreateChatCompletionRequest::default();
+        assert!(build_json_mode_system_block(&req).unwrap().is_none());
+
+        req.response_format = Some(ResponseFormat::JsonObject);
+        let block = build_json_mode_system_block(&req).unwrap().unwrap();
+        assert!(block.as_text().unwrap().contains("valid JSON object"));
+
+        req.response_format = Some(ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description: None,
+                name: "answer".to_string(),
+                schema: Some(serde_json::json!({"type": "object"})),
+                strict: None,
+            },
+        });
+        let block = build_json_mode_system_block(&req).unwrap().unwrap();
+        assert!(block.as_text().unwrap().ends_with(r#"{"type":"object"}"#));
+    }
+
+    #[test]
+    fn test_build_parallel_tool_calls_system_block() {
+        let mut req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude",
+            "messages": [],
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}]
+        }))
+        .unwrap();
+        assert!(build_parallel_tool_calls_system_block(&req).is_none());
+
+        req.parallel_tool_calls = Some(false);
+        let block = build_parallel_tool_calls_system_block(&req).unwrap();
+        assert!(block.as_text().unwrap().contains("at most one tool"));
+
+        req.tools = None;
+        assert!(build_parallel_tool_calls_system_block(&req).is_none());
+    }
+
     #[test]
     fn test_build_inference_config_none() {
         let req = CreateChatCompletionRequest {
@@ -490,7 +2224,7 @@ mod tests {
             messages: vec![],
             ..Default::default()
         };
-        assert!(build_inference_config(&req).is_none());
+        assert!(build_inference_config(&req).unwrap().is_none());
     }
 
     #[test]
@@ -502,7 +2236,157 @@ mod tests {
             top_p: Some(0.9),
             ..Default::default()
         };
-        let config = build_inference_config(&req);
+        let config = build_inference_config(&req).unwrap();
         assert!(config.is_some());
     }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_build_inference_config_max_tokens_fallback() {
+        let mut req = CreateChatCompletionRequest {
+            max_tokens: Some(50),
+            ..Default::default()
+        };
+        let config = build_inference_config(&req).unwrap().unwrap();
+        assert_eq!(config.max_tokens(), Some(50));
+
+        req.max_completion_tokens = Some(100);
+        let config = build_inference_config(&req).unwrap().unwrap();
+        assert_eq!(config.max_tokens(), Some(100));
+    }
+
+    #[test]
+    fn test_build_inference_config_stop_sequence_limit() {
+        let req = |count: usize| CreateChatCompletionRequest {
+            model: "test".to_string(),
+            messages: vec![],
+            stop: Some(StopConfiguration::StringArray(
+                (0..count).map(|i| format!("STOP{i}")).collect(),
+            )),
+            ..Default::default()
+        };
+
+        let config = build_inference_config(&req(MAX_STOP_SEQUENCES))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.stop_sequences().len(), MAX_STOP_SEQUENCES);
+        assert!(matches!(
+            build_inference_config(&req(MAX_STOP_SEQUENCES + 1)),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_additional_model_request_fields() {
+        assert!(
+            build_additional_model_request_fields(&BedrockOptions::default())
+                .unwrap()
+                .is_none()
+        );
+
+        let opts = BedrockOptions {
+            additional_model_request_fields: Some(serde_json::json!({"foo": "bar"})),
+            top_k: Some(50),
+            thinking_budget: Some(2048),
+            ..Default::default()
+        };
+        let doc = build_additional_model_request_fields(&opts)
+            .unwrap()
+            .unwrap();
+        let json = document_to_json(&doc);
+        assert_eq!(json["foo"], "bar");
+        assert_eq!(json["top_k"], 50);
+        assert_eq!(json["thinking"]["type"], "enabled");
+        assert_eq!(json["thinking"]["budget_tokens"], 2048);
+
+        let invalid = BedrockOptions {
+            additional_model_request_fields: Some(serde_json::json!([1, 2])),
+            ..Default::default()
+        };
+        assert!(build_additional_model_request_fields(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_converse_input_to_json() {
+        let messages = vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content("You are helpful.")
+                    .build()
+                    .unwrap(),
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Hello")
+                    .build()
+                    .unwrap(),
+            ),
+        ];
+        let (system, messages) = extract_system_and_messages(messages, &[]).unwrap();
+        let req = CreateChatCompletionRequest {
+            max_completion_tokens: Some(100),
+            stop: Some(StopConfiguration::String("END".to_string())),
+            ..Default::default()
+        };
+        let input = ConverseInput {
+            model_id: "anthropic.claude-3".to_string(),
+            system,
+            messages,
+            inference_config: build_inference_config(&req).unwrap(),
+            tool_config: None,
+            additional_model_request_fields: None,
+            prompt_variables: None,
+            guardrail: None,
+            performance_config: None,
+        };
+
+        assert_eq!(
+            input.to_json(),
+            serde_json::json!({
+                "modelId": "anthropic.claude-3",
+                "messages": [{"role": "user", "content": [{"text": "Hello"}]}],
+                "system": [{"text": "You are helpful."}],
+                "inferenceConfig": {"maxTokens": 100, "stopSequences": ["END"]},
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_unsupported_params() {
+        let mut req = CreateChatCompletionRequest {
+            model: "test".to_string(),
+            messages: vec![],
+            ..Default::default()
+        };
+        assert!(check_unsupported_params(&req).is_ok());
+
+        req.reasoning_effort = Some(ReasoningEffort::None);
+        assert!(check_unsupported_params(&req).is_ok());
+
+        req.reasoning_effort = Some(ReasoningEffort::High);
+        assert!(matches!(
+            check_unsupported_params(&req),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+
+        req.reasoning_effort = None;
+        req.logprobs = Some(false);
+        assert!(check_unsupported_params(&req).is_ok());
+
+        req.logprobs = Some(true);
+        assert!(matches!(
+            check_unsupported_params(&req),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+
+        req.logprobs = None;
+        req.modalities = Some(vec![ResponseModalities::Text]);
+        assert!(check_unsupported_params(&req).is_ok());
+
+        req.modalities = Some(vec![ResponseModalities::Text, ResponseModalities::Audio]);
+        assert!(matches!(
+            check_unsupported_params(&req),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
 }
@@ -1,7 +1,83 @@
-use uuid::Uuid;
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+use async_openai::types::chat::{
+    ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestAssistantMessageContentPart,
+    ChatCompletionRequestDeveloperMessageContent, ChatCompletionRequestDeveloperMessageContentPart,
+    ChatCompletionRequestSystemMessageContent, ChatCompletionRequestSystemMessageContentPart,
+    ChatCompletionRequestToolMessageContent, ChatCompletionRequestToolMessageContentPart,
+    ChatCompletionRequestUserMessageContent, ChatCompletionRequestUserMessageContentPart,
+};
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+use std::borrow::Cow;
+
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+use async_openai::types::chat::StopConfiguration;
+use async_openai::types::chat::{
+    CompletionUsage, CreateChatCompletionRequest, CreateChatCompletionStreamResponse,
+};
+use std::sync::OnceLock;
+#[cfg(not(feature = "uuid"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+use crate::error::CompositeLlmError;
+
+/// The `object` of a synthesized non-streaming response.
+pub const CHAT_COMPLETION_OBJECT: &str = "chat.completion";
+/// The `object` of a synthesized stream chunk.
+pub const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+
+/// A function returning a new unique id; see [`set_id_generator`].
+pub type IdGenerator = Box<dyn Fn() -> String + Send + Sync>;
+
+static ID_GENERATOR: OnceLock<IdGenerator> = OnceLock::new();
+
+/// Sets the process-wide generator for the unique part of synthesized ids
+/// (`chatcmpl-...` response ids and `call_...` tool call ids).
+///
+/// Without one, ids use a random UUID with the `uuid` feature (default), or
+/// a timestamp and counter without it. The generator can only be set once;
+/// returns `false` if one was already set.
+pub fn set_id_generator(generator: impl Fn() -> String + Send + Sync + 'static) -> bool {
+    ID_GENERATOR.set(Box::new(generator)).is_ok()
+}
+
+#[cfg(feature = "uuid")]
+fn default_id() -> String {
+    uuid::Uuid::new_v4().as_simple().to_string()
+}
+
+/// A 32 hex digit id: the time of the first call in nanoseconds, then a
+/// counter, so ids are unique within the process and unlikely to repeat
+/// across restarts.
+#[cfg(not(feature = "uuid"))]
+fn default_id() -> String {
+    static START: OnceLock<u64> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let start = *START.get_or_init(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+    });
+    format!(
+        "{start:016x}{:016x}",
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn generate_id() -> String {
+    ID_GENERATOR
+        .get()
+        .map_or_else(default_id, |generator| generator())
+}
 
 pub fn generate_chat_cmpl_id() -> String {
-    format!("chatcmpl-{}", Uuid::new_v4().as_simple())
+    format!("chatcmpl-{}", generate_id())
+}
+
+/// A new id for a tool call the provider did not assign one to.
+pub fn generate_tool_call_id() -> String {
+    format!("call_{}", generate_id())
 }
 
 pub fn unix_timestamp() -> u32 {
@@ -11,8 +87,332 @@ pub fn unix_timestamp() -> u32 {
         .as_secs() as u32
 }
 
+/// Whether the caller asked for a final usage chunk via
+/// `stream_options.include_usage`.
+pub fn include_usage(req: &CreateChatCompletionRequest) -> bool {
+    req.stream_options
+        .as_ref()
+        .and_then(|o| o.include_usage)
+        .unwrap_or(false)
+}
+
+/// The output token limit: `max_completion_tokens`, or the deprecated
+/// `max_tokens` still sent by older clients.
+#[allow(deprecated)]
+pub fn max_tokens(req: &CreateChatCompletionRequest) -> Option<u32> {
+    req.max_completion_tokens.or(req.max_tokens)
+}
+
+/// A provider-native response paired with the model name to report, for
+/// converting it with `TryFrom`:
+///
+/// ```ignore
+/// let native = NativeResponse::new(vertex_resp, "gemini-2.0-flash");
+/// let response = CreateChatCompletionResponse::try_from(native)?;
+/// ```
+///
+/// The converted response gets a new id from [`generate_chat_cmpl_id`]. A
+/// plain `(response, model)` tuple cannot be used, as Rust only allows
+/// implementing `TryFrom` for `CreateChatCompletionResponse` with a type of
+/// this crate.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+#[derive(Debug, Clone)]
+pub struct NativeResponse<'a, T> {
+    /// The provider's response, e.g. a
+    /// [`VertexResponse`](vertex::VertexResponse) or a Bedrock `ConverseOutput`.
+    pub response: T,
+    /// The model reported in the converted response.
+    pub model: &'a str,
+}
+
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+impl<'a, T> NativeResponse<'a, T> {
+    /// Pairs `response` with `model`.
+    pub fn new(response: T, model: &'a str) -> Self {
+        Self { response, model }
+    }
+}
+
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+impl<'a, T> From<(T, &'a str)> for NativeResponse<'a, T> {
+    fn from((response, model): (T, &'a str)) -> Self {
+        Self::new(response, model)
+    }
+}
+
+/// Returns `req` with a backend's default stop sequences appended to its own.
+///
+/// Defaults already in the request are skipped, and defaults that would take
+/// the list past `limit` are dropped: the request's own sequences always win.
+/// A request that is over `limit` by itself is returned unchanged, so it is
+/// still rejected when converted.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn with_default_stop_sequences<'a>(
+    req: &'a CreateChatCompletionRequest,
+    defaults: &[String],
+    limit: usize,
+) -> Cow<'a, CreateChatCompletionRequest> {
+    if defaults.is_empty() {
+        return Cow::Borrowed(req);
+    }
+    let mut stop = match req.stop {
+        Some(StopConfiguration::String(ref s)) => vec![s.clone()],
+        Some(StopConfiguration::StringArray(ref arr)) => arr.clone(),
+        None => Vec::new(),
+    };
+    let own = stop.len();
+    for default in defaults {
+        if stop.contains(default) {
+            continue;
+        }
+        if stop.len() >= limit {
+            tracing::debug!(
+                stop_sequence = %default,
+                limit,
+                "dropping default stop sequence over the provider limit"
+            );
+            continue;
+        }
+        stop.push(default.clone());
+    }
+    if stop.len() == own {
+        return Cow::Borrowed(req);
+    }
+    let mut req = req.clone();
+    req.stop = Some(StopConfiguration::StringArray(stop));
+    Cow::Owned(req)
+}
+
+/// Builds the final usage-only stream chunk (empty `choices`), matching the
+/// chunk OpenAI sends when `stream_options.include_usage` is set.
+#[allow(deprecated)]
+pub fn usage_chunk(
+    usage: CompletionUsage,
+    model: &str,
+    id: &str,
+) -> CreateChatCompletionStreamResponse {
+    CreateChatCompletionStreamResponse {
+        id: id.to_string(),
+        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+        created: unix_timestamp(),
+        model: model.to_string(),
+        choices: vec![],
+        usage: Some(usage),
+        system_fingerprint: None,
+        service_tier: None,
+    }
+}
+
+// ── Message content flattening shared by Bedrock and Vertex AI ──
+
+/// Separator placed between the text parts of one message when they are
+/// joined into a single text.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub const TEXT_PART_SEPARATOR: &str = "\n";
+
+/// Joins the text of a system message.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn system_text(content: &ChatCompletionRequestSystemMessageContent) -> String {
+    match content {
+        ChatCompletionRequestSystemMessageContent::Text(t) => t.clone(),
+        ChatCompletionRequestSystemMessageContent::Array(parts) => parts
+            .iter()
+            .map(|ChatCompletionRequestSystemMessageContentPart::Text(t)| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(TEXT_PART_SEPARATOR),
+    }
+}
+
+/// Joins the text of a developer message.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn developer_text(content: &ChatCompletionRequestDeveloperMessageContent) -> String {
+    match content {
+        ChatCompletionRequestDeveloperMessageContent::Text(t) => t.clone(),
+        ChatCompletionRequestDeveloperMessageContent::Array(parts) => parts
+            .iter()
+            .map(|ChatCompletionRequestDeveloperMessageContentPart::Text(t)| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(TEXT_PART_SEPARATOR),
+    }
+}
+
+/// Joins the text of an assistant message; refusal parts are dropped.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn assistant_text(content: &ChatCompletionRequestAssistantMessageContent) -> String {
+    match content {
+        ChatCompletionRequestAssistantMessageContent::Text(t) => t.clone(),
+        ChatCompletionRequestAssistantMessageContent::Array(parts) => parts
+            .iter()
+            .filter_map(|p| match p {
+                ChatCompletionRequestAssistantMessageContentPart::Text(t) => Some(t.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(TEXT_PART_SEPARATOR),
+    }
+}
+
+/// Joins the text of a tool result message.
+///
+/// Tool results can only hold text parts today. Should `async-openai` add
+/// other part types, they are rejected as unsupported instead of being
+/// dropped; until then the catch-all arm is unreachable, hence its `allow`.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn tool_text(
+    content: &ChatCompletionRequestToolMessageContent,
+) -> Result<String, CompositeLlmError> {
+    match content {
+        ChatCompletionRequestToolMessageContent::Text(t) => Ok(t.clone()),
+        ChatCompletionRequestToolMessageContent::Array(parts) => parts
+            .iter()
+            .map(|p| match p {
+                ChatCompletionRequestToolMessageContentPart::Text(t) => Ok(t.text.as_str()),
+                #[allow(unreachable_patterns)]
+                _ => Err(CompositeLlmError::Unsupported(
+                    "tool messages only support text content".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|texts| texts.join(TEXT_PART_SEPARATOR)),
+    }
+}
+
+/// A part of a user message.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub enum UserContentPart<'a> {
+    /// Text, from either the plain text form or a text part.
+    Text(&'a str),
+    /// A non-text part (image, audio, file), left to the backend to convert
+    /// or reject.
+    Other(&'a ChatCompletionRequestUserMessageContentPart),
+}
+
+/// Splits a user message into its parts, in order.
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn user_parts(content: &ChatCompletionRequestUserMessageContent) -> Vec<UserContentPart<'_>> {
+    match content {
+        ChatCompletionRequestUserMessageContent::Text(t) => vec![UserContentPart::Text(t)],
+        ChatCompletionRequestUserMessageContent::Array(parts) => parts
+            .iter()
+            .map(|p| match p {
+                ChatCompletionRequestUserMessageContentPart::Text(t) => {
+                    UserContentPart::Text(&t.text)
+                }
+                other => UserContentPart::Other(other),
+            })
+            .collect(),
+    }
+}
+
+/// The error for a user message part `provider` cannot take, e.g.
+/// "input_audio content is not supported by Bedrock".
+#[cfg(any(feature = "backend-bedrock", feature = "backend-vertex"))]
+pub fn unsupported_part(
+    part: &ChatCompletionRequestUserMessageContentPart,
+    provider: &str,
+) -> CompositeLlmError {
+    let kind = match part {
+        ChatCompletionRequestUserMessageContentPart::Text(_) => "text",
+        ChatCompletionRequestUserMessageContentPart::ImageUrl(_) => "image_url",
+        ChatCompletionRequestUserMessageContentPart::InputAudio(_) => "input_audio",
+        ChatCompletionRequestUserMessageContentPart::File(_) => "file",
+    };
+    CompositeLlmError::Unsupported(format!("{kind} content is not supported by {provider}"))
+}
+
+pub mod openai;
+
 #[cfg(feature = "backend-bedrock")]
 pub mod bedrock;
 
 #[cfg(feature = "backend-vertex")]
 pub mod vertex;
+
+#[cfg(all(test, any(feature = "backend-bedrock", feature = "backend-vertex")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_helpers() {
+        let system: ChatCompletionRequestSystemMessageContent =
+            serde_json::from_value(serde_json::json!([
+                {"type": "text", "text": "a"},
+                {"type": "text", "text": "b"}
+            ]))
+            .unwrap();
+        assert_eq!(system_text(&system), "a\nb");
+
+        let tool: ChatCompletionRequestToolMessageContent =
+            serde_json::from_value(serde_json::json!([
+                {"type": "text", "text": "42"},
+                {"type": "text", "text": "degrees"}
+            ]))
+            .unwrap();
+        assert_eq!(tool_text(&tool).unwrap(), "42\ndegrees");
+
+        let user: ChatCompletionRequestUserMessageContent =
+            serde_json::from_value(serde_json::json!([
+                {"type": "text", "text": "look"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}
+            ]))
+            .unwrap();
+        let parts = user_parts(&user);
+        assert!(matches!(parts[0], UserContentPart::Text("look")));
+        assert!(matches!(
+            parts[1],
+            UserContentPart::Other(ChatCompletionRequestUserMessageContentPart::ImageUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_generated_ids_are_unique() {
+        let a = generate_chat_cmpl_id();
+        let b = generate_chat_cmpl_id();
+        assert_ne!(a, b);
+        assert_eq!(a.strip_prefix("chatcmpl-").map(str::len), Some(32));
+        assert!(generate_tool_call_id().starts_with("call_"));
+    }
+
+    #[test]
+    fn test_with_default_stop_sequences() {
+        let defaults = ["END".to_string(), "STOP".to_string(), "DONE".to_string()];
+        let req = |stop: Option<StopConfiguration>| CreateChatCompletionRequest {
+            stop,
+            ..Default::default()
+        };
+        let stop = |req: &CreateChatCompletionRequest| match req.stop {
+            Some(StopConfiguration::StringArray(ref arr)) => arr.clone(),
+            ref other => panic!("unexpected stop: {other:?}"),
+        };
+
+        // Request sequences come first; duplicates are skipped.
+        let base = req(Some(StopConfiguration::String("STOP".to_string())));
+        let merged = with_default_stop_sequences(&base, &defaults, 5);
+        assert_eq!(stop(&merged), ["STOP", "END", "DONE"]);
+
+        // Defaults over the limit are dropped, the request's are kept.
+        let merged = with_default_stop_sequences(&base, &defaults, 2);
+        assert_eq!(stop(&merged), ["STOP", "END"]);
+
+        let base = req(None);
+        assert_eq!(
+            stop(&with_default_stop_sequences(&base, &defaults, 5)),
+            defaults
+        );
+        assert!(matches!(
+            with_default_stop_sequences(&base, &[], 5),
+            Cow::Borrowed(_)
+        ));
+
+        // A request already at or over the limit is left for conversion to reject.
+        let full = req(Some(StopConfiguration::StringArray(vec![
+            "A".to_string(),
+            "B".to_string(),
+            "C".to_string(),
+        ])));
+        assert!(matches!(
+            with_default_stop_sequences(&full, &defaults, 2),
+            Cow::Borrowed(_)
+        ));
+    }
+}
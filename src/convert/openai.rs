@@ -0,0 +1,104 @@
+//! Encoding of unified responses back to the OpenAI wire format.
+//!
+//! These are the inverse of the backend conversions: any backend's output can
+//! be served to an OpenAI-compatible client unchanged.
+
+use async_openai::types::chat::{CreateChatCompletionResponse, CreateChatCompletionStreamResponse};
+
+use crate::error::CompositeLlmError;
+
+/// The frame terminating an OpenAI server-sent event stream.
+pub const SSE_DONE: &[u8] = b"data: [DONE]\n\n";
+
+/// Encodes a stream chunk as a server-sent event frame: `data: {json}\n\n`.
+pub fn to_openai_sse_bytes(
+    chunk: &CreateChatCompletionStreamResponse,
+) -> Result<Vec<u8>, CompositeLlmError> {
+    let mut frame = b"data: ".to_vec();
+    serde_json::to_writer(&mut frame, chunk)?;
+    frame.extend_from_slice(b"\n\n");
+    Ok(frame)
+}
+
+/// Encodes a non-streaming response as an OpenAI JSON response body.
+pub fn to_openai_json(
+    response: &CreateChatCompletionResponse,
+) -> Result<Vec<u8>, CompositeLlmError> {
+    Ok(serde_json::to_vec(response)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::chat::{
+        ChatChoice, ChatChoiceStream, ChatCompletionResponseMessage,
+        ChatCompletionStreamResponseDelta, FinishReason, Role,
+    };
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_sse_frame_round_trip() {
+        let chunk = CreateChatCompletionStreamResponse {
+            id: "chatcmpl-test".to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created: 1,
+            model: "test".to_string(),
+            choices: vec![ChatChoiceStream {
+                index: 0,
+                delta: ChatCompletionStreamResponseDelta {
+                    content: Some("Hi".to_string()),
+                    tool_calls: None,
+                    role: Some(Role::Assistant),
+                    function_call: None,
+                    refusal: None,
+                },
+                finish_reason: Some(FinishReason::Stop),
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        };
+
+        let frame = String::from_utf8(to_openai_sse_bytes(&chunk).unwrap()).unwrap();
+        let json = frame
+            .strip_prefix("data: ")
+            .and_then(|f| f.strip_suffix("\n\n"))
+            .unwrap();
+        assert!(!json.contains('\n'));
+        let parsed: CreateChatCompletionStreamResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed, chunk);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_json_round_trip() {
+        let response = CreateChatCompletionResponse {
+            id: "chatcmpl-test".to_string(),
+            object: "chat.completion".to_string(),
+            created: 1,
+            model: "test".to_string(),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatCompletionResponseMessage {
+                    content: Some("Hello".to_string()),
+                    refusal: None,
+                    tool_calls: None,
+                    annotations: None,
+                    role: Role::Assistant,
+                    function_call: None,
+                    audio: None,
+                },
+                finish_reason: Some(FinishReason::Stop),
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        };
+
+        let parsed: CreateChatCompletionResponse =
+            serde_json::from_slice(&to_openai_json(&response).unwrap()).unwrap();
+        assert_eq!(parsed, response);
+    }
+}
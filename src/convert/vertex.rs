@@ -1,26 +1,31 @@
+use std::collections::{HashMap, HashSet};
+
 use async_openai::types::chat::FunctionCall;
 use async_openai::types::chat::{
-    ChatChoice, ChatChoiceStream, ChatCompletionMessageToolCall, ChatCompletionMessageToolCalls,
-    ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestAssistantMessageContentPart,
-    ChatCompletionRequestDeveloperMessageContent, ChatCompletionRequestDeveloperMessageContentPart,
-    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageContent,
-    ChatCompletionRequestSystemMessageContentPart, ChatCompletionRequestToolMessageContent,
-    ChatCompletionRequestToolMessageContentPart, ChatCompletionRequestUserMessageContent,
-    ChatCompletionRequestUserMessageContentPart, ChatCompletionResponseMessage,
-    ChatCompletionStreamResponseDelta, ChatCompletionToolChoiceOption, ChatCompletionTools,
-    CompletionUsage, CreateChatCompletionRequest, CreateChatCompletionResponse,
-    CreateChatCompletionStreamResponse, FinishReason, ResponseFormat, Role, StopConfiguration,
-    ToolChoiceOptions,
+    ChatChoice, ChatChoiceLogprobs, ChatChoiceStream, ChatCompletionMessageToolCall,
+    ChatCompletionMessageToolCallChunk, ChatCompletionMessageToolCalls,
+    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageContentPart,
+    ChatCompletionResponseMessage, ChatCompletionResponseMessageAnnotation,
+    ChatCompletionStreamResponseDelta, ChatCompletionTokenLogprob, ChatCompletionToolChoiceOption,
+    ChatCompletionTools, CompletionUsage, CreateChatCompletionRequest,
+    CreateChatCompletionResponse, CreateChatCompletionStreamResponse, FinishReason,
+    FunctionCallStream, FunctionType, ReasoningEffort, ResponseFormat, ResponseModalities, Role,
+    StopConfiguration, ToolChoiceOptions, TopLogprobs, UrlCitation,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::error::CompositeLlmError;
+use crate::options::{SafetySetting, VertexOptions, VertexSearchTool};
 
-use super::{generate_chat_cmpl_id, unix_timestamp};
+use super::{
+    CHAT_COMPLETION_CHUNK_OBJECT, CHAT_COMPLETION_OBJECT, NativeResponse, UserContentPart,
+    assistant_text, developer_text, generate_chat_cmpl_id, generate_tool_call_id, max_tokens,
+    system_text, tool_text, unix_timestamp, unsupported_part, user_parts,
+};
 
 // ── Vertex AI REST API types ──
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VertexRequest {
     pub contents: Vec<VertexContent>,
@@ -32,38 +37,104 @@ pub struct VertexRequest {
     pub tools: Option<Vec<VertexTool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_config: Option<VertexToolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<Vec<SafetySetting>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VertexContent {
-    pub role: String,
+    /// `user` or `model`; omitted for `systemInstruction`, where the role is
+    /// ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
     pub parts: Vec<VertexPart>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VertexPart {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<VertexBlob>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<VertexFileData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub function_call: Option<VertexFunctionCall>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub function_response: Option<VertexFunctionResponse>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Base64-encoded bytes sent inline with the request, or generated by the
+/// model (images, audio) in a response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexBlob {
+    pub mime_type: String,
+    pub data: String,
+}
+
+impl VertexBlob {
+    /// The blob as a `data:` URI, the form OpenAI uses for inline images.
+    pub fn to_data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// A file referenced by URI (`gs://` or a public `https://` URL).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexFileData {
+    pub mime_type: String,
+    pub file_uri: String,
+}
+
+/// A function call. When arguments are streamed, a call is spread over
+/// several parts: the first carries the name, the following ones
+/// `partial_args`, and all but the last set `will_continue`; see
+/// [`FunctionCallAssembler`].
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct VertexFunctionCall {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub args: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partial_args: Vec<VertexPartialArg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub will_continue: Option<bool>,
+}
+
+/// One streamed argument value of a function call, at `json_path` (e.g.
+/// `$.location` or `$.items[0].name`). A value with none of the typed fields
+/// set is `null`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexPartialArg {
+    pub json_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub string_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bool_value: Option<bool>,
+    /// Whether `string_value` continues in the next value for this path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub will_continue: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VertexFunctionResponse {
     pub name: String,
     pub response: serde_json::Value,
 }
 
-#[derive(Debug, Serialize)]
+/// Gemini `generationConfig`.
+///
+/// Also usable as backend-wide defaults, see
+/// [`VertexBackend::with_generation_config`](crate::VertexBackend::with_generation_config).
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -71,20 +142,91 @@ pub struct GenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_output_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequences: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_logprobs: Option<bool>,
+    /// Number of top candidate tokens to return per position.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<u8>,
+    /// Number of candidates to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i32>,
+    /// Output modalities, e.g. `["TEXT", "IMAGE"]` for image generation.
+    /// Generated images and audio are returned as inline data; see
+    /// [`inline_data`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<Vec<String>>,
+}
+
+impl GenerationConfig {
+    /// Fills every field unset in `self` from `defaults`.
+    pub fn or(self, defaults: &GenerationConfig) -> Self {
+        let defaults = defaults.clone();
+        Self {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            max_output_tokens: self.max_output_tokens.or(defaults.max_output_tokens),
+            stop_sequences: self.stop_sequences.or(defaults.stop_sequences),
+            response_mime_type: self.response_mime_type.or(defaults.response_mime_type),
+            thinking_config: self.thinking_config.or(defaults.thinking_config),
+            response_logprobs: self.response_logprobs.or(defaults.response_logprobs),
+            logprobs: self.logprobs.or(defaults.logprobs),
+            candidate_count: self.candidate_count.or(defaults.candidate_count),
+            seed: self.seed.or(defaults.seed),
+            response_modalities: self.response_modalities.or(defaults.response_modalities),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThinkingConfig {
+    pub thinking_budget: i32,
 }
 
-#[derive(Debug, Serialize)]
+/// A Vertex tool entry. Each entry carries one kind of tool.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VertexTool {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub function_declarations: Vec<VertexFunctionDeclaration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<GoogleSearch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_search_retrieval: Option<GoogleSearchRetrieval>,
+}
+
+/// The `googleSearch` tool; it takes no parameters.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoogleSearch {}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleSearchRetrieval {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_retrieval_config: Option<DynamicRetrievalConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicRetrievalConfig {
+    pub mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_threshold: Option<f32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VertexFunctionDeclaration {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -93,17 +235,41 @@ pub struct VertexFunctionDeclaration {
     pub parameters: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VertexToolConfig {
     pub function_calling_config: FunctionCallingConfig,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctionCallingConfig {
     pub mode: String,
 }
 
+/// Body of a `:countTokens` request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexCountTokensRequest {
+    pub contents: Vec<VertexContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<VertexContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<VertexTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GenerationConfig>,
+}
+
+impl From<VertexRequest> for VertexCountTokensRequest {
+    fn from(req: VertexRequest) -> Self {
+        Self {
+            contents: req.contents,
+            system_instruction: req.system_instruction,
+            tools: req.tools,
+            generation_config: req.generation_config,
+        }
+    }
+}
+
 // ── Vertex AI Response types ──
 
 #[derive(Debug, Deserialize)]
@@ -113,11 +279,101 @@ pub struct VertexResponse {
     pub usage_metadata: Option<VertexUsageMetadata>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VertexCandidate {
+    /// Position of the candidate when `candidateCount` is above one.
+    pub index: Option<u32>,
     pub content: Option<VertexContent>,
     pub finish_reason: Option<String>,
+    pub citation_metadata: Option<VertexCitationMetadata>,
+    pub grounding_metadata: Option<VertexGroundingMetadata>,
+    pub logprobs_result: Option<VertexLogprobsResult>,
+    /// Average log probability of the candidate's tokens.
+    pub avg_logprobs: Option<f64>,
+}
+
+/// Log probabilities of the chosen tokens and, per position, the top
+/// candidates.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexLogprobsResult {
+    #[serde(default)]
+    pub top_candidates: Vec<VertexTopCandidates>,
+    #[serde(default)]
+    pub chosen_candidates: Vec<VertexLogprobCandidate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VertexTopCandidates {
+    #[serde(default)]
+    pub candidates: Vec<VertexLogprobCandidate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexLogprobCandidate {
+    pub token: Option<String>,
+    pub log_probability: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VertexCitationMetadata {
+    #[serde(default)]
+    pub citations: Vec<VertexCitation>,
+}
+
+/// A source the model recited from. Indices are UTF-8 byte offsets into the
+/// candidate text.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexCitation {
+    pub start_index: Option<u32>,
+    pub end_index: Option<u32>,
+    pub uri: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexGroundingMetadata {
+    #[serde(default)]
+    pub web_search_queries: Vec<String>,
+    #[serde(default)]
+    pub grounding_chunks: Vec<VertexGroundingChunk>,
+    #[serde(default)]
+    pub grounding_supports: Vec<VertexGroundingSupport>,
+}
+
+/// A grounding source: a web page or a retrieved document.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexGroundingChunk {
+    pub web: Option<VertexGroundingSource>,
+    pub retrieved_context: Option<VertexGroundingSource>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VertexGroundingSource {
+    pub uri: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Links a segment of the candidate text to the chunks supporting it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexGroundingSupport {
+    pub segment: Option<VertexSegment>,
+    #[serde(default)]
+    pub grounding_chunk_indices: Vec<usize>,
+}
+
+/// A span of the candidate text. Indices are UTF-8 byte offsets.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexSegment {
+    pub start_index: Option<u32>,
+    pub end_index: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -128,143 +384,158 @@ pub struct VertexUsageMetadata {
     pub total_token_count: Option<u32>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexCountTokensResponse {
+    pub total_tokens: u32,
+    pub total_billable_characters: Option<u32>,
+}
+
+/// A page of the `publishers/google/models` list.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexListPublisherModelsResponse {
+    #[serde(default)]
+    pub publisher_models: Vec<VertexPublisherModel>,
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VertexPublisherModel {
+    /// The resource name, e.g. `publishers/google/models/gemini-2.0-flash`.
+    pub name: String,
+}
+
+impl VertexPublisherModel {
+    /// The model ID, e.g. `gemini-2.0-flash`.
+    pub fn id(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
 // ── Conversion functions ──
 
+impl TryFrom<&CreateChatCompletionRequest> for VertexRequest {
+    type Error = CompositeLlmError;
+
+    /// Converts with [`convert_request`].
+    fn try_from(req: &CreateChatCompletionRequest) -> Result<Self, Self::Error> {
+        convert_request(req)
+    }
+}
+
+/// Converts an OpenAI request to a `generateContent` request body.
+///
+/// Also available as `VertexRequest::try_from(&req)`, for sending the request
+/// through another HTTP client.
+///
+/// Gemini may always return several function calls at once, so
+/// `parallel_tool_calls: false` is rejected as unsupported when tools are
+/// given.
 pub fn convert_request(
     req: &CreateChatCompletionRequest,
 ) -> Result<VertexRequest, CompositeLlmError> {
+    if req.parallel_tool_calls == Some(false) && req.tools.as_ref().is_some_and(|t| !t.is_empty()) {
+        return Err(CompositeLlmError::Unsupported(
+            "parallel_tool_calls: false is not supported by Vertex AI".to_string(),
+        ));
+    }
+
     let mut contents = Vec::new();
     let mut system_parts = Vec::new();
+    // Gemini matches function responses by name, while OpenAI tool results
+    // only carry the call id. Ids are resolved against the latest assistant
+    // turn only, since some models reuse ids across turns.
+    let mut call_names: HashMap<&str, &str> = HashMap::new();
 
     for msg in &req.messages {
         match msg {
             ChatCompletionRequestMessage::System(s) => {
-                let text = match &s.content {
-                    ChatCompletionRequestSystemMessageContent::Text(t) => t.clone(),
-                    ChatCompletionRequestSystemMessageContent::Array(parts) => parts
-                        .iter()
-                        .map(|ChatCompletionRequestSystemMessageContentPart::Text(t)| {
-                            t.text.clone()
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
                 system_parts.push(VertexPart {
-                    text: Some(text),
-                    function_call: None,
-                    function_response: None,
+                    text: Some(system_text(&s.content)),
+                    ..Default::default()
                 });
             }
             ChatCompletionRequestMessage::Developer(d) => {
-                let text = match &d.content {
-                    ChatCompletionRequestDeveloperMessageContent::Text(t) => t.clone(),
-                    ChatCompletionRequestDeveloperMessageContent::Array(parts) => parts
-                        .iter()
-                        .map(
-                            |ChatCompletionRequestDeveloperMessageContentPart::Text(t)| {
-                                t.text.clone()
-                            },
-                        )
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
                 system_parts.push(VertexPart {
-                    text: Some(text),
-                    function_call: None,
-                    function_response: None,
+                    text: Some(developer_text(&d.content)),
+                    ..Default::default()
                 });
             }
             ChatCompletionRequestMessage::User(u) => {
-                let text = match &u.content {
-                    ChatCompletionRequestUserMessageContent::Text(t) => t.clone(),
-                    ChatCompletionRequestUserMessageContent::Array(parts) => parts
-                        .iter()
-                        .filter_map(|p| match p {
-                            ChatCompletionRequestUserMessageContentPart::Text(t) => {
-                                Some(t.text.clone())
-                            }
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
+                let mut parts = Vec::new();
+                for part in user_parts(&u.content) {
+                    match part {
+                        UserContentPart::Text(t) => parts.push(VertexPart {
+                            text: Some(t.to_string()),
+                            ..Default::default()
+                        }),
+                        UserContentPart::Other(
+                            ChatCompletionRequestUserMessageContentPart::ImageUrl(img),
+                        ) => parts.push(convert_image_url(&img.image_url.url)?),
+                        UserContentPart::Other(other) => {
+                            return Err(unsupported_part(other, "Vertex AI"));
+                        }
+                    }
+                }
                 contents.push(VertexContent {
-                    role: "user".to_string(),
-                    parts: vec![VertexPart {
-                        text: Some(text),
-                        function_call: None,
-                        function_response: None,
-                    }],
+                    role: Some("user".to_string()),
+                    parts,
                 });
             }
             ChatCompletionRequestMessage::Assistant(a) => {
                 let mut parts = Vec::new();
                 if let Some(ref content) = a.content {
-                    let text = match content {
-                        ChatCompletionRequestAssistantMessageContent::Text(t) => t.clone(),
-                        ChatCompletionRequestAssistantMessageContent::Array(arr) => arr
-                            .iter()
-                            .filter_map(|p| match p {
-                                ChatCompletionRequestAssistantMessageContentPart::Text(t) => {
-                                    Some(t.text.clone())
-                                }
-                                _ => None,
-                            })
-                            .collect::<Vec<_>>()
-                            .join("\n"),
-                    };
+                    let text = assistant_text(content);
                     if !text.is_empty() {
                         parts.push(VertexPart {
                             text: Some(text),
-                            function_call: None,
-                            function_response: None,
+                            ..Default::default()
                         });
                     }
                 }
                 if let Some(ref tool_calls) = a.tool_calls {
+                    call_names.clear();
                     for tc in tool_calls {
                         if let ChatCompletionMessageToolCalls::Function(func_call) = tc {
+                            call_names.insert(&func_call.id, &func_call.function.name);
                             let args: serde_json::Value =
                                 serde_json::from_str(&func_call.function.arguments)
                                     .unwrap_or_default();
                             parts.push(VertexPart {
-                                text: None,
                                 function_call: Some(VertexFunctionCall {
                                     name: func_call.function.name.clone(),
                                     args,
+                                    ..Default::default()
                                 }),
-                                function_response: None,
+                                ..Default::default()
                             });
                         }
                     }
                 }
                 if !parts.is_empty() {
                     contents.push(VertexContent {
-                        role: "model".to_string(),
+                        role: Some("model".to_string()),
                         parts,
                     });
                 }
             }
             ChatCompletionRequestMessage::Tool(t) => {
-                let response_text = match &t.content {
-                    ChatCompletionRequestToolMessageContent::Text(text) => text.clone(),
-                    ChatCompletionRequestToolMessageContent::Array(parts) => parts
-                        .iter()
-                        .map(|ChatCompletionRequestToolMessageContentPart::Text(t)| t.text.clone())
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                };
+                let response_text = tool_text(&t.content)?;
                 let response_value = serde_json::from_str(&response_text)
                     .unwrap_or_else(|_| serde_json::json!({"result": response_text}));
                 contents.push(VertexContent {
-                    role: "user".to_string(),
+                    role: Some("user".to_string()),
                     parts: vec![VertexPart {
-                        text: None,
-                        function_call: None,
                         function_response: Some(VertexFunctionResponse {
-                            name: t.tool_call_id.clone(),
+                            name: call_names
+                                .get(t.tool_call_id.as_str())
+                                .copied()
+                                .unwrap_or(&t.tool_call_id)
+                                .to_string(),
                             response: response_value,
                         }),
+                        ..Default::default()
                     }],
                 });
             }
@@ -276,12 +547,12 @@ pub fn convert_request(
         None
     } else {
         Some(VertexContent {
-            role: "user".to_string(),
+            role: None,
             parts: system_parts,
         })
     };
 
-    let generation_config = build_generation_config(req);
+    let generation_config = build_generation_config(req)?;
     let tools = build_vertex_tools(req);
     let tool_config = build_vertex_tool_config(req);
 
@@ -291,24 +562,180 @@ pub fn convert_request(
         generation_config,
         tools,
         tool_config,
+        safety_settings: None,
     })
 }
 
-fn build_generation_config(req: &CreateChatCompletionRequest) -> Option<GenerationConfig> {
+/// Converts an image URL to a Vertex part.
+///
+/// - `data:<mime>;base64,<data>` URIs are sent inline as `inlineData`.
+/// - `gs://` URIs are sent as `fileData`; Vertex reads the object directly.
+/// - `http(s)://` URLs are sent as `fileData` as well; Vertex fetches them,
+///   so they must be publicly accessible.
+///
+/// For `fileData` the MIME type is inferred from the file extension.
+fn convert_image_url(url: &str) -> Result<VertexPart, CompositeLlmError> {
+    if let Some(rest) = url.strip_prefix("data:") {
+        let (mime_type, data) = rest.split_once(";base64,").ok_or_else(|| {
+            CompositeLlmError::Unsupported("only base64 data URIs are supported".to_string())
+        })?;
+        return Ok(VertexPart {
+            inline_data: Some(VertexBlob {
+                mime_type: mime_type.to_string(),
+                data: data.to_string(),
+            }),
+            ..Default::default()
+        });
+    }
+
+    if url.starts_with("gs://") || url.starts_with("https://") || url.starts_with("http://") {
+        let mime_type = mime_type_from_extension(url).ok_or_else(|| {
+            CompositeLlmError::Unsupported(format!("cannot infer image MIME type for {url}"))
+        })?;
+        return Ok(VertexPart {
+            file_data: Some(VertexFileData {
+                mime_type: mime_type.to_string(),
+                file_uri: url.to_string(),
+            }),
+            ..Default::default()
+        });
+    }
+
+    Err(CompositeLlmError::Unsupported(format!(
+        "unsupported image URL scheme: {url}"
+    )))
+}
+
+fn mime_type_from_extension(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "heic" => Some("image/heic"),
+        "heif" => Some("image/heif"),
+        _ => None,
+    }
+}
+
+/// Apply Vertex-specific options on top of a converted request.
+pub fn apply_vertex_options(vertex_req: &mut VertexRequest, opts: &VertexOptions) {
+    if let Some(ref settings) = opts.safety_settings {
+        vertex_req.safety_settings = Some(settings.clone());
+    }
+
+    if let Some(search) = opts.search {
+        let tool = match search {
+            VertexSearchTool::GoogleSearch => VertexTool {
+                google_search: Some(GoogleSearch {}),
+                ..Default::default()
+            },
+            VertexSearchTool::GoogleSearchRetrieval { dynamic_threshold } => VertexTool {
+                google_search_retrieval: Some(GoogleSearchRetrieval {
+                    dynamic_retrieval_config: dynamic_threshold.map(|threshold| {
+                        DynamicRetrievalConfig {
+                            mode: "MODE_DYNAMIC".to_string(),
+                            dynamic_threshold: Some(threshold),
+                        }
+                    }),
+                }),
+                ..Default::default()
+            },
+        };
+        vertex_req.tools.get_or_insert_with(Vec::new).push(tool);
+    }
+
+    if let Some(ref separator) = opts.system_separator
+        && let Some(ref mut instruction) = vertex_req.system_instruction
+    {
+        let text = instruction
+            .parts
+            .iter()
+            .filter_map(|part| part.text.as_deref())
+            .collect::<Vec<_>>()
+            .join(separator);
+        instruction.parts = vec![VertexPart {
+            text: Some(text),
+            ..Default::default()
+        }];
+    }
+
+    if opts.top_k.is_none() && opts.thinking_budget.is_none() && opts.response_modalities.is_none()
+    {
+        return;
+    }
+
+    let config = vertex_req
+        .generation_config
+        .get_or_insert_with(GenerationConfig::default);
+    if let Some(top_k) = opts.top_k {
+        config.top_k = Some(top_k);
+    }
+    if let Some(budget) = opts.thinking_budget {
+        config.thinking_config = Some(ThinkingConfig {
+            thinking_budget: budget,
+        });
+    }
+    if let Some(ref modalities) = opts.response_modalities {
+        config.response_modalities = Some(modalities.clone());
+    }
+}
+
+/// The smallest thinking budget Gemini Pro models accept; they cannot turn
+/// thinking off.
+pub const MIN_PRO_THINKING_BUDGET: i32 = 128;
+
+/// Raises the thinking budget of `vertex_req` to the minimum `model` accepts,
+/// so a `reasoning_effort` of `none` keeps thinking as low as a Pro model
+/// allows instead of being rejected.
+pub fn clamp_thinking_budget(vertex_req: &mut VertexRequest, model: &str) {
+    if !model.contains("-pro") {
+        return;
+    }
+    if let Some(ref mut thinking) = vertex_req
+        .generation_config
+        .as_mut()
+        .and_then(|config| config.thinking_config.as_mut())
+    {
+        thinking.thinking_budget = thinking.thinking_budget.max(MIN_PRO_THINKING_BUDGET);
+    }
+}
+
+/// Maximum number of stop sequences accepted by Gemini.
+pub const MAX_STOP_SEQUENCES: usize = 5;
+
+fn build_generation_config(
+    req: &CreateChatCompletionRequest,
+) -> Result<Option<GenerationConfig>, CompositeLlmError> {
     let has_params = req.temperature.is_some()
         || req.top_p.is_some()
-        || req.max_completion_tokens.is_some()
+        || max_tokens(req).is_some()
         || req.stop.is_some()
-        || req.response_format.is_some();
+        || req.response_format.is_some()
+        || req.reasoning_effort.is_some()
+        || req.logprobs.is_some()
+        || req.top_logprobs.is_some()
+        || req.n.is_some()
+        || req.modalities.is_some();
 
     if !has_params {
-        return None;
+        return Ok(None);
     }
 
     let stop_sequences = req.stop.as_ref().map(|s| match s {
         StopConfiguration::String(s) => vec![s.clone()],
         StopConfiguration::StringArray(arr) => arr.clone(),
     });
+    if let Some(ref stop) = stop_sequences
+        && stop.len() > MAX_STOP_SEQUENCES
+    {
+        return Err(CompositeLlmError::Unsupported(format!(
+            "Vertex AI accepts at most {MAX_STOP_SEQUENCES} stop sequences, got {}",
+            stop.len()
+        )));
+    }
 
     let response_mime_type = req.response_format.as_ref().and_then(|rf| match rf {
         ResponseFormat::JsonObject => Some("application/json".to_string()),
@@ -316,13 +743,45 @@ fn build_generation_config(req: &CreateChatCompletionRequest) -> Option<Generati
         _ => None,
     });
 
-    Some(GenerationConfig {
+    Ok(Some(GenerationConfig {
         temperature: req.temperature,
         top_p: req.top_p,
-        max_output_tokens: req.max_completion_tokens,
+        max_output_tokens: max_tokens(req),
         stop_sequences,
         response_mime_type,
-    })
+        thinking_config: req.reasoning_effort.as_ref().map(|effort| ThinkingConfig {
+            thinking_budget: thinking_budget_for_effort(effort),
+        }),
+        response_logprobs: req.logprobs,
+        logprobs: req.top_logprobs,
+        candidate_count: req.n.map(u32::from),
+        response_modalities: req.modalities.as_ref().map(|modalities| {
+            modalities
+                .iter()
+                .map(|m| match m {
+                    ResponseModalities::Text => "TEXT",
+                    ResponseModalities::Audio => "AUDIO",
+                })
+                .map(str::to_string)
+                .collect()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Maps an OpenAI `reasoning_effort` to a Gemini thinking budget in tokens.
+///
+/// `none` turns thinking off, which Pro models do not allow; see
+/// [`clamp_thinking_budget`]. An explicit [`VertexOptions::thinking_budget`]
+/// overrides the mapping.
+fn thinking_budget_for_effort(effort: &ReasoningEffort) -> i32 {
+    match effort {
+        ReasoningEffort::None => 0,
+        ReasoningEffort::Minimal => 512,
+        ReasoningEffort::Low => 1024,
+        ReasoningEffort::Medium => 8192,
+        ReasoningEffort::High | ReasoningEffort::Xhigh => 24576,
+    }
 }
 
 fn build_vertex_tools(req: &CreateChatCompletionRequest) -> Option<Vec<VertexTool>> {
@@ -348,6 +807,7 @@ fn build_vertex_tools(req: &CreateChatCompletionRequest) -> Option<Vec<VertexToo
     } else {
         Some(vec![VertexTool {
             function_declarations: declarations,
+            ..Default::default()
         }])
     }
 }
@@ -375,30 +835,95 @@ fn convert_finish_reason(reason: &str) -> FinishReason {
     match reason {
         "STOP" => FinishReason::Stop,
         "MAX_TOKENS" => FinishReason::Length,
-        "SAFETY" => FinishReason::ContentFilter,
-        _ => FinishReason::Stop,
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY" => {
+            FinishReason::ContentFilter
+        }
+        // Documented reasons with no closer OpenAI equivalent.
+        "FINISH_REASON_UNSPECIFIED" | "OTHER" | "LANGUAGE" | "MALFORMED_FUNCTION_CALL" => {
+            FinishReason::Stop
+        }
+        other => {
+            tracing::debug!(finish_reason = other, "unknown Vertex finish reason");
+            FinishReason::Stop
+        }
+    }
+}
+
+/// Gemini finishes a function-calling turn with `STOP`; report it as
+/// `ToolCalls` like OpenAI does, so clients know to run the tools.
+fn finish_reason_with_tool_calls(reason: FinishReason, has_tool_calls: bool) -> FinishReason {
+    match reason {
+        FinishReason::Stop if has_tool_calls => FinishReason::ToolCalls,
+        other => other,
+    }
+}
+
+/// Returns the `avgLogprobs` of each candidate, in choice order.
+///
+/// Gemini reports it on every candidate, even without `logprobs` requested,
+/// which makes it a cheap confidence signal.
+pub fn avg_logprobs(resp: &VertexResponse) -> Vec<Option<f64>> {
+    resp.candidates
+        .iter()
+        .flatten()
+        .map(|candidate| candidate.avg_logprobs)
+        .collect()
+}
+
+/// Returns the inline data parts (generated images or audio) of each
+/// candidate, indexed like the converted response's `choices`.
+///
+/// The OpenAI response has no place for them, so
+/// [`convert_vertex_response`] keeps only the text and function calls.
+pub fn inline_data(resp: &VertexResponse) -> Vec<Vec<VertexBlob>> {
+    resp.candidates
+        .iter()
+        .flatten()
+        .map(|candidate| candidate_blobs(candidate).cloned().collect())
+        .collect()
+}
+
+impl TryFrom<NativeResponse<'_, VertexResponse>> for CreateChatCompletionResponse {
+    type Error = CompositeLlmError;
+
+    /// Converts with [`convert_vertex_response`].
+    fn try_from(native: NativeResponse<'_, VertexResponse>) -> Result<Self, Self::Error> {
+        convert_vertex_response(&native.response, native.model, &generate_chat_cmpl_id())
     }
 }
 
+/// Converts a Vertex response to an OpenAI response carrying the given `id`,
+/// e.g. one from [`generate_chat_cmpl_id`](super::generate_chat_cmpl_id).
 #[allow(deprecated)]
 pub fn convert_vertex_response(
     resp: &VertexResponse,
     model: &str,
+    id: &str,
 ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
     let mut choices = Vec::new();
 
     if let Some(ref candidates) = resp.candidates {
         for (i, candidate) in candidates.iter().enumerate() {
             let (text, tool_calls) = extract_parts(candidate);
+            for blob in candidate_blobs(candidate) {
+                tracing::debug!(
+                    mime_type = blob.mime_type,
+                    "inline data left out of the OpenAI message; see `inline_data`"
+                );
+            }
+            let annotations = convert_annotations(candidate, &text);
 
-            let finish_reason = candidate
-                .finish_reason
-                .as_deref()
-                .map(convert_finish_reason)
-                .unwrap_or(FinishReason::Stop);
+            let finish_reason = finish_reason_with_tool_calls(
+                candidate
+                    .finish_reason
+                    .as_deref()
+                    .map(convert_finish_reason)
+                    .unwrap_or(FinishReason::Stop),
+                !tool_calls.is_empty(),
+            );
 
             choices.push(ChatChoice {
-                index: i as u32,
+                index: candidate.index.unwrap_or(i as u32),
                 message: ChatCompletionResponseMessage {
                     content: if text.is_empty() { None } else { Some(text) },
                     tool_calls: if tool_calls.is_empty() {
@@ -410,25 +935,23 @@ pub fn convert_vertex_response(
                     function_call: None,
                     refusal: None,
                     audio: None,
-                    annotations: None,
+                    annotations: if annotations.is_empty() {
+                        None
+                    } else {
+                        Some(annotations)
+                    },
                 },
                 finish_reason: Some(finish_reason),
-                logprobs: None,
+                logprobs: candidate.logprobs_result.as_ref().map(convert_logprobs),
             });
         }
     }
 
-    let usage = resp.usage_metadata.as_ref().map(|u| CompletionUsage {
-        prompt_tokens: u.prompt_token_count.unwrap_or(0),
-        completion_tokens: u.candidates_token_count.unwrap_or(0),
-        total_tokens: u.total_token_count.unwrap_or(0),
-        prompt_tokens_details: None,
-        completion_tokens_details: None,
-    });
+    let usage = resp.usage_metadata.as_ref().map(convert_vertex_usage);
 
     Ok(CreateChatCompletionResponse {
-        id: generate_chat_cmpl_id(),
-        object: "chat.completion".to_string(),
+        id: id.to_string(),
+        object: CHAT_COMPLETION_OBJECT.to_string(),
         created: unix_timestamp(),
         model: model.to_string(),
         choices,
@@ -438,6 +961,157 @@ pub fn convert_vertex_response(
     })
 }
 
+/// Maps citation and grounding metadata to URL citation annotations.
+///
+/// Each grounding support yields one annotation per supporting chunk; chunks
+/// not referenced by any support cover the whole text. Vertex reports UTF-8
+/// byte offsets, which are converted to the character offsets OpenAI uses.
+fn convert_annotations(
+    candidate: &VertexCandidate,
+    text: &str,
+) -> Vec<ChatCompletionResponseMessageAnnotation> {
+    let char_index = |byte: Option<u32>| {
+        let byte = (byte.unwrap_or(0) as usize).min(text.len());
+        text.char_indices().take_while(|(i, _)| *i < byte).count() as u32
+    };
+    let text_chars = text.chars().count() as u32;
+    let annotation = |start_index, end_index, url: &str, title: Option<&String>| {
+        ChatCompletionResponseMessageAnnotation::UrlCitation {
+            url_citation: UrlCitation {
+                start_index,
+                end_index,
+                title: title.cloned().unwrap_or_default(),
+                url: url.to_string(),
+            },
+        }
+    };
+
+    let mut annotations = Vec::new();
+
+    if let Some(ref metadata) = candidate.citation_metadata {
+        for citation in &metadata.citations {
+            if let Some(ref uri) = citation.uri {
+                annotations.push(annotation(
+                    char_index(citation.start_index),
+                    citation
+                        .end_index
+                        .map_or(text_chars, |e| char_index(Some(e))),
+                    uri,
+                    citation.title.as_ref(),
+                ));
+            }
+        }
+    }
+
+    if let Some(ref metadata) = candidate.grounding_metadata {
+        let sources: Vec<Option<&VertexGroundingSource>> = metadata
+            .grounding_chunks
+            .iter()
+            .map(|c| c.web.as_ref().or(c.retrieved_context.as_ref()))
+            .collect();
+        let mut referenced = vec![false; sources.len()];
+
+        for support in &metadata.grounding_supports {
+            let (start, end) = support
+                .segment
+                .as_ref()
+                .map_or((None, None), |s| (s.start_index, s.end_index));
+            for &i in &support.grounding_chunk_indices {
+                let Some(Some(source)) = sources.get(i) else {
+                    continue;
+                };
+                referenced[i] = true;
+                if let Some(ref uri) = source.uri {
+                    annotations.push(annotation(
+                        char_index(start),
+                        end.map_or(text_chars, |e| char_index(Some(e))),
+                        uri,
+                        source.title.as_ref(),
+                    ));
+                }
+            }
+        }
+
+        for (source, referenced) in sources.iter().zip(referenced) {
+            if let (Some(source), false) = (source, referenced)
+                && let Some(ref uri) = source.uri
+            {
+                annotations.push(annotation(0, text_chars, uri, source.title.as_ref()));
+            }
+        }
+    }
+
+    annotations
+}
+
+/// Converts a `logprobsResult` to OpenAI content logprobs.
+fn convert_logprobs(result: &VertexLogprobsResult) -> ChatChoiceLogprobs {
+    let token_logprob = |c: &VertexLogprobCandidate| {
+        let token = c.token.clone().unwrap_or_default();
+        (
+            token.as_bytes().to_vec(),
+            token,
+            c.log_probability.unwrap_or(0.0),
+        )
+    };
+
+    let content = result
+        .chosen_candidates
+        .iter()
+        .enumerate()
+        .map(|(i, chosen)| {
+            let (bytes, token, logprob) = token_logprob(chosen);
+            let top_logprobs = result
+                .top_candidates
+                .get(i)
+                .map(|top| {
+                    top.candidates
+                        .iter()
+                        .map(|c| {
+                            let (bytes, token, logprob) = token_logprob(c);
+                            TopLogprobs {
+                                token,
+                                logprob,
+                                bytes: Some(bytes),
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            ChatCompletionTokenLogprob {
+                token,
+                logprob,
+                bytes: Some(bytes),
+                top_logprobs,
+            }
+        })
+        .collect();
+
+    ChatChoiceLogprobs {
+        content: Some(content),
+        refusal: None,
+    }
+}
+
+pub fn convert_vertex_usage(usage: &VertexUsageMetadata) -> CompletionUsage {
+    CompletionUsage {
+        prompt_tokens: usage.prompt_token_count.unwrap_or(0),
+        completion_tokens: usage.candidates_token_count.unwrap_or(0),
+        total_tokens: usage.total_token_count.unwrap_or(0),
+        prompt_tokens_details: None,
+        completion_tokens_details: None,
+    }
+}
+
+/// The inline data parts of a candidate.
+fn candidate_blobs(candidate: &VertexCandidate) -> impl Iterator<Item = &VertexBlob> {
+    candidate
+        .content
+        .iter()
+        .flat_map(|content| &content.parts)
+        .filter_map(|part| part.inline_data.as_ref())
+}
+
 fn extract_parts(candidate: &VertexCandidate) -> (String, Vec<ChatCompletionMessageToolCalls>) {
     let mut text = String::new();
     let mut tool_calls = Vec::new();
@@ -450,7 +1124,7 @@ fn extract_parts(candidate: &VertexCandidate) -> (String, Vec<ChatCompletionMess
             if let Some(ref fc) = part.function_call {
                 tool_calls.push(ChatCompletionMessageToolCalls::Function(
                     ChatCompletionMessageToolCall {
-                        id: format!("call_{}", uuid::Uuid::new_v4().as_simple()),
+                        id: generate_tool_call_id(),
                         function: FunctionCall {
                             name: fc.name.clone(),
                             arguments: serde_json::to_string(&fc.args)
@@ -465,6 +1139,18 @@ fn extract_parts(candidate: &VertexCandidate) -> (String, Vec<ChatCompletionMess
     (text, tool_calls)
 }
 
+/// Converts a streamed Vertex response to an OpenAI stream chunk.
+///
+/// Every candidate in the response becomes a choice delta indexed by the
+/// candidate's `index`, so streams requested with `candidateCount` above one
+/// interleave their choices like OpenAI does with `n`.
+///
+/// Gemini streams each function call whole, so every call becomes a single
+/// tool call delta, indexed by its position in the chunk; a caller converting
+/// several chunks of one stream offsets the indices by the calls already sent.
+///
+/// Usage is never attached here; the stream reports it once, in a final
+/// usage-only chunk, when `stream_options.include_usage` is set.
 #[allow(deprecated)]
 pub fn convert_vertex_stream_chunk(
     resp: &VertexResponse,
@@ -472,82 +1158,261 @@ pub fn convert_vertex_stream_chunk(
     id: &str,
 ) -> Option<CreateChatCompletionStreamResponse> {
     let candidates = resp.candidates.as_ref()?;
-    let candidate = candidates.first()?;
-
-    let (text, _tool_calls) = extract_parts(candidate);
-
-    let finish_reason = candidate
-        .finish_reason
-        .as_deref()
-        .map(convert_finish_reason);
-
-    let usage = resp.usage_metadata.as_ref().map(|u| CompletionUsage {
-        prompt_tokens: u.prompt_token_count.unwrap_or(0),
-        completion_tokens: u.candidates_token_count.unwrap_or(0),
-        total_tokens: u.total_token_count.unwrap_or(0),
-        prompt_tokens_details: None,
-        completion_tokens_details: None,
-    });
+    if candidates.is_empty() {
+        return None;
+    }
 
     Some(CreateChatCompletionStreamResponse {
         id: id.to_string(),
-        object: "chat.completion.chunk".to_string(),
+        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
         created: unix_timestamp(),
         model: model.to_string(),
-        choices: vec![ChatChoiceStream {
-            index: 0,
-            delta: ChatCompletionStreamResponseDelta {
-                content: if text.is_empty() { None } else { Some(text) },
-                tool_calls: None,
-                role: Some(Role::Assistant),
-                function_call: None,
-                refusal: None,
-            },
-            finish_reason,
-            logprobs: None,
-        }],
-        usage,
+        choices: candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| convert_stream_candidate(candidate, i as u32))
+            .collect(),
+        usage: None,
         system_fingerprint: None,
         service_tier: None,
     })
 }
 
-/// Parse SSE data lines from a byte buffer, returning parsed responses and remaining bytes.
-pub fn parse_sse_events(buffer: &[u8]) -> (Vec<VertexResponse>, Vec<u8>) {
-    let text = String::from_utf8_lossy(buffer);
-    let mut responses = Vec::new();
+#[allow(deprecated)]
+fn convert_stream_candidate(candidate: &VertexCandidate, position: u32) -> ChatChoiceStream {
+    let (text, tool_calls) = extract_parts(candidate);
+    for blob in candidate_blobs(candidate) {
+        tracing::warn!(
+            mime_type = blob.mime_type,
+            "dropping inline data from a Vertex AI stream; use chat_completion_with_details"
+        );
+    }
+
+    let finish_reason = candidate
+        .finish_reason
+        .as_deref()
+        .map(convert_finish_reason)
+        .map(|reason| finish_reason_with_tool_calls(reason, !tool_calls.is_empty()));
+    let tool_calls: Vec<_> = tool_calls
+        .into_iter()
+        .filter_map(|call| match call {
+            ChatCompletionMessageToolCalls::Function(call) => Some(call),
+            _ => None,
+        })
+        .enumerate()
+        .map(|(i, call)| ChatCompletionMessageToolCallChunk {
+            index: i as u32,
+            id: Some(call.id),
+            r#type: Some(FunctionType::Function),
+            function: Some(FunctionCallStream {
+                name: Some(call.function.name),
+                arguments: Some(call.function.arguments),
+            }),
+        })
+        .collect();
+
+    // A finish-only delta carries no role; some clients reject a role
+    // without content.
+    let role = (!text.is_empty() || !tool_calls.is_empty()).then_some(Role::Assistant);
+
+    ChatChoiceStream {
+        index: candidate.index.unwrap_or(position),
+        delta: ChatCompletionStreamResponseDelta {
+            content: if text.is_empty() { None } else { Some(text) },
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            },
+            role,
+            function_call: None,
+            refusal: None,
+        },
+        finish_reason,
+        logprobs: candidate.logprobs_result.as_ref().map(convert_logprobs),
+    }
+}
+
+/// Reassembles function calls whose arguments are streamed across events.
+///
+/// Parts of a call still in progress are taken out of the response and
+/// merged, and the last part is replaced by the complete call, so a tool
+/// call is only converted once its arguments are whole, valid JSON. Calls
+/// sent in a single part pass through unchanged.
+#[derive(Debug, Default)]
+pub struct FunctionCallAssembler {
+    /// The call in progress, per candidate index.
+    pending: HashMap<u32, PendingFunctionCall>,
+}
 
-    // Find the last complete event boundary (double newline)
-    let last_boundary = text.rfind("\n\n");
+#[derive(Debug)]
+struct PendingFunctionCall {
+    name: String,
+    args: serde_json::Value,
+    /// Paths of string values that continue in a later part.
+    open_strings: HashSet<String>,
+}
 
-    let (complete_text, remaining_text) = match last_boundary {
-        Some(pos) => {
-            let complete = &text[..pos + 2];
-            let remaining = &text[pos + 2..];
-            (complete, remaining)
-        }
-        None => {
-            // No complete event found
-            return (vec![], buffer.to_vec());
+impl FunctionCallAssembler {
+    /// Merges the streamed function call parts of `resp` into the calls in
+    /// progress. Returns whether any part was held back.
+    pub fn assemble(&mut self, resp: &mut VertexResponse) -> bool {
+        let mut held_back = false;
+        let candidates = resp.candidates.iter_mut().flatten().enumerate();
+        for (position, candidate) in candidates {
+            let index = candidate.index.unwrap_or(position as u32);
+            let Some(ref mut content) = candidate.content else {
+                continue;
+            };
+            let mut parts = Vec::with_capacity(content.parts.len());
+            for mut part in std::mem::take(&mut content.parts) {
+                let Some(call) = part.function_call.take_if(|call| {
+                    call.will_continue == Some(true)
+                        || !call.partial_args.is_empty()
+                        || self.pending.contains_key(&index)
+                }) else {
+                    parts.push(part);
+                    continue;
+                };
+                if let Some(call) = self.merge(index, call) {
+                    part.function_call = Some(call);
+                    parts.push(part);
+                } else {
+                    held_back = true;
+                }
+            }
+            content.parts = parts;
         }
-    };
+        held_back
+    }
+
+    /// Whether a call is still incomplete, e.g. because the stream ended.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
 
-    for line in complete_text.lines() {
-        let trimmed = line.trim();
-        if let Some(json_str) = trimmed.strip_prefix("data: ")
-            && let Ok(resp) = serde_json::from_str::<VertexResponse>(json_str)
+    /// Merges one part of a call; returns the call once it is complete.
+    fn merge(&mut self, index: u32, call: VertexFunctionCall) -> Option<VertexFunctionCall> {
+        let pending = self
+            .pending
+            .entry(index)
+            .or_insert_with(|| PendingFunctionCall {
+                name: String::new(),
+                args: serde_json::Value::Object(Default::default()),
+                open_strings: HashSet::new(),
+            });
+        if !call.name.is_empty() {
+            pending.name = call.name;
+        }
+        if let serde_json::Value::Object(args) = call.args
+            && let serde_json::Value::Object(ref mut merged) = pending.args
         {
-            responses.push(resp);
+            merged.extend(args);
+        }
+        for arg in call.partial_args {
+            pending.set(arg);
+        }
+        if call.will_continue == Some(true) {
+            return None;
         }
+        let done = self.pending.remove(&index)?;
+        Some(VertexFunctionCall {
+            name: done.name,
+            args: done.args,
+            ..Default::default()
+        })
     }
+}
 
-    let remaining = if remaining_text.trim().is_empty() {
-        Vec::new()
+impl PendingFunctionCall {
+    fn set(&mut self, arg: VertexPartialArg) {
+        let Some(slot) = json_path_entry(&mut self.args, &arg.json_path) else {
+            tracing::warn!(
+                json_path = arg.json_path,
+                "skipping a streamed function argument with an unsupported path"
+            );
+            return;
+        };
+        if let Some(text) = arg.string_value {
+            match slot {
+                serde_json::Value::String(s) if self.open_strings.contains(&arg.json_path) => {
+                    s.push_str(&text)
+                }
+                _ => *slot = serde_json::Value::String(text),
+            }
+        } else if let Some(number) = arg.number_value {
+            *slot = json_number(number);
+        } else if let Some(b) = arg.bool_value {
+            *slot = serde_json::Value::Bool(b);
+        } else {
+            *slot = serde_json::Value::Null;
+        }
+        if arg.will_continue == Some(true) {
+            self.open_strings.insert(arg.json_path);
+        } else {
+            self.open_strings.remove(&arg.json_path);
+        }
+    }
+}
+
+/// Converts a streamed `numberValue`, keeping whole numbers as integers so
+/// they deserialize into integer fields.
+fn json_number(number: f64) -> serde_json::Value {
+    if number.fract() == 0.0 && number >= i64::MIN as f64 && number < i64::MAX as f64 {
+        serde_json::Value::Number((number as i64).into())
     } else {
-        remaining_text.as_bytes().to_vec()
-    };
+        serde_json::json!(number)
+    }
+}
 
-    (responses, remaining)
+/// Returns the value at a JSONPath of `.key` and `[index]` steps, creating
+/// objects, arrays and `null` slots on the way.
+fn json_path_entry<'a>(
+    root: &'a mut serde_json::Value,
+    path: &str,
+) -> Option<&'a mut serde_json::Value> {
+    let mut rest = path.strip_prefix('$')?;
+    let mut value = root;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            rest = &after[end..];
+            if !value.is_object() {
+                *value = serde_json::Value::Object(Default::default());
+            }
+            value = value
+                .as_object_mut()?
+                .entry(key)
+                .or_insert(serde_json::Value::Null);
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let i: usize = after[..end].parse().ok()?;
+            rest = &after[end + 1..];
+            if !value.is_array() {
+                *value = serde_json::Value::Array(Vec::new());
+            }
+            let items = value.as_array_mut()?;
+            // Only append or overwrite; a far index from the server must not
+            // allocate or overflow.
+            if i > items.len() {
+                return None;
+            }
+            if i == items.len() {
+                items.push(serde_json::Value::Null);
+            }
+            value = &mut items[i];
+        } else {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+/// Parses the data of one streamed event; events that are not a valid
+/// response are skipped. Used with [`SseDecoder`](crate::backend::SseDecoder).
+pub fn parse_sse_event(data: &str) -> Option<VertexResponse> {
+    serde_json::from_str(data).ok()
 }
 
 #[cfg(test)]
@@ -581,7 +1446,480 @@ mod tests {
         let vertex_req = convert_request(&req).unwrap();
         assert!(vertex_req.system_instruction.is_some());
         assert_eq!(vertex_req.contents.len(), 1);
-        assert_eq!(vertex_req.contents[0].role, "user");
+        assert_eq!(vertex_req.contents[0].role.as_deref(), Some("user"));
+
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(
+            json["systemInstruction"],
+            serde_json::json!({"parts": [{"text": "Be helpful."}]})
+        );
+    }
+
+    #[test]
+    fn test_vertex_request_try_from() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        let vertex_req = VertexRequest::try_from(&req).unwrap();
+        assert_eq!(vertex_req, convert_request(&req).unwrap());
+    }
+
+    #[test]
+    fn test_parallel_tool_calls_false_is_unsupported() {
+        let mut req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-pro",
+            "messages": [],
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}],
+            "parallel_tool_calls": false
+        }))
+        .unwrap();
+        assert!(matches!(
+            convert_request(&req),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+
+        req.parallel_tool_calls = Some(true);
+        assert!(convert_request(&req).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_user_part_is_rejected() {
+        let req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-pro",
+            "messages": [{
+                "role": "user",
+                "content": [{"type": "input_audio", "input_audio": {"data": "UklGRg==", "format": "wav"}}]
+            }]
+        }))
+        .unwrap();
+        match convert_request(&req) {
+            Err(CompositeLlmError::Unsupported(message)) => {
+                assert_eq!(message, "input_audio content is not supported by Vertex AI")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_repeated_tool_call_ids_across_turns() {
+        let tool_turn = |name: &str, result: &str| {
+            vec![
+                serde_json::json!({"role": "user", "content": "next"}),
+                serde_json::json!({
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": name, "arguments": "{}"}
+                    }]
+                }),
+                serde_json::json!({"role": "tool", "tool_call_id": "call_1", "content": result}),
+            ]
+        };
+        let mut messages = tool_turn("get_weather", "sunny");
+        messages.extend(tool_turn("get_time", "noon"));
+        messages.extend(tool_turn("get_weather", "rainy"));
+        let req: CreateChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-pro",
+            "messages": messages,
+        }))
+        .unwrap();
+
+        let vertex_req = convert_request(&req).unwrap();
+        let responses: Vec<_> = vertex_req
+            .contents
+            .iter()
+            .flat_map(|c| &c.parts)
+            .filter_map(|p| p.function_response.as_ref())
+            .map(|r| (r.name.as_str(), r.response["result"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            responses,
+            vec![
+                ("get_weather", "sunny"),
+                ("get_time", "noon"),
+                ("get_weather", "rainy"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_tokens_request() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Hello")
+                    .build()
+                    .unwrap(),
+            )],
+            ..Default::default()
+        };
+
+        let count_req = VertexCountTokensRequest::from(convert_request(&req).unwrap());
+        assert_eq!(
+            serde_json::to_value(&count_req).unwrap(),
+            serde_json::json!({"contents": [{"role": "user", "parts": [{"text": "Hello"}]}]})
+        );
+
+        let resp: VertexCountTokensResponse =
+            serde_json::from_str(r#"{"totalTokens": 31, "totalBillableCharacters": 96}"#).unwrap();
+        assert_eq!(resp.total_tokens, 31);
+    }
+
+    #[test]
+    fn test_list_publisher_models_response() {
+        let resp: VertexListPublisherModelsResponse = serde_json::from_value(serde_json::json!({
+            "publisherModels": [
+                {"name": "publishers/google/models/gemini-2.0-flash", "versionId": "001"}
+            ],
+            "nextPageToken": "abc"
+        }))
+        .unwrap();
+        assert_eq!(resp.publisher_models[0].id(), "gemini-2.0-flash");
+        assert_eq!(resp.next_page_token.as_deref(), Some("abc"));
+
+        let last: VertexListPublisherModelsResponse =
+            serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(last.publisher_models.is_empty());
+    }
+
+    #[test]
+    fn test_convert_request_image_parts() {
+        use async_openai::types::chat::{
+            ChatCompletionRequestMessageContentPartImage,
+            ChatCompletionRequestMessageContentPartText, ImageUrl,
+        };
+
+        let image = |url: &str| {
+            ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                ChatCompletionRequestMessageContentPartImage {
+                    image_url: ImageUrl {
+                        url: url.to_string(),
+                        detail: None,
+                    },
+                },
+            )
+        };
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(vec![
+                        ChatCompletionRequestUserMessageContentPart::Text(
+                            ChatCompletionRequestMessageContentPartText {
+                                text: "Compare these.".to_string(),
+                            },
+                        ),
+                        image("gs://bucket/photos/cat.JPG"),
+                        image("data:image/png;base64,iVBORw0KGgo="),
+                    ])
+                    .build()
+                    .unwrap(),
+            )],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(convert_request(&req).unwrap()).unwrap();
+        let parts = &json["contents"][0]["parts"];
+        assert_eq!(parts[0]["text"], "Compare these.");
+        assert_eq!(
+            parts[1]["fileData"]["fileUri"],
+            "gs://bucket/photos/cat.JPG"
+        );
+        assert_eq!(parts[1]["fileData"]["mimeType"], "image/jpeg");
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(parts[2]["inlineData"]["data"], "iVBORw0KGgo=");
+
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(vec![image("gs://bucket/no-extension")])
+                    .build()
+                    .unwrap(),
+            )],
+            ..Default::default()
+        };
+        assert!(matches!(
+            convert_request(&req),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_convert_request_generation_config_value() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Hello")
+                    .build()
+                    .unwrap(),
+            )],
+            temperature: Some(0.5),
+            stop: Some(StopConfiguration::String("END".to_string())),
+            ..Default::default()
+        };
+
+        let vertex_req = convert_request(&req).unwrap();
+        assert_eq!(
+            vertex_req.generation_config,
+            Some(GenerationConfig {
+                temperature: Some(0.5),
+                stop_sequences: Some(vec!["END".to_string()]),
+                ..Default::default()
+            })
+        );
+
+        let json = serde_json::to_string(&vertex_req).unwrap();
+        let round_trip: VertexRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip, vertex_req);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_generation_config_max_tokens_fallback() {
+        let mut req = CreateChatCompletionRequest {
+            max_tokens: Some(50),
+            ..Default::default()
+        };
+        let config = build_generation_config(&req).unwrap().unwrap();
+        assert_eq!(config.max_output_tokens, Some(50));
+
+        req.max_completion_tokens = Some(100);
+        let config = build_generation_config(&req).unwrap().unwrap();
+        assert_eq!(config.max_output_tokens, Some(100));
+    }
+
+    #[test]
+    fn test_generation_config_defaults() {
+        let defaults = GenerationConfig {
+            temperature: Some(0.2),
+            top_k: Some(40),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            temperature: Some(0.9),
+            ..Default::default()
+        };
+
+        let config = convert_request(&req)
+            .unwrap()
+            .generation_config
+            .unwrap()
+            .or(&defaults);
+        assert_eq!(config.temperature, Some(0.9));
+        assert_eq!(config.top_k, Some(40));
+        assert_eq!(config.seed, Some(7));
+    }
+
+    #[test]
+    fn test_stop_sequence_limit() {
+        let req = |count: usize| CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            stop: Some(StopConfiguration::StringArray(
+                (0..count).map(|i| format!("STOP{i}")).collect(),
+            )),
+            ..Default::default()
+        };
+
+        let vertex_req = convert_request(&req(MAX_STOP_SEQUENCES)).unwrap();
+        let stop = vertex_req
+            .generation_config
+            .unwrap()
+            .stop_sequences
+            .unwrap();
+        assert_eq!(stop.len(), MAX_STOP_SEQUENCES);
+        assert!(matches!(
+            convert_request(&req(MAX_STOP_SEQUENCES + 1)),
+            Err(CompositeLlmError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_reasoning_effort_maps_to_thinking_budget() {
+        let mut req = CreateChatCompletionRequest {
+            model: "gemini-2.5-flash".to_string(),
+            messages: vec![],
+            reasoning_effort: Some(ReasoningEffort::Low),
+            ..Default::default()
+        };
+        let vertex_req = convert_request(&req).unwrap();
+        assert_eq!(
+            vertex_req.generation_config.unwrap().thinking_config,
+            Some(ThinkingConfig {
+                thinking_budget: 1024
+            })
+        );
+
+        req.reasoning_effort = Some(ReasoningEffort::High);
+        let mut vertex_req = convert_request(&req).unwrap();
+        let opts = VertexOptions {
+            thinking_budget: Some(100),
+            ..Default::default()
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+        assert_eq!(
+            vertex_req.generation_config.unwrap().thinking_config,
+            Some(ThinkingConfig {
+                thinking_budget: 100
+            })
+        );
+
+        req.reasoning_effort = Some(ReasoningEffort::None);
+        let mut vertex_req = convert_request(&req).unwrap();
+        clamp_thinking_budget(&mut vertex_req, "gemini-2.5-flash");
+        let budget = |vertex_req: &VertexRequest| {
+            let config = vertex_req.generation_config.as_ref().unwrap();
+            config.thinking_config.as_ref().unwrap().thinking_budget
+        };
+        assert_eq!(budget(&vertex_req), 0);
+        clamp_thinking_budget(&mut vertex_req, "gemini-2.5-pro");
+        assert_eq!(budget(&vertex_req), MIN_PRO_THINKING_BUDGET);
+    }
+
+    #[test]
+    fn test_system_separator() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessageArgs::default()
+                        .content("Be brief.")
+                        .build()
+                        .unwrap(),
+                ),
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessageArgs::default()
+                        .content("Answer in French.")
+                        .build()
+                        .unwrap(),
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let mut vertex_req = convert_request(&req).unwrap();
+        assert_eq!(
+            vertex_req.system_instruction.as_ref().unwrap().parts.len(),
+            2
+        );
+
+        let opts = VertexOptions {
+            system_separator: Some("\n\n".to_string()),
+            ..Default::default()
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+        let parts = vertex_req.system_instruction.unwrap().parts;
+        assert_eq!(parts.len(), 1);
+        assert_eq!(
+            parts[0].text.as_deref(),
+            Some("Be brief.\n\nAnswer in French.")
+        );
+    }
+
+    #[test]
+    fn test_response_modalities() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-2.0-flash".to_string(),
+            modalities: Some(vec![ResponseModalities::Text, ResponseModalities::Audio]),
+            ..Default::default()
+        };
+
+        let mut vertex_req = convert_request(&req).unwrap();
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(
+            json["generationConfig"]["responseModalities"],
+            serde_json::json!(["TEXT", "AUDIO"])
+        );
+
+        let opts = VertexOptions {
+            response_modalities: Some(vec!["TEXT".to_string(), "IMAGE".to_string()]),
+            ..Default::default()
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(
+            json["generationConfig"]["responseModalities"],
+            serde_json::json!(["TEXT", "IMAGE"])
+        );
+    }
+
+    #[test]
+    fn test_apply_vertex_options() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        let mut vertex_req = convert_request(&req).unwrap();
+        let opts = VertexOptions {
+            safety_settings: Some(vec![SafetySetting {
+                category: "HARM_CATEGORY_HATE_SPEECH".to_string(),
+                threshold: "BLOCK_ONLY_HIGH".to_string(),
+            }]),
+            top_k: Some(40),
+            thinking_budget: Some(1024),
+            search: None,
+            system_separator: None,
+            response_modalities: None,
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(json["generationConfig"]["temperature"], 0.5);
+        assert_eq!(json["generationConfig"]["topK"], 40);
+        assert_eq!(
+            json["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            1024
+        );
+        assert_eq!(
+            json["safetySettings"][0]["category"],
+            "HARM_CATEGORY_HATE_SPEECH"
+        );
+    }
+
+    #[test]
+    fn test_apply_vertex_search_tool() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-2.0-flash".to_string(),
+            messages: vec![],
+            ..Default::default()
+        };
+
+        let mut vertex_req = convert_request(&req).unwrap();
+        let opts = VertexOptions {
+            search: Some(VertexSearchTool::GoogleSearch),
+            ..Default::default()
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(json["tools"], serde_json::json!([{"googleSearch": {}}]));
+
+        let mut vertex_req = convert_request(&req).unwrap();
+        let opts = VertexOptions {
+            search: Some(VertexSearchTool::GoogleSearchRetrieval {
+                dynamic_threshold: Some(0.5),
+            }),
+            ..Default::default()
+        };
+        apply_vertex_options(&mut vertex_req, &opts);
+        let json = serde_json::to_value(&vertex_req).unwrap();
+        assert_eq!(
+            json["tools"],
+            serde_json::json!([{"googleSearchRetrieval": {
+                "dynamicRetrievalConfig": {"mode": "MODE_DYNAMIC", "dynamicThreshold": 0.5}
+            }}])
+        );
     }
 
     #[test]
@@ -589,14 +1927,14 @@ mod tests {
         let resp = VertexResponse {
             candidates: Some(vec![VertexCandidate {
                 content: Some(VertexContent {
-                    role: "model".to_string(),
+                    role: Some("model".to_string()),
                     parts: vec![VertexPart {
                         text: Some("Hello!".to_string()),
-                        function_call: None,
-                        function_response: None,
+                        ..Default::default()
                     }],
                 }),
                 finish_reason: Some("STOP".to_string()),
+                ..Default::default()
             }]),
             usage_metadata: Some(VertexUsageMetadata {
                 prompt_token_count: Some(10),
@@ -605,20 +1943,275 @@ mod tests {
             }),
         };
 
-        let result = convert_vertex_response(&resp, "gemini-pro").unwrap();
+        let result = convert_vertex_response(&resp, "gemini-pro", "chatcmpl-test").unwrap();
+        assert_eq!(result.id, "chatcmpl-test");
+        assert_eq!(result.object, CHAT_COMPLETION_OBJECT);
         assert_eq!(result.choices.len(), 1);
         assert_eq!(result.choices[0].message.content.as_deref(), Some("Hello!"));
         assert_eq!(result.choices[0].finish_reason, Some(FinishReason::Stop));
         let usage = result.usage.unwrap();
         assert_eq!(usage.prompt_tokens, 10);
         assert_eq!(usage.completion_tokens, 5);
+
+        let converted =
+            CreateChatCompletionResponse::try_from(NativeResponse::from((resp, "gemini-pro")))
+                .unwrap();
+        assert_eq!(converted.model, "gemini-pro");
+        assert!(converted.id.starts_with("chatcmpl-"));
+        assert_eq!(converted.choices[0].message, result.choices[0].message);
+    }
+
+    #[test]
+    fn test_convert_vertex_response_annotations() {
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Café is open. Rust is fast."}]},
+                "finishReason": "STOP",
+                "citationMetadata": {"citations": [
+                    {"startIndex": 0, "endIndex": 14, "uri": "https://example.com/cafe"}
+                ]},
+                "groundingMetadata": {
+                    "webSearchQueries": ["rust speed"],
+                    "groundingChunks": [
+                        {"web": {"uri": "https://example.com/rust", "title": "Rust"}},
+                        {"web": {"uri": "https://example.com/other", "title": "Other"}}
+                    ],
+                    "groundingSupports": [
+                        {"segment": {"startIndex": 15, "endIndex": 28}, "groundingChunkIndices": [0]}
+                    ]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let result = convert_vertex_response(&resp, "gemini-pro", "chatcmpl-test").unwrap();
+        let citations: Vec<_> = result.choices[0]
+            .message
+            .annotations
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|a| match a {
+                ChatCompletionResponseMessageAnnotation::UrlCitation { url_citation } => {
+                    url_citation.clone()
+                }
+            })
+            .collect();
+
+        assert_eq!(citations.len(), 3);
+        // "Café" has a two-byte "é", so byte offsets shift by one character.
+        assert_eq!(citations[0].url, "https://example.com/cafe");
+        assert_eq!((citations[0].start_index, citations[0].end_index), (0, 13));
+        assert_eq!(citations[1].url, "https://example.com/rust");
+        assert_eq!(citations[1].title, "Rust");
+        assert_eq!((citations[1].start_index, citations[1].end_index), (14, 27));
+        assert_eq!(citations[2].url, "https://example.com/other");
+        assert_eq!((citations[2].start_index, citations[2].end_index), (0, 27));
+    }
+
+    #[test]
+    fn test_stream_chunk_with_two_candidates() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            n: Some(2),
+            ..Default::default()
+        };
+        let config = convert_request(&req).unwrap().generation_config.unwrap();
+        assert_eq!(config.candidate_count, Some(2));
+
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "Hi"}]}},
+                {
+                    "index": 1,
+                    "content": {"role": "model", "parts": [{"text": "Hello"}]},
+                    "finishReason": "STOP"
+                }
+            ]
+        }))
+        .unwrap();
+
+        let chunk = convert_vertex_stream_chunk(&resp, "gemini-pro", "id").unwrap();
+        assert_eq!(chunk.choices.len(), 2);
+        assert_eq!(chunk.choices[0].index, 0);
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Hi"));
+        assert_eq!(chunk.choices[0].finish_reason, None);
+        assert_eq!(chunk.choices[1].index, 1);
+        assert_eq!(chunk.choices[1].delta.content.as_deref(), Some("Hello"));
+        assert_eq!(chunk.choices[1].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[test]
+    fn test_stream_chunk_finish_only_has_no_role() {
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": ""}]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let chunk = convert_vertex_stream_chunk(&resp, "gemini-pro", "id").unwrap();
+        let choice = &chunk.choices[0];
+        assert_eq!(choice.delta.role, None);
+        assert_eq!(choice.delta.content, None);
+        assert_eq!(choice.finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[test]
+    fn test_avg_logprobs() {
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [
+                {
+                    "content": {"role": "model", "parts": [{"text": "Hi"}]},
+                    "finishReason": "STOP",
+                    "avgLogprobs": -0.25
+                },
+                {"content": {"role": "model", "parts": [{"text": "Hello"}]}}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(avg_logprobs(&resp), vec![Some(-0.25), None]);
+        let empty: VertexResponse = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(avg_logprobs(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_inline_data() {
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Here it is"},
+                    {"inlineData": {"mimeType": "image/png", "data": "iVBORw0K"}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let blobs = inline_data(&resp);
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0][0].to_data_uri(), "data:image/png;base64,iVBORw0K");
+
+        let converted = convert_vertex_response(&resp, "gemini", "id").unwrap();
+        assert_eq!(
+            converted.choices[0].message.content.as_deref(),
+            Some("Here it is")
+        );
+    }
+
+    #[test]
+    fn test_logprobs() {
+        let req = CreateChatCompletionRequest {
+            model: "gemini-pro".to_string(),
+            messages: vec![],
+            logprobs: Some(true),
+            top_logprobs: Some(2),
+            ..Default::default()
+        };
+        let config = convert_request(&req).unwrap().generation_config.unwrap();
+        assert_eq!(config.response_logprobs, Some(true));
+        assert_eq!(config.logprobs, Some(2));
+
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Hi"}]},
+                "finishReason": "STOP",
+                "logprobsResult": {
+                    "topCandidates": [{"candidates": [
+                        {"token": "Hi", "logProbability": -0.1},
+                        {"token": "Hello", "logProbability": -2.5}
+                    ]}],
+                    "chosenCandidates": [{"token": "Hi", "logProbability": -0.1}]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let result = convert_vertex_response(&resp, "gemini-pro", "id").unwrap();
+        let content = result.choices[0]
+            .logprobs
+            .as_ref()
+            .unwrap()
+            .content
+            .as_ref()
+            .unwrap();
+        assert_eq!(content.len(), 1);
+        assert_eq!(content[0].token, "Hi");
+        assert_eq!(content[0].logprob, -0.1);
+        assert_eq!(content[0].bytes.as_deref(), Some(&b"Hi"[..]));
+        assert_eq!(content[0].top_logprobs.len(), 2);
+        assert_eq!(content[0].top_logprobs[1].token, "Hello");
+    }
+
+    #[test]
+    fn test_function_call_finishes_with_tool_calls() {
+        let resp: VertexResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"functionCall": {"name": "get_weather", "args": {"city": "Tokyo"}}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let result = convert_vertex_response(&resp, "gemini-pro", "id").unwrap();
+        assert_eq!(
+            result.choices[0].finish_reason,
+            Some(FinishReason::ToolCalls)
+        );
+
+        let chunk = convert_vertex_stream_chunk(&resp, "gemini-pro", "id").unwrap();
+        let choice = &chunk.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        let call = &choice.delta.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.index, 0);
+        let function = call.function.as_ref().unwrap();
+        assert_eq!(function.name.as_deref(), Some("get_weather"));
+        assert_eq!(function.arguments.as_deref(), Some(r#"{"city":"Tokyo"}"#));
+    }
+
+    #[test]
+    fn test_json_path_entry_rejects_far_index() {
+        let mut args = serde_json::json!({"items": ["a"]});
+        assert!(json_path_entry(&mut args, "$.items[1]").is_some());
+        assert!(json_path_entry(&mut args, "$.items[100000000000]").is_none());
+        assert!(json_path_entry(&mut args, "$.items[18446744073709551615]").is_none());
+        assert_eq!(args, serde_json::json!({"items": ["a", null]}));
+        assert_eq!(json_number(3.0), serde_json::json!(3));
+        assert_eq!(json_number(2.5), serde_json::json!(2.5));
+    }
+
+    #[test]
+    fn test_convert_finish_reason() {
+        let cases = [
+            ("STOP", FinishReason::Stop),
+            ("MAX_TOKENS", FinishReason::Length),
+            ("SAFETY", FinishReason::ContentFilter),
+            ("RECITATION", FinishReason::ContentFilter),
+            ("BLOCKLIST", FinishReason::ContentFilter),
+            ("PROHIBITED_CONTENT", FinishReason::ContentFilter),
+            ("SPII", FinishReason::ContentFilter),
+            ("IMAGE_SAFETY", FinishReason::ContentFilter),
+            ("FINISH_REASON_UNSPECIFIED", FinishReason::Stop),
+            ("OTHER", FinishReason::Stop),
+            ("LANGUAGE", FinishReason::Stop),
+            ("MALFORMED_FUNCTION_CALL", FinishReason::Stop),
+            ("SOMETHING_NEW", FinishReason::Stop),
+        ];
+        for (reason, expected) in cases {
+            assert_eq!(convert_finish_reason(reason), expected, "{reason}");
+        }
     }
 
     #[test]
-    fn test_parse_sse_events() {
-        let data = b"data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Hi\"}]},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":1,\"totalTokenCount\":2}}\n\n";
-        let (responses, remaining) = parse_sse_events(data);
-        assert_eq!(responses.len(), 1);
-        assert!(remaining.is_empty());
+    fn test_parse_sse_event() {
+        let data = "{\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Hi\"}]},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":1,\"totalTokenCount\":2}}";
+        let resp = parse_sse_event(data).unwrap();
+        assert_eq!(resp.candidates.unwrap().len(), 1);
+        assert!(parse_sse_event("not json").is_none());
     }
 }
@@ -1,22 +1,193 @@
+use std::sync::Arc;
+#[cfg(feature = "backend-vertex")]
+use std::time::Duration;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CompositeLlmError {
     #[error("OpenAI error: {0}")]
     #[cfg(any(feature = "backend-openai", feature = "backend-azure"))]
     OpenAI(#[from] async_openai::error::OpenAIError),
 
-    #[error("Bedrock error: {0}")]
+    #[error("Bedrock error: {message}")]
     #[cfg(feature = "backend-bedrock")]
-    Bedrock(String),
+    Bedrock {
+        message: String,
+        /// What kind of failure Bedrock reported.
+        kind: BedrockErrorKind,
+        /// Whether the same request may succeed if retried later.
+        retryable: bool,
+        /// The underlying SDK or decoding error, if any.
+        #[source]
+        source: Option<BoxError>,
+    },
 
-    #[error("Vertex AI error: {0}")]
+    #[error("Vertex AI error: {message}")]
     #[cfg(feature = "backend-vertex")]
-    Vertex(String),
+    Vertex {
+        message: String,
+        /// How long the server asked to wait before retrying, from the
+        /// `Retry-After` header of a rate-limited or unavailable response.
+        retry_after: Option<Duration>,
+        /// The underlying HTTP, authentication or decoding error, if any.
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// An [`AzureTokenProvider`](crate::AzureTokenProvider) failed to supply
+    /// an Entra ID token.
+    #[error("Failed to obtain an Entra ID token: {0}")]
+    #[cfg(feature = "backend-azure")]
+    TokenProvider(#[source] BoxError),
 
     #[error("Serialization error: {0}")]
     Serde(#[from] serde_json::Error),
 
     #[error("Unsupported: {0}")]
     Unsupported(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Stream interrupted: {source}")]
+    StreamInterrupted {
+        /// Content of the first choice received before the error.
+        partial: String,
+        #[source]
+        source: Box<CompositeLlmError>,
+    },
+
+    #[error("Circuit breaker is open")]
+    CircuitOpen,
+
+    #[error("Rate limit exceeded")]
+    RateLimited,
+
+    #[error("All backends failed: {}", join_errors(.0))]
+    AllBackendsFailed(Vec<CompositeLlmError>),
+
+    /// A copy of an error handed to another consumer, such as the future
+    /// returned by [`tee`](crate::stream::tee). The copy keeps the variant
+    /// and its fields, but sources are kept only as their messages.
+    #[error(transparent)]
+    Shared(Arc<CompositeLlmError>),
+
+    /// An error that fits no other variant, such as one raised by a
+    /// user-defined [`ChatCompletionBackend`](crate::ChatCompletionBackend).
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Classification of a [`CompositeLlmError::Bedrock`] error.
+#[cfg(feature = "backend-bedrock")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BedrockErrorKind {
+    /// The model ID or ARN does not exist, or is not offered in the region.
+    ResourceNotFound,
+    /// The caller lacks IAM permission or has not been granted model access.
+    AccessDenied,
+    /// The request was rejected as invalid.
+    Validation,
+    /// The request was throttled.
+    Throttling,
+    /// Bedrock or the model is temporarily unavailable (internal server
+    /// error, service unavailable, model not ready or model timeout).
+    Unavailable,
+    /// Any other failure, including connection errors and errors raised
+    /// while converting requests or responses.
+    Other,
+}
+
+#[cfg(feature = "backend-bedrock")]
+impl BedrockErrorKind {
+    /// Whether errors of this kind are transient.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Throttling | Self::Unavailable)
+    }
+}
+
+/// A boxed error usable as the source of a [`CompositeLlmError`].
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl CompositeLlmError {
+    /// A Bedrock error with a short message; `source` carries the detail.
+    #[cfg(feature = "backend-bedrock")]
+    pub(crate) fn bedrock(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Bedrock {
+            message: "failed to build the Bedrock request".to_string(),
+            kind: BedrockErrorKind::Other,
+            retryable: false,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// A Vertex AI error with a short message; `source` carries the detail.
+    #[cfg(feature = "backend-vertex")]
+    pub(crate) fn vertex(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Vertex {
+            message: "Vertex AI request failed".to_string(),
+            retry_after: None,
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+impl CompositeLlmError {
+    /// A copy of this error for another consumer. Variants that cannot be
+    /// cloned, and the sources of the others, are kept as messages.
+    pub(crate) fn detached_copy(&self) -> Self {
+        match self {
+            #[cfg(feature = "backend-bedrock")]
+            Self::Bedrock {
+                message,
+                kind,
+                retryable,
+                source,
+            } => Self::Bedrock {
+                message: message.clone(),
+                kind: *kind,
+                retryable: *retryable,
+                source: source.as_ref().map(|e| e.to_string().into()),
+            },
+            #[cfg(feature = "backend-vertex")]
+            Self::Vertex {
+                message,
+                retry_after,
+                source,
+            } => Self::Vertex {
+                message: message.clone(),
+                retry_after: *retry_after,
+                source: source.as_ref().map(|e| e.to_string().into()),
+            },
+            Self::Unsupported(message) => Self::Unsupported(message.clone()),
+            Self::Io(e) => Self::Io(std::io::Error::new(e.kind(), e.to_string())),
+            Self::Config(message) => Self::Config(message.clone()),
+            Self::StreamInterrupted { partial, source } => Self::StreamInterrupted {
+                partial: partial.clone(),
+                source: Box::new(source.detached_copy()),
+            },
+            Self::CircuitOpen => Self::CircuitOpen,
+            Self::RateLimited => Self::RateLimited,
+            Self::AllBackendsFailed(errors) => {
+                Self::AllBackendsFailed(errors.iter().map(Self::detached_copy).collect())
+            }
+            Self::Shared(e) => Self::Shared(e.clone()),
+            Self::Other(message) => Self::Other(message.clone()),
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+fn join_errors(errors: &[CompositeLlmError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
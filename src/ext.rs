@@ -0,0 +1,187 @@
+//! Convenience extension traits for the OpenAI response types.
+
+use async_openai::types::chat::{
+    CompletionTokensDetails, CompletionUsage, CreateChatCompletionResponse, PromptTokensDetails,
+};
+
+/// Shortcuts for reading a [`CreateChatCompletionResponse`].
+pub trait ChatCompletionResponseExt {
+    /// Returns the assistant text of every choice, in order.
+    ///
+    /// Choices without text content, such as tool-call-only choices, are
+    /// skipped.
+    fn texts(&self) -> Vec<&str>;
+}
+
+impl ChatCompletionResponseExt for CreateChatCompletionResponse {
+    fn texts(&self) -> Vec<&str> {
+        self.choices
+            .iter()
+            .filter_map(|c| c.message.content.as_deref())
+            .collect()
+    }
+}
+
+/// Arithmetic on [`CompletionUsage`], e.g. to total the usage of retries or
+/// of the turns of an agent loop.
+///
+/// Counts saturate instead of overflowing. A `*_details` field is kept when
+/// either side has it, and each of its counters likewise: a counter only one
+/// side reports is taken as is, one neither reports stays `None`.
+pub trait UsageExt {
+    /// Adds `other` to `self`.
+    fn add_usage(&mut self, other: &CompletionUsage);
+
+    /// Returns the sum of `self` and `other`.
+    fn plus(&self, other: &CompletionUsage) -> CompletionUsage;
+}
+
+impl UsageExt for CompletionUsage {
+    fn add_usage(&mut self, other: &CompletionUsage) {
+        self.prompt_tokens = self.prompt_tokens.saturating_add(other.prompt_tokens);
+        self.completion_tokens = self
+            .completion_tokens
+            .saturating_add(other.completion_tokens);
+        self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+        self.prompt_tokens_details = add_details(
+            self.prompt_tokens_details.take(),
+            other.prompt_tokens_details.as_ref(),
+            |sum, other| PromptTokensDetails {
+                audio_tokens: add_counts(sum.audio_tokens, other.audio_tokens),
+                cached_tokens: add_counts(sum.cached_tokens, other.cached_tokens),
+            },
+        );
+        self.completion_tokens_details = add_details(
+            self.completion_tokens_details.take(),
+            other.completion_tokens_details.as_ref(),
+            |sum, other| CompletionTokensDetails {
+                accepted_prediction_tokens: add_counts(
+                    sum.accepted_prediction_tokens,
+                    other.accepted_prediction_tokens,
+                ),
+                audio_tokens: add_counts(sum.audio_tokens, other.audio_tokens),
+                reasoning_tokens: add_counts(sum.reasoning_tokens, other.reasoning_tokens),
+                rejected_prediction_tokens: add_counts(
+                    sum.rejected_prediction_tokens,
+                    other.rejected_prediction_tokens,
+                ),
+            },
+        );
+    }
+
+    fn plus(&self, other: &CompletionUsage) -> CompletionUsage {
+        let mut sum = self.clone();
+        sum.add_usage(other);
+        sum
+    }
+}
+
+/// Sums usages; an empty iterator gives all zeros and no details.
+pub fn sum_usage<'a>(usages: impl IntoIterator<Item = &'a CompletionUsage>) -> CompletionUsage {
+    usages
+        .into_iter()
+        .fold(CompletionUsage::default(), |mut sum, usage| {
+            sum.add_usage(usage);
+            sum
+        })
+}
+
+fn add_details<T: Clone>(sum: Option<T>, other: Option<&T>, add: impl Fn(T, &T) -> T) -> Option<T> {
+    match (sum, other) {
+        (Some(sum), Some(other)) => Some(add(sum, other)),
+        (sum, other) => sum.or_else(|| other.cloned()),
+    }
+}
+
+fn add_counts(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.saturating_add(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt: u32, completion: u32) -> CompletionUsage {
+        CompletionUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sum_usage_without_details() {
+        let sum = sum_usage(&[usage(10, 5), usage(20, 7)]);
+        assert_eq!(sum, usage(30, 12));
+        assert_eq!(sum_usage([]), CompletionUsage::default());
+        assert_eq!(
+            usage(1, 2).plus(&usage(u32::MAX, 0)).prompt_tokens,
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_sum_usage_with_details() {
+        let first = CompletionUsage {
+            prompt_tokens_details: Some(PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(8),
+            }),
+            ..usage(10, 5)
+        };
+        let second = CompletionUsage {
+            prompt_tokens_details: Some(PromptTokensDetails {
+                audio_tokens: Some(3),
+                cached_tokens: Some(4),
+            }),
+            completion_tokens_details: Some(CompletionTokensDetails {
+                reasoning_tokens: Some(2),
+                ..Default::default()
+            }),
+            ..usage(20, 7)
+        };
+
+        let sum = sum_usage([&first, &usage(1, 1), &second]);
+        assert_eq!(sum.total_tokens, 44);
+        assert_eq!(
+            sum.prompt_tokens_details,
+            Some(PromptTokensDetails {
+                audio_tokens: Some(3),
+                cached_tokens: Some(12),
+            })
+        );
+        assert_eq!(
+            sum.completion_tokens_details,
+            Some(CompletionTokensDetails {
+                reasoning_tokens: Some(2),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_texts_skips_tool_only_choices() {
+        let resp: CreateChatCompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-test",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "test",
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"role": "assistant", "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "f", "arguments": "{}"}
+                }]}, "finish_reason": "tool_calls"},
+                {"index": 2, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(resp.texts(), vec!["Hello", "Hi"]);
+    }
+}
@@ -1,22 +1,56 @@
+#[cfg(not(any(
+    feature = "backend-openai",
+    feature = "backend-azure",
+    feature = "backend-bedrock",
+    feature = "backend-vertex",
+)))]
+compile_error!(
+    "composite-llm requires at least one backend feature: \
+     `backend-openai`, `backend-azure`, `backend-bedrock` or `backend-vertex`"
+);
+
 pub mod backend;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod convert;
 pub mod error;
+pub mod ext;
+pub mod options;
+pub mod request;
+pub mod router;
+pub mod stream;
 
 pub use async_openai::types::chat::{
     CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
 };
 pub use backend::ChatCompletionBackend;
 pub use backend::ChatCompletionStream;
-pub use error::CompositeLlmError;
+pub use backend::circuit_breaker::CircuitBreakerBackend;
+#[cfg(feature = "metrics")]
+pub use backend::metered::MeteredBackend;
+pub use backend::race::RaceBackend;
+pub use backend::rate_limit::RateLimitedBackend;
+pub use backend::transform::TransformBackend;
+pub use error::{BoxError, CompositeLlmError};
+pub use ext::{ChatCompletionResponseExt, UsageExt, sum_usage};
+pub use options::{
+    BedrockCachePoint, BedrockManagedPrompt, BedrockOptions, Credentials, LatencyMode,
+    ProviderOptions, VertexOptions, VertexSearchTool,
+};
+pub use request::ChatRequest;
+pub use router::ModelRouter;
+pub use stream::CompletionStream;
 
 #[cfg(feature = "backend-azure")]
-pub use backend::azure::AzureBackend;
+pub use backend::azure::{AzureBackend, AzureTokenProvider};
 #[cfg(feature = "backend-bedrock")]
-pub use backend::bedrock::BedrockBackend;
+pub use backend::bedrock::{BedrockBackend, BedrockResponse, ConverseEventStream};
 #[cfg(feature = "backend-openai")]
 pub use backend::openai::OpenAIBackend;
 #[cfg(feature = "backend-vertex")]
-pub use backend::vertex::VertexBackend;
+pub use backend::vertex::{VertexBackend, VertexChatResponse};
+#[cfg(feature = "backend-bedrock")]
+pub use error::BedrockErrorKind;
 
 /// A unified client for multiple LLM backends.
 ///
@@ -24,6 +58,7 @@ pub use backend::vertex::VertexBackend;
 /// and delegates method calls to the active backend.
 ///
 /// Use feature flags to enable specific backends.
+#[derive(Clone)]
 pub enum CompositeClient {
     #[cfg(feature = "backend-openai")]
     /// The OpenAI backend.
@@ -35,8 +70,59 @@ pub enum CompositeClient {
     /// The Amazon Bedrock backend.
     Bedrock(BedrockBackend),
     #[cfg(feature = "backend-vertex")]
-    /// The Google Vertex AI backend.
-    Vertex(VertexBackend),
+    /// The Google Vertex AI backend, boxed since it is much larger than the
+    /// other variants.
+    Vertex(Box<VertexBackend>),
+}
+
+/// Identifies one of the supported backend providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackendKind {
+    /// OpenAI.
+    OpenAI,
+    /// Azure OpenAI.
+    Azure,
+    /// Amazon Bedrock.
+    Bedrock,
+    /// Google Vertex AI.
+    Vertex,
+}
+
+impl BackendKind {
+    /// The lowercase name accepted by [`FromStr`](std::str::FromStr), e.g.
+    /// `"bedrock"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::OpenAI => "openai",
+            Self::Azure => "azure",
+            Self::Bedrock => "bedrock",
+            Self::Vertex => "vertex",
+        }
+    }
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = CompositeLlmError;
+
+    /// Parses a backend name case-insensitively: `openai`, `azure` (or
+    /// `azure-openai`), `bedrock` and `vertex` (or `vertex-ai`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAI),
+            "azure" | "azure-openai" => Ok(Self::Azure),
+            "bedrock" => Ok(Self::Bedrock),
+            "vertex" | "vertex-ai" => Ok(Self::Vertex),
+            _ => Err(CompositeLlmError::Config(format!(
+                "unknown backend `{s}`; expected openai, azure, bedrock or vertex"
+            ))),
+        }
+    }
 }
 
 macro_rules! dispatch {
@@ -50,18 +136,158 @@ macro_rules! dispatch {
             CompositeClient::Bedrock(b) => b.$method($($arg),*).await,
             #[cfg(feature = "backend-vertex")]
             CompositeClient::Vertex(b) => b.$method($($arg),*).await,
+            // Only reachable in a build already failed by `compile_error!`;
+            // keeps that error from being buried under follow-on errors.
             #[cfg(not(any(
                 feature = "backend-openai",
                 feature = "backend-azure",
                 feature = "backend-bedrock",
                 feature = "backend-vertex",
             )))]
-            _ => unreachable!("no backend feature enabled"),
+            _ => unreachable!(),
         }
     };
 }
 
 impl CompositeClient {
+    /// Creates an OpenAI client; see [`OpenAIBackend::new`].
+    #[cfg(feature = "backend-openai")]
+    pub fn openai(config: async_openai::config::OpenAIConfig) -> Self {
+        Self::OpenAI(OpenAIBackend::new(config))
+    }
+
+    /// Creates an OpenAI client from environment variables; see
+    /// [`OpenAIBackend::from_env`].
+    #[cfg(feature = "backend-openai")]
+    pub fn openai_from_env() -> Self {
+        Self::OpenAI(OpenAIBackend::from_env())
+    }
+
+    /// Creates an Azure OpenAI client; see [`AzureBackend::new`].
+    #[cfg(feature = "backend-azure")]
+    pub fn azure(config: async_openai::config::AzureConfig) -> Self {
+        Self::Azure(AzureBackend::new(config))
+    }
+
+    /// Creates a Bedrock client; see [`BedrockBackend::new`].
+    #[cfg(feature = "backend-bedrock")]
+    pub fn bedrock(client: aws_sdk_bedrockruntime::Client, model_id: impl Into<String>) -> Self {
+        Self::Bedrock(BedrockBackend::new(client, model_id))
+    }
+
+    /// Creates a Bedrock client from the default AWS configuration chain; see
+    /// [`BedrockBackend::from_env`].
+    #[cfg(feature = "backend-bedrock")]
+    pub async fn bedrock_from_env(model_id: impl Into<String>) -> Self {
+        Self::Bedrock(BedrockBackend::from_env(model_id).await)
+    }
+
+    /// Creates a Vertex AI client; see [`VertexBackend::new`].
+    #[cfg(feature = "backend-vertex")]
+    pub async fn vertex(
+        project_id: impl Into<String>,
+        location: impl Into<String>,
+        model_id: impl Into<String>,
+    ) -> Result<Self, CompositeLlmError> {
+        Ok(Self::Vertex(Box::new(
+            VertexBackend::new(project_id, location, model_id).await?,
+        )))
+    }
+
+    /// Builds a client for `kind` from conventional environment variables.
+    ///
+    /// - OpenAI: `OPENAI_API_KEY`.
+    /// - Azure: `AZURE_OPENAI_ENDPOINT`, `AZURE_OPENAI_API_KEY`,
+    ///   `AZURE_OPENAI_DEPLOYMENT_ID` and optionally `AZURE_OPENAI_API_VERSION`
+    ///   (defaults to `2024-02-01`).
+    /// - Bedrock: the default AWS configuration chain; the model ID is `model`
+    ///   or `BEDROCK_MODEL_ID`.
+    /// - Vertex: `GCP_PROJECT_ID`, optionally `GCP_LOCATION` (defaults to
+    ///   `us-central1`); the model ID is `model` or `VERTEX_MODEL_ID`.
+    ///
+    /// OpenAI and Azure take the model from each request, so `model` is
+    /// ignored for them. Missing variables are all listed in the returned
+    /// [`CompositeLlmError::Config`].
+    pub async fn from_env_for(
+        kind: BackendKind,
+        model: Option<&str>,
+    ) -> Result<Self, CompositeLlmError> {
+        match kind {
+            BackendKind::OpenAI => Self::openai_env(),
+            BackendKind::Azure => Self::azure_env(),
+            BackendKind::Bedrock => Self::bedrock_env(model).await,
+            BackendKind::Vertex => Self::vertex_env(model).await,
+        }
+    }
+
+    #[cfg(feature = "backend-openai")]
+    fn openai_env() -> Result<Self, CompositeLlmError> {
+        require_env(&["OPENAI_API_KEY"])?;
+        Ok(Self::OpenAI(OpenAIBackend::from_env()))
+    }
+
+    #[cfg(not(feature = "backend-openai"))]
+    fn openai_env() -> Result<Self, CompositeLlmError> {
+        Err(feature_disabled("backend-openai"))
+    }
+
+    #[cfg(feature = "backend-azure")]
+    fn azure_env() -> Result<Self, CompositeLlmError> {
+        let vars = require_env(&[
+            "AZURE_OPENAI_ENDPOINT",
+            "AZURE_OPENAI_API_KEY",
+            "AZURE_OPENAI_DEPLOYMENT_ID",
+        ])?;
+        let api_version =
+            std::env::var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|_| "2024-02-01".to_string());
+        let config = async_openai::config::AzureConfig::new()
+            .with_api_base(&vars[0])
+            .with_api_key(&vars[1])
+            .with_deployment_id(&vars[2])
+            .with_api_version(api_version);
+        Ok(Self::Azure(AzureBackend::new(config)))
+    }
+
+    #[cfg(not(feature = "backend-azure"))]
+    fn azure_env() -> Result<Self, CompositeLlmError> {
+        Err(feature_disabled("backend-azure"))
+    }
+
+    #[cfg(feature = "backend-bedrock")]
+    async fn bedrock_env(model: Option<&str>) -> Result<Self, CompositeLlmError> {
+        let model_id = match model {
+            Some(model) => model.to_string(),
+            None => require_env(&["BEDROCK_MODEL_ID"])?.remove(0),
+        };
+        Ok(Self::Bedrock(BedrockBackend::from_env(model_id).await))
+    }
+
+    #[cfg(not(feature = "backend-bedrock"))]
+    async fn bedrock_env(_model: Option<&str>) -> Result<Self, CompositeLlmError> {
+        Err(feature_disabled("backend-bedrock"))
+    }
+
+    #[cfg(feature = "backend-vertex")]
+    async fn vertex_env(model: Option<&str>) -> Result<Self, CompositeLlmError> {
+        let mut names = vec!["GCP_PROJECT_ID"];
+        if model.is_none() {
+            names.push("VERTEX_MODEL_ID");
+        }
+        let mut vars = require_env(&names)?;
+        let model_id = match model {
+            Some(model) => model.to_string(),
+            None => vars.remove(1),
+        };
+        let location = std::env::var("GCP_LOCATION").unwrap_or_else(|_| "us-central1".to_string());
+        let backend = VertexBackend::new(vars.remove(0), location, model_id).await?;
+        Ok(Self::Vertex(Box::new(backend)))
+    }
+
+    #[cfg(not(feature = "backend-vertex"))]
+    async fn vertex_env(_model: Option<&str>) -> Result<Self, CompositeLlmError> {
+        Err(feature_disabled("backend-vertex"))
+    }
+
     /// Sends a chat completion request to the configured backend.
     ///
     /// # Arguments
@@ -93,4 +319,187 @@ impl CompositeClient {
     ) -> Result<ChatCompletionStream, CompositeLlmError> {
         dispatch!(self, chat_completion_stream, req)
     }
+
+    /// Sends a streaming chat completion request and also returns a future
+    /// resolving to the reassembled response once the stream has been
+    /// consumed; see [`stream::tee`].
+    pub async fn chat_completion_stream_tee(
+        &self,
+        req: CreateChatCompletionRequest,
+    ) -> Result<
+        (
+            ChatCompletionStream,
+            impl std::future::Future<Output = Result<CreateChatCompletionResponse, CompositeLlmError>>
+            + Send
+            + 'static,
+        ),
+        CompositeLlmError,
+    > {
+        Ok(stream::tee(self.chat_completion_stream(req).await?))
+    }
+
+    /// Sends a chat completion request with provider-specific options.
+    ///
+    /// Only the section of `opts` matching the configured backend is used.
+    pub async fn chat_completion_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+        dispatch!(self, chat_completion_with, req, opts)
+    }
+
+    /// Sends a streaming chat completion request that ends as soon as `token`
+    /// is cancelled; see [`ChatCompletionBackend::chat_completion_stream_cancellable`].
+    pub async fn chat_completion_stream_cancellable(
+        &self,
+        req: CreateChatCompletionRequest,
+        token: tokio_util::sync::CancellationToken,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        dispatch!(self, chat_completion_stream_cancellable, req, token)
+    }
+
+    /// Sends a streaming chat completion request with provider-specific options.
+    ///
+    /// Only the section of `opts` matching the configured backend is used.
+    pub async fn chat_completion_stream_with(
+        &self,
+        req: CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<ChatCompletionStream, CompositeLlmError> {
+        dispatch!(self, chat_completion_stream_with, req, opts)
+    }
+
+    /// Checks that the configured backend is reachable and its credentials
+    /// are valid; see [`ChatCompletionBackend::health_check`].
+    pub async fn health_check(&self) -> Result<(), CompositeLlmError> {
+        dispatch!(self, health_check,)
+    }
+
+    /// Lists the models the configured backend offers; see
+    /// [`ChatCompletionBackend::list_models`].
+    pub async fn list_models(&self) -> Result<Vec<String>, CompositeLlmError> {
+        dispatch!(self, list_models,)
+    }
+
+    /// Returns the provider-bound request `req` would be sent as, without
+    /// making a network call.
+    ///
+    /// OpenAI and Azure return the chat completion body, Bedrock the Converse
+    /// request and Vertex AI the `generateContent` body, all as JSON. Useful
+    /// for debugging conversions and generating fixtures.
+    pub fn preview_request(
+        &self,
+        req: &CreateChatCompletionRequest,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        self.preview_request_with(req, &ProviderOptions::default())
+    }
+
+    /// Like [`preview_request`](Self::preview_request), with provider-specific options.
+    pub fn preview_request_with(
+        &self,
+        req: &CreateChatCompletionRequest,
+        opts: &ProviderOptions,
+    ) -> Result<serde_json::Value, CompositeLlmError> {
+        let _ = opts;
+        match self {
+            #[cfg(feature = "backend-openai")]
+            CompositeClient::OpenAI(b) => b.build_request(req),
+            #[cfg(feature = "backend-azure")]
+            CompositeClient::Azure(b) => b.build_request(req),
+            #[cfg(feature = "backend-bedrock")]
+            CompositeClient::Bedrock(b) => b.build_request_with(req, opts),
+            #[cfg(feature = "backend-vertex")]
+            CompositeClient::Vertex(b) => b.build_request_with(req, opts),
+            #[cfg(not(any(
+                feature = "backend-openai",
+                feature = "backend-azure",
+                feature = "backend-bedrock",
+                feature = "backend-vertex",
+            )))]
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Reads the given environment variables, reporting every missing one at once.
+fn require_env(names: &[&str]) -> Result<Vec<String>, CompositeLlmError> {
+    let mut values = Vec::new();
+    let mut missing = Vec::new();
+    for name in names {
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() => values.push(value),
+            _ => missing.push(*name),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(values)
+    } else {
+        Err(CompositeLlmError::Config(format!(
+            "missing environment variable(s): {}",
+            missing.join(", ")
+        )))
+    }
+}
+
+#[cfg(not(all(
+    feature = "backend-openai",
+    feature = "backend-azure",
+    feature = "backend-bedrock",
+    feature = "backend-vertex",
+)))]
+fn feature_disabled(feature: &str) -> CompositeLlmError {
+    CompositeLlmError::Unsupported(format!("the `{feature}` feature is not enabled"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_kind_from_str() {
+        assert_eq!(
+            "openai".parse::<BackendKind>().unwrap(),
+            BackendKind::OpenAI
+        );
+        assert_eq!(
+            "Azure-OpenAI".parse::<BackendKind>().unwrap(),
+            BackendKind::Azure
+        );
+        assert_eq!(
+            "BEDROCK".parse::<BackendKind>().unwrap(),
+            BackendKind::Bedrock
+        );
+        assert_eq!(
+            "vertex-ai".parse::<BackendKind>().unwrap(),
+            BackendKind::Vertex
+        );
+        for kind in [
+            BackendKind::OpenAI,
+            BackendKind::Azure,
+            BackendKind::Bedrock,
+            BackendKind::Vertex,
+        ] {
+            assert_eq!(kind.to_string().parse::<BackendKind>().unwrap(), kind);
+        }
+        assert!(matches!(
+            "cohere".parse::<BackendKind>(),
+            Err(CompositeLlmError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_require_env_lists_all_missing() {
+        let err = require_env(&[
+            "COMPOSITE_LLM_TEST_MISSING_A",
+            "COMPOSITE_LLM_TEST_MISSING_B",
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Configuration error: missing environment variable(s): \
+             COMPOSITE_LLM_TEST_MISSING_A, COMPOSITE_LLM_TEST_MISSING_B"
+        );
+    }
 }
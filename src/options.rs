@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Provider-specific options carried alongside a `CreateChatCompletionRequest`.
+///
+/// The OpenAI request type has no room for provider-only knobs, so they are
+/// passed separately via `chat_completion_with`/`chat_completion_stream_with`.
+/// Each backend only reads its own section and ignores the rest, so the same
+/// options value can be reused across backends.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderOptions {
+    /// Options applied by the Vertex AI backend.
+    pub vertex: Option<VertexOptions>,
+    /// Options applied by the Amazon Bedrock backend.
+    pub bedrock: Option<BedrockOptions>,
+    /// Credentials used for this request instead of the backend's own.
+    pub credentials: Option<Credentials>,
+    /// An id tying this request to the caller's traces. Every backend records
+    /// it as `correlation_id` on its `chat_completion` tracing span, and the
+    /// Vertex AI backend also sends it as the `X-Correlation-ID` header.
+    pub correlation_id: Option<String>,
+}
+
+impl ProviderOptions {
+    /// Sets the Vertex AI options.
+    pub fn with_vertex(mut self, vertex: VertexOptions) -> Self {
+        self.vertex = Some(vertex);
+        self
+    }
+
+    /// Sets the Amazon Bedrock options.
+    pub fn with_bedrock(mut self, bedrock: BedrockOptions) -> Self {
+        self.bedrock = Some(bedrock);
+        self
+    }
+
+    /// Sets per-request credentials.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Sets the correlation id.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+}
+
+/// Per-request credentials overriding those the backend was built with.
+///
+/// Useful for multi-tenant servers where each tenant has its own key. A
+/// backend returns [`CompositeLlmError::Unsupported`](crate::CompositeLlmError::Unsupported)
+/// when given a kind of credentials it cannot use.
+#[derive(Clone)]
+pub enum Credentials {
+    /// An API key, used by the OpenAI and Azure OpenAI backends.
+    ApiKey(String),
+    /// An OAuth2 access token, used by the Vertex AI backend and as an Entra ID
+    /// token by the Azure OpenAI backend.
+    BearerToken(String),
+    /// Static AWS credentials, used by the Amazon Bedrock backend.
+    Aws {
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    },
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print secrets.
+        match self {
+            Credentials::ApiKey(_) => f.write_str("ApiKey(**redacted**)"),
+            Credentials::BearerToken(_) => f.write_str("BearerToken(**redacted**)"),
+            Credentials::Aws { access_key_id, .. } => f
+                .debug_struct("Aws")
+                .field("access_key_id", access_key_id)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Vertex AI (Gemini) specific options.
+#[derive(Debug, Clone, Default)]
+pub struct VertexOptions {
+    /// Safety settings sent as `safetySettings`.
+    pub safety_settings: Option<Vec<SafetySetting>>,
+    /// Top-k sampling, sent as `generationConfig.topK`.
+    pub top_k: Option<u32>,
+    /// Thinking budget in tokens, sent as `generationConfig.thinkingConfig.thinkingBudget`.
+    pub thinking_budget: Option<i32>,
+    /// Built-in Google Search grounding, added to `tools` next to any function
+    /// declarations. Sources are returned as message annotations.
+    pub search: Option<VertexSearchTool>,
+    /// Joins the system and developer messages into a single
+    /// `systemInstruction` part, separated by this string (e.g. `"\n"`). By
+    /// default each message is kept as its own part.
+    pub system_separator: Option<String>,
+    /// Output modalities sent as `generationConfig.responseModalities`, e.g.
+    /// `["TEXT", "IMAGE"]` for Gemini image generation models. Overrides the
+    /// request's `modalities`; generated images are returned as inline data
+    /// (see `VertexChatResponse::inline_data`).
+    pub response_modalities: Option<Vec<String>>,
+}
+
+/// The built-in Google Search tool to enable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexSearchTool {
+    /// `googleSearch`, for Gemini 2.0 and later.
+    GoogleSearch,
+    /// `googleSearchRetrieval`, for Gemini 1.5. With a threshold, search only
+    /// runs when the predicted benefit exceeds it (dynamic retrieval).
+    GoogleSearchRetrieval { dynamic_threshold: Option<f32> },
+}
+
+/// A Gemini safety setting, e.g. `HARM_CATEGORY_HATE_SPEECH` / `BLOCK_ONLY_HIGH`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+/// Amazon Bedrock specific options.
+#[derive(Debug, Clone, Default)]
+pub struct BedrockOptions {
+    /// Guardrail applied to the Converse call.
+    pub guardrail: Option<BedrockGuardrail>,
+    /// Raw `additionalModelRequestFields`, merged with `top_k`/`thinking_budget`.
+    pub additional_model_request_fields: Option<serde_json::Value>,
+    /// Top-k sampling, sent as `additionalModelRequestFields.top_k`.
+    pub top_k: Option<u32>,
+    /// Extended thinking budget in tokens (Anthropic models), sent as
+    /// `additionalModelRequestFields.thinking`.
+    pub thinking_budget: Option<u32>,
+    /// Prompt cache checkpoints inserted into the converted conversation.
+    pub cache_points: Vec<BedrockCachePoint>,
+    /// A Bedrock-managed prompt invoked instead of the backend's model.
+    pub managed_prompt: Option<BedrockManagedPrompt>,
+}
+
+/// A prompt stored in Bedrock Prompt management.
+///
+/// The prompt ARN is sent as the model ID together with `promptVariables`;
+/// the request's messages, if any, are appended to the prompt's. The system
+/// prompt, inference parameters and tools must be defined in the prompt, so
+/// requests setting them are rejected. The backend's default stop sequences
+/// and the request's cache points are not applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedrockManagedPrompt {
+    /// The prompt ARN, optionally including a version.
+    pub arn: String,
+    /// Values for the prompt's `{{variable}}` placeholders.
+    pub variables: HashMap<String, String>,
+}
+
+impl BedrockManagedPrompt {
+    /// Creates a reference to the prompt `arn` with no variables.
+    pub fn new(arn: impl Into<String>) -> Self {
+        Self {
+            arn: arn.into(),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Sets the value of a prompt variable.
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Where to insert a Bedrock prompt caching `cachePoint` block.
+///
+/// Everything before a checkpoint is cached and reused by later requests with
+/// the same prefix. Prompt caching is supported by Anthropic Claude (3.5 Haiku,
+/// 3.7 Sonnet and later) and Amazon Nova models, and each checkpoint must
+/// follow a model-specific minimum number of tokens (e.g. 1,024 for Claude
+/// Sonnet). See <https://docs.aws.amazon.com/bedrock/latest/userguide/prompt-caching.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BedrockCachePoint {
+    /// After the system prompt.
+    AfterSystem,
+    /// After the message at this index in the request's `messages`.
+    AfterMessage(usize),
+}
+
+/// The Bedrock latency mode (`performanceConfig.latency`).
+///
+/// Latency-optimized inference lowers time to first token for supported
+/// models (e.g. Claude 3.5 Haiku and Llama 3.1 405B in some regions); other
+/// models reject it. See
+/// <https://docs.aws.amazon.com/bedrock/latest/userguide/latency-optimized-inference.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyMode {
+    /// Standard inference, the Bedrock default.
+    Standard,
+    /// Latency-optimized inference.
+    Optimized,
+}
+
+/// A Bedrock guardrail reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedrockGuardrail {
+    /// The guardrail identifier or ARN.
+    pub identifier: String,
+    /// The guardrail version, e.g. `"1"` or `"DRAFT"`.
+    pub version: String,
+    /// Whether to enable the guardrail trace.
+    pub trace: bool,
+}
@@ -0,0 +1,142 @@
+//! A fluent builder for chat completion requests.
+
+use async_openai::types::chat::{
+    ChatCompletionRequestAssistantMessage, ChatCompletionRequestDeveloperMessage,
+    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
+    ChatCompletionRequestUserMessage, ChatCompletionTools, CreateChatCompletionRequest,
+    StopConfiguration,
+};
+
+/// Builds a [`CreateChatCompletionRequest`] without the struct-update and
+/// message builder boilerplate.
+///
+/// Messages are appended in call order. Anything not covered by a method can
+/// be set on the built request directly.
+///
+/// ```
+/// use composite_llm::ChatRequest;
+///
+/// let req = ChatRequest::new("gpt-4o-mini")
+///     .system("Answer in one word.")
+///     .user("What is the capital of France?")
+///     .temperature(0.2)
+///     .build();
+///
+/// assert_eq!(req.model, "gpt-4o-mini");
+/// assert_eq!(req.messages.len(), 2);
+/// assert_eq!(req.temperature, Some(0.2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChatRequest {
+    req: CreateChatCompletionRequest,
+}
+
+impl ChatRequest {
+    /// Starts a request for `model`; an empty model uses the backend's
+    /// default where it has one.
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            req: CreateChatCompletionRequest {
+                model: model.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Appends a message.
+    pub fn message(mut self, message: impl Into<ChatCompletionRequestMessage>) -> Self {
+        self.req.messages.push(message.into());
+        self
+    }
+
+    /// Appends a system message.
+    pub fn system(self, text: impl Into<String>) -> Self {
+        self.message(ChatCompletionRequestSystemMessage::from(text.into()))
+    }
+
+    /// Appends a developer message.
+    pub fn developer(self, text: impl Into<String>) -> Self {
+        self.message(ChatCompletionRequestDeveloperMessage::from(text.into()))
+    }
+
+    /// Appends a user message.
+    pub fn user(self, text: impl Into<String>) -> Self {
+        self.message(ChatCompletionRequestUserMessage::from(text.into()))
+    }
+
+    /// Appends an assistant message, e.g. an earlier reply in a conversation.
+    pub fn assistant(self, text: impl Into<String>) -> Self {
+        self.message(ChatCompletionRequestAssistantMessage::from(text.into()))
+    }
+
+    /// Adds a tool the model may call.
+    pub fn tool(mut self, tool: ChatCompletionTools) -> Self {
+        self.req.tools.get_or_insert_with(Vec::new).push(tool);
+        self
+    }
+
+    /// Sets the sampling temperature.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.req.temperature = Some(temperature);
+        self
+    }
+
+    /// Sets the nucleus sampling probability mass.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.req.top_p = Some(top_p);
+        self
+    }
+
+    /// Sets the maximum number of tokens to generate.
+    pub fn max_completion_tokens(mut self, max_completion_tokens: u32) -> Self {
+        self.req.max_completion_tokens = Some(max_completion_tokens);
+        self
+    }
+
+    /// Sets the stop sequences.
+    pub fn stop(mut self, stop: impl Into<StopConfiguration>) -> Self {
+        self.req.stop = Some(stop.into());
+        self
+    }
+
+    /// Returns the request.
+    pub fn build(self) -> CreateChatCompletionRequest {
+        self.req
+    }
+}
+
+impl From<ChatRequest> for CreateChatCompletionRequest {
+    fn from(req: ChatRequest) -> Self {
+        req.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_keep_call_order() {
+        let req = ChatRequest::new("m")
+            .system("s")
+            .user("u")
+            .assistant("a")
+            .developer("d")
+            .max_completion_tokens(10)
+            .stop("END")
+            .build();
+
+        assert!(matches!(
+            req.messages[..],
+            [
+                ChatCompletionRequestMessage::System(_),
+                ChatCompletionRequestMessage::User(_),
+                ChatCompletionRequestMessage::Assistant(_),
+                ChatCompletionRequestMessage::Developer(_),
+            ]
+        ));
+        assert_eq!(req.max_completion_tokens, Some(10));
+        assert_eq!(req.stop, Some(StopConfiguration::String("END".to_string())));
+        assert!(req.tools.is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! Mapping model names to the backend that serves them.
+
+use crate::BackendKind;
+
+/// Model name prefixes routed by [`ModelRouter::new`].
+const DEFAULT_PREFIXES: &[(&str, BackendKind)] = &[
+    ("gpt-", BackendKind::OpenAI),
+    ("chatgpt-", BackendKind::OpenAI),
+    ("o1", BackendKind::OpenAI),
+    ("o3", BackendKind::OpenAI),
+    ("o4", BackendKind::OpenAI),
+    ("gemini", BackendKind::Vertex),
+    ("anthropic.", BackendKind::Bedrock),
+    ("amazon.", BackendKind::Bedrock),
+    ("meta.", BackendKind::Bedrock),
+    ("mistral.", BackendKind::Bedrock),
+    ("cohere.", BackendKind::Bedrock),
+    ("ai21.", BackendKind::Bedrock),
+    // Cross-region inference profiles, e.g. `us.anthropic.claude-...`.
+    ("us.", BackendKind::Bedrock),
+    ("eu.", BackendKind::Bedrock),
+    ("apac.", BackendKind::Bedrock),
+    ("arn:aws:bedrock:", BackendKind::Bedrock),
+];
+
+/// Picks a [`BackendKind`] for a model name by its prefix.
+///
+/// The longest matching prefix wins; among prefixes of equal length the one
+/// added last wins, so [`add_prefix`](Self::add_prefix) overrides the
+/// default table.
+///
+/// ```
+/// use composite_llm::{BackendKind, ModelRouter};
+///
+/// let mut router = ModelRouter::new();
+/// router.add_prefix("llama", BackendKind::Bedrock);
+///
+/// assert_eq!(router.route("gpt-4o"), Some(BackendKind::OpenAI));
+/// assert_eq!(router.route("llama3-70b"), Some(BackendKind::Bedrock));
+/// assert_eq!(router.route("unknown"), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModelRouter {
+    prefixes: Vec<(String, BackendKind)>,
+}
+
+impl ModelRouter {
+    /// Creates a router with the default table: OpenAI model families
+    /// (`gpt-`, `o1`, ...), `gemini` for Vertex AI, and Bedrock model provider
+    /// prefixes (`anthropic.`, `meta.`, ...) including inference profiles and
+    /// ARNs.
+    pub fn new() -> Self {
+        Self {
+            prefixes: DEFAULT_PREFIXES
+                .iter()
+                .map(|(prefix, kind)| (prefix.to_string(), *kind))
+                .collect(),
+        }
+    }
+
+    /// Creates a router without any prefix.
+    pub fn empty() -> Self {
+        Self {
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Routes models starting with `prefix` to `kind`.
+    pub fn add_prefix(&mut self, prefix: impl Into<String>, kind: BackendKind) -> &mut Self {
+        self.prefixes.push((prefix.into(), kind));
+        self
+    }
+
+    /// Returns the backend for `model`, or `None` if no prefix matches.
+    pub fn route(&self, model: &str) -> Option<BackendKind> {
+        self.prefixes
+            .iter()
+            .enumerate()
+            .filter(|(_, (prefix, _))| model.starts_with(prefix.as_str()))
+            .max_by_key(|(i, (prefix, _))| (prefix.len(), *i))
+            .map(|(_, (_, kind))| *kind)
+    }
+}
+
+impl Default for ModelRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_table() {
+        let router = ModelRouter::new();
+        assert_eq!(router.route("gpt-4o-mini"), Some(BackendKind::OpenAI));
+        assert_eq!(router.route("o3-mini"), Some(BackendKind::OpenAI));
+        assert_eq!(router.route("gemini-2.5-pro"), Some(BackendKind::Vertex));
+        assert_eq!(
+            router.route("anthropic.claude-3-5-sonnet-20240620-v1:0"),
+            Some(BackendKind::Bedrock)
+        );
+        assert_eq!(
+            router.route("us.meta.llama3-2-90b-instruct-v1:0"),
+            Some(BackendKind::Bedrock)
+        );
+        assert_eq!(router.route("llama3"), None);
+        assert_eq!(ModelRouter::empty().route("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_override() {
+        let mut router = ModelRouter::new();
+        router
+            .add_prefix("llama", BackendKind::Bedrock)
+            .add_prefix("gpt-", BackendKind::Azure)
+            .add_prefix("gemini-1.5", BackendKind::OpenAI);
+
+        assert_eq!(router.route("llama3"), Some(BackendKind::Bedrock));
+        // Same length as the default prefix: the override wins.
+        assert_eq!(router.route("gpt-4o"), Some(BackendKind::Azure));
+        // Longer prefix wins over the default `gemini`.
+        assert_eq!(router.route("gemini-1.5-pro"), Some(BackendKind::OpenAI));
+        assert_eq!(router.route("gemini-2.0-flash"), Some(BackendKind::Vertex));
+    }
+}
@@ -0,0 +1,803 @@
+//! Adapters for [`ChatCompletionStream`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_openai::types::chat::{
+    ChatChoice, ChatChoiceLogprobs, ChatCompletionMessageToolCall, ChatCompletionMessageToolCalls,
+    ChatCompletionResponseMessage, CreateChatCompletionResponse,
+    CreateChatCompletionStreamResponse, FinishReason, FunctionCall, Role,
+};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time::Sleep;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::backend::ChatCompletionStream;
+use crate::convert::CHAT_COMPLETION_OBJECT;
+use crate::convert::openai::{SSE_DONE, to_openai_sse_bytes};
+use crate::error::CompositeLlmError;
+
+/// Buffers small content deltas and emits them as merged chunks.
+///
+/// Content-only chunks are accumulated until at least `min_bytes` of content
+/// is buffered or `max_delay` has passed since the first buffered delta,
+/// whichever comes first. Any other chunk (tool calls, finish reason, usage,
+/// a role change) and errors flush the buffer and are passed through
+/// immediately, so ordering is preserved.
+pub fn coalesce(
+    stream: ChatCompletionStream,
+    min_bytes: usize,
+    max_delay: Duration,
+) -> ChatCompletionStream {
+    Box::pin(Coalesce {
+        inner: stream,
+        min_bytes,
+        max_delay,
+        buffer: None,
+        deadline: None,
+        queued: None,
+        done: false,
+    })
+}
+
+/// Attaches the content received so far to a mid-stream error.
+///
+/// Content deltas of the first choice (index 0) are accumulated; if the
+/// stream then yields an error, it is replaced by
+/// [`CompositeLlmError::StreamInterrupted`] carrying the partial text and the
+/// original error as its source. Chunks are passed through unchanged.
+pub fn with_partial_on_error(stream: ChatCompletionStream) -> ChatCompletionStream {
+    let mut partial = String::new();
+    Box::pin(stream.map(move |item| match item {
+        Ok(chunk) => {
+            let content = chunk
+                .choices
+                .iter()
+                .filter(|c| c.index == 0)
+                .filter_map(|c| c.delta.content.as_deref());
+            for text in content {
+                partial.push_str(text);
+            }
+            Ok(chunk)
+        }
+        Err(e) => Err(CompositeLlmError::StreamInterrupted {
+            partial: std::mem::take(&mut partial),
+            source: Box::new(e),
+        }),
+    }))
+}
+
+/// Ends a stream as soon as a chunk finishes with `ToolCalls`.
+///
+/// The chunk carrying `finish_reason: ToolCalls` is the last one yielded; the
+/// tool call deltas before it are passed through as usual. The inner stream
+/// is dropped right away instead of being polled to its end, so providers
+/// that keep the connection open briefly after a tool call are not waited
+/// on. Anything after that chunk, including a trailing usage chunk, is
+/// discarded.
+pub fn stop_after_tool_calls(stream: ChatCompletionStream) -> ChatCompletionStream {
+    Box::pin(futures_util::stream::unfold(
+        Some(stream),
+        |stream| async move {
+            let mut stream = stream?;
+            let item = stream.next().await?;
+            let done = matches!(item, Ok(ref chunk) if chunk
+                .choices
+                .iter()
+                .any(|c| c.finish_reason == Some(FinishReason::ToolCalls)));
+            Some((item, (!done).then_some(stream)))
+        },
+    ))
+}
+
+/// Drains a stream into a single non-streaming response.
+///
+/// Deltas are reassembled per `choice.index`, so the interleaved choices of a
+/// request with `n > 1` come out as separate choices ordered by index. Tool
+/// call fragments are joined per tool call index. The id, model and creation
+/// time come from the first chunk and usage from the last chunk carrying it.
+/// The first stream error is returned.
+#[allow(deprecated)]
+pub async fn collect_stream(
+    mut stream: ChatCompletionStream,
+) -> Result<CreateChatCompletionResponse, CompositeLlmError> {
+    let mut first: Option<CreateChatCompletionStreamResponse> = None;
+    let mut usage = None;
+    let mut choices: BTreeMap<u32, CollectedChoice> = BTreeMap::new();
+
+    while let Some(chunk) = stream.next().await {
+        let mut chunk = chunk?;
+        if chunk.usage.is_some() {
+            usage = chunk.usage.take();
+        }
+        for choice in std::mem::take(&mut chunk.choices) {
+            let collected = choices.entry(choice.index).or_default();
+            let delta = choice.delta;
+            if delta.role.is_some() {
+                collected.role = delta.role;
+            }
+            if let Some(text) = delta.content {
+                collected.content.get_or_insert_default().push_str(&text);
+            }
+            if let Some(text) = delta.refusal {
+                collected.refusal.get_or_insert_default().push_str(&text);
+            }
+            for call in delta.tool_calls.into_iter().flatten() {
+                let (id, name, arguments) = collected.tool_calls.entry(call.index).or_default();
+                if let Some(call_id) = call.id {
+                    *id = call_id;
+                }
+                if let Some(function) = call.function {
+                    if let Some(n) = function.name {
+                        name.push_str(&n);
+                    }
+                    if let Some(a) = function.arguments {
+                        arguments.push_str(&a);
+                    }
+                }
+            }
+            if choice.finish_reason.is_some() {
+                collected.finish_reason = choice.finish_reason;
+            }
+            if let Some(logprobs) = choice.logprobs {
+                let merged = collected.logprobs.get_or_insert(ChatChoiceLogprobs {
+                    content: None,
+                    refusal: None,
+                });
+                if let Some(content) = logprobs.content {
+                    merged.content.get_or_insert_default().extend(content);
+                }
+                if let Some(refusal) = logprobs.refusal {
+                    merged.refusal.get_or_insert_default().extend(refusal);
+                }
+            }
+        }
+        first.get_or_insert(chunk);
+    }
+
+    let first = first.unwrap_or_else(|| CreateChatCompletionStreamResponse {
+        id: String::new(),
+        object: String::new(),
+        created: 0,
+        model: String::new(),
+        choices: vec![],
+        usage: None,
+        system_fingerprint: None,
+        service_tier: None,
+    });
+
+    let choices = choices
+        .into_iter()
+        .map(|(index, c)| {
+            let tool_calls: Vec<_> = c
+                .tool_calls
+                .into_values()
+                .map(|(id, name, arguments)| {
+                    ChatCompletionMessageToolCalls::Function(ChatCompletionMessageToolCall {
+                        id,
+                        function: FunctionCall { name, arguments },
+                    })
+                })
+                .collect();
+            ChatChoice {
+                index,
+                message: ChatCompletionResponseMessage {
+                    content: c.content,
+                    refusal: c.refusal,
+                    tool_calls: if tool_calls.is_empty() {
+                        None
+                    } else {
+                        Some(tool_calls)
+                    },
+                    annotations: None,
+                    role: c.role.unwrap_or(Role::Assistant),
+                    function_call: None,
+                    audio: None,
+                },
+                finish_reason: c.finish_reason,
+                logprobs: c.logprobs,
+            }
+        })
+        .collect();
+
+    Ok(CreateChatCompletionResponse {
+        id: first.id,
+        object: CHAT_COMPLETION_OBJECT.to_string(),
+        created: first.created,
+        model: first.model,
+        choices,
+        usage,
+        system_fingerprint: first.system_fingerprint,
+        service_tier: first.service_tier,
+    })
+}
+
+/// A choice being reassembled by [`collect_stream`].
+#[derive(Default)]
+struct CollectedChoice {
+    role: Option<Role>,
+    content: Option<String>,
+    refusal: Option<String>,
+    /// `(id, name, arguments)` by tool call index.
+    tool_calls: BTreeMap<u32, (String, String, String)>,
+    finish_reason: Option<FinishReason>,
+    logprobs: Option<ChatChoiceLogprobs>,
+}
+
+/// A chat completion stream of a concrete type `S`, or a boxed
+/// [`ChatCompletionStream`] by default.
+///
+/// Backends whose stream has a nameable type return it in this wrapper from
+/// their inherent methods (e.g. `OpenAIBackend::chat_completion_stream_typed`),
+/// so callers composing on it skip the boxed trait object.
+/// [`into_boxed`](Self::into_boxed) converts it to the form the
+/// [`ChatCompletionBackend`](crate::ChatCompletionBackend) methods return.
+#[derive(Debug)]
+pub struct CompletionStream<S = ChatCompletionStream> {
+    inner: S,
+}
+
+impl<S> CompletionStream<S> {
+    /// Wraps `inner`.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> CompletionStream<S>
+where
+    S: Stream<Item = Item> + Send + 'static,
+{
+    /// Boxes the stream into a [`ChatCompletionStream`].
+    pub fn into_boxed(self) -> ChatCompletionStream {
+        Box::pin(self.inner)
+    }
+}
+
+impl<S> Stream for CompletionStream<S>
+where
+    S: Stream<Item = Item> + Unpin,
+{
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Splits a stream into a live copy and its reassembled response.
+///
+/// The returned stream yields the chunks of `stream` unchanged. Every chunk
+/// is also handed to the returned future, which resolves to the response
+/// [`collect_stream`] would build once the stream has ended. Nothing is
+/// fetched twice; the future only makes progress while the stream is
+/// consumed, so both must be driven, e.g. by polling the future from another
+/// task or awaiting it after the stream is drained.
+///
+/// If the stream yields an error while the future is alive, the stream yields
+/// it unchanged and the future fails with a copy of it as
+/// [`CompositeLlmError::Shared`]; if the stream is dropped before it ends,
+/// the future fails as well.
+pub fn tee(
+    stream: ChatCompletionStream,
+) -> (
+    ChatCompletionStream,
+    impl Future<Output = Result<CreateChatCompletionResponse, CompositeLlmError>> + Send + 'static,
+) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let copies = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    });
+    let tee = Tee {
+        inner: stream,
+        tx: Some(tx),
+    };
+    (Box::pin(tee), collect_stream(Box::pin(copies)))
+}
+
+/// The live half of [`tee`].
+struct Tee {
+    inner: ChatCompletionStream,
+    /// Taken once the inner stream has ended.
+    tx: Option<tokio::sync::mpsc::UnboundedSender<Item>>,
+}
+
+impl Stream for Tee {
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = match this.inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(item)) => item,
+            Poll::Ready(None) => {
+                this.tx = None;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => return Poll::Pending,
+        };
+        // Once the future has been dropped, the stream goes on unchanged.
+        let tx = match this.tx {
+            Some(ref tx) if !tx.is_closed() => tx,
+            _ => return Poll::Ready(Some(item)),
+        };
+        let item = match item {
+            Ok(chunk) => {
+                let _ = tx.send(Ok(chunk.clone()));
+                Ok(chunk)
+            }
+            Err(e) => {
+                let copy = CompositeLlmError::Shared(Arc::new(e.detached_copy()));
+                let _ = tx.send(Err(copy));
+                Err(e)
+            }
+        };
+        Poll::Ready(Some(item))
+    }
+}
+
+impl Drop for Tee {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Err(CompositeLlmError::Other(
+                "stream dropped before it ended".to_string(),
+            )));
+        }
+    }
+}
+
+/// Ends a stream as soon as `token` is cancelled.
+///
+/// On cancellation the wrapped stream is dropped right away, which closes the
+/// upstream connection (and stops a Bedrock stream task) even if the returned
+/// stream is kept around. Chunks already yielded are unaffected; no error is
+/// reported, the stream simply ends.
+pub fn cancellable(stream: ChatCompletionStream, token: CancellationToken) -> ChatCompletionStream {
+    Box::pin(Cancellable {
+        inner: Some(stream),
+        cancelled: Box::pin(token.cancelled_owned()),
+    })
+}
+
+/// The stream returned by [`cancellable`].
+struct Cancellable {
+    /// Dropped once the stream has ended or been cancelled.
+    inner: Option<ChatCompletionStream>,
+    cancelled: Pin<Box<WaitForCancellationFutureOwned>>,
+}
+
+impl Stream for Cancellable {
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.inner.is_none() || this.cancelled.as_mut().poll(cx).is_ready() {
+            this.inner = None;
+            return Poll::Ready(None);
+        }
+        let Some(inner) = this.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let poll = inner.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            this.inner = None;
+        }
+        poll
+    }
+}
+
+/// Writes the content deltas of a stream to `writer` as they arrive.
+///
+/// Only the first choice (index 0) is written. The writer is flushed after
+/// every delta so output reaches the client immediately. A stream error stops
+/// the copy and is returned; content written before it is kept.
+pub async fn write_stream_to<W>(
+    mut stream: ChatCompletionStream,
+    writer: &mut W,
+) -> Result<(), CompositeLlmError>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let content = chunk
+            .choices
+            .iter()
+            .filter(|c| c.index == 0)
+            .filter_map(|c| c.delta.content.as_deref());
+        for text in content {
+            writer.write_all(text.as_bytes()).await?;
+            writer.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a stream to `writer` as OpenAI-compatible server-sent events.
+///
+/// Every chunk is encoded as a `data: {json}\n\n` frame, followed by the
+/// terminating `data: [DONE]\n\n` once the stream ends. A stream error stops
+/// the copy before `[DONE]` is written and is returned.
+pub async fn write_stream_sse_to<W>(
+    mut stream: ChatCompletionStream,
+    writer: &mut W,
+) -> Result<(), CompositeLlmError>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    while let Some(chunk) = stream.next().await {
+        writer.write_all(&to_openai_sse_bytes(&chunk?)?).await?;
+        writer.flush().await?;
+    }
+    writer.write_all(SSE_DONE).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+type Item = Result<CreateChatCompletionStreamResponse, CompositeLlmError>;
+
+struct Coalesce {
+    inner: ChatCompletionStream,
+    min_bytes: usize,
+    max_delay: Duration,
+    buffer: Option<CreateChatCompletionStreamResponse>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    queued: Option<Item>,
+    done: bool,
+}
+
+impl Coalesce {
+    fn flush(&mut self) -> Option<Item> {
+        self.deadline = None;
+        self.buffer.take().map(Ok)
+    }
+
+    fn buffered_len(&self) -> usize {
+        self.buffer
+            .as_ref()
+            .and_then(|b| b.choices[0].delta.content.as_ref())
+            .map_or(0, String::len)
+    }
+}
+
+/// Whether a chunk carries nothing but a content delta for a single choice.
+fn is_content_only(chunk: &CreateChatCompletionStreamResponse) -> bool {
+    #[allow(deprecated)]
+    let only_content = |c: &async_openai::types::chat::ChatChoiceStream| {
+        c.delta.content.is_some()
+            && c.delta.tool_calls.is_none()
+            && c.delta.function_call.is_none()
+            && c.delta.refusal.is_none()
+            && c.finish_reason.is_none()
+            && c.logprobs.is_none()
+    };
+    chunk.usage.is_none() && chunk.choices.len() == 1 && only_content(&chunk.choices[0])
+}
+
+/// Whether `next` can be appended to the buffered chunk `buf`.
+fn can_merge(
+    buf: &CreateChatCompletionStreamResponse,
+    next: &CreateChatCompletionStreamResponse,
+) -> bool {
+    buf.choices[0].index == next.choices[0].index && next.choices[0].delta.role.is_none()
+}
+
+impl Stream for Coalesce {
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(item) = this.queued.take() {
+                return Poll::Ready(Some(item));
+            }
+
+            if this.done {
+                return Poll::Ready(this.flush());
+            }
+
+            if this.buffer.is_some() && this.buffered_len() >= this.min_bytes {
+                return Poll::Ready(this.flush());
+            }
+
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) if is_content_only(&chunk) => match this.buffer {
+                    Some(ref mut buf) if can_merge(buf, &chunk) => {
+                        let text = chunk.choices[0].delta.content.as_deref().unwrap_or("");
+                        if let Some(ref mut content) = buf.choices[0].delta.content {
+                            content.push_str(text);
+                        }
+                    }
+                    Some(_) => {
+                        let flushed = this.flush();
+                        this.buffer = Some(chunk);
+                        this.deadline = Some(Box::pin(tokio::time::sleep(this.max_delay)));
+                        return Poll::Ready(flushed);
+                    }
+                    None => {
+                        this.buffer = Some(chunk);
+                        this.deadline = Some(Box::pin(tokio::time::sleep(this.max_delay)));
+                    }
+                },
+                Poll::Ready(Some(item)) => {
+                    if this.buffer.is_some() {
+                        this.queued = Some(item);
+                        return Poll::Ready(this.flush());
+                    }
+                    return Poll::Ready(Some(item));
+                }
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => {
+                    if let Some(ref mut deadline) = this.deadline
+                        && deadline.as_mut().poll(cx).is_ready()
+                    {
+                        return Poll::Ready(this.flush());
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::chat::{
+        ChatChoiceStream, ChatCompletionStreamResponseDelta, FinishReason,
+    };
+
+    #[allow(deprecated)]
+    fn chunk(
+        content: Option<&str>,
+        finish_reason: Option<FinishReason>,
+    ) -> CreateChatCompletionStreamResponse {
+        CreateChatCompletionStreamResponse {
+            id: "chatcmpl-test".to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created: 0,
+            model: "test".to_string(),
+            choices: vec![ChatChoiceStream {
+                index: 0,
+                delta: ChatCompletionStreamResponseDelta {
+                    content: content.map(str::to_string),
+                    tool_calls: None,
+                    role: None,
+                    function_call: None,
+                    refusal: None,
+                },
+                finish_reason,
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_after_tool_calls() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("a"), None)),
+            Ok(chunk(None, Some(FinishReason::ToolCalls))),
+            Ok(chunk(Some("late"), None)),
+        ]));
+        let chunks: Vec<Item> = stop_after_tool_calls(inner).collect().await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[1].as_ref().unwrap().choices[0].finish_reason,
+            Some(FinishReason::ToolCalls)
+        );
+    }
+
+    fn contents(chunks: &[Item]) -> Vec<Option<String>> {
+        chunks
+            .iter()
+            .map(|c| c.as_ref().unwrap().choices[0].delta.content.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_merges_until_min_bytes() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("He"), None)),
+            Ok(chunk(Some("ll"), None)),
+            Ok(chunk(Some("o "), None)),
+            Ok(chunk(Some("wo"), None)),
+            Ok(chunk(Some("rld"), None)),
+            Ok(chunk(None, Some(FinishReason::Stop))),
+        ]));
+
+        let out: Vec<_> = coalesce(inner, 4, Duration::from_secs(60)).collect().await;
+        assert_eq!(
+            contents(&out),
+            vec![
+                Some("Hell".to_string()),
+                Some("o wo".to_string()),
+                Some("rld".to_string()),
+                None,
+            ]
+        );
+        assert_eq!(
+            out[3].as_ref().unwrap().choices[0].finish_reason,
+            Some(FinishReason::Stop)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_flushes_after_max_delay() {
+        let inner: ChatCompletionStream = Box::pin(
+            futures_util::stream::iter(vec![Ok(chunk(Some("Hi"), None))])
+                .chain(futures_util::stream::pending()),
+        );
+
+        let mut out = coalesce(inner, 1024, Duration::from_millis(10));
+        let first = tokio::time::timeout(Duration::from_secs(5), out.next())
+            .await
+            .expect("buffer was not flushed")
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.choices[0].delta.content.as_deref(), Some("Hi"));
+    }
+
+    #[tokio::test]
+    async fn test_with_partial_on_error() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hello, "), None)),
+            Ok(chunk(Some("wor"), None)),
+            Err(CompositeLlmError::Unsupported(
+                "connection reset".to_string(),
+            )),
+        ]));
+
+        let out: Vec<_> = with_partial_on_error(inner).collect().await;
+        assert_eq!(out.len(), 3);
+        match out.into_iter().last().unwrap() {
+            Err(CompositeLlmError::StreamInterrupted { partial, source }) => {
+                assert_eq!(partial, "Hello, wor");
+                assert!(matches!(*source, CompositeLlmError::Unsupported(_)));
+            }
+            other => panic!("unexpected item: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_reassembles_choices_by_index() {
+        let indexed = |index, content: Option<&str>, finish_reason| {
+            let mut c = chunk(content, finish_reason);
+            c.choices[0].index = index;
+            c
+        };
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(indexed(0, Some("Hel"), None)),
+            Ok(indexed(1, Some("Bon"), None)),
+            Ok(indexed(1, Some("jour"), None)),
+            Ok(indexed(0, Some("lo"), None)),
+            Ok(indexed(1, None, Some(FinishReason::Stop))),
+            Ok(indexed(0, None, Some(FinishReason::Length))),
+        ]));
+
+        let resp = collect_stream(inner).await.unwrap();
+        assert_eq!(resp.id, "chatcmpl-test");
+        assert_eq!(resp.object, "chat.completion");
+        assert_eq!(resp.choices.len(), 2);
+        assert_eq!(resp.choices[0].index, 0);
+        assert_eq!(resp.choices[0].message.content.as_deref(), Some("Hello"));
+        assert_eq!(resp.choices[0].finish_reason, Some(FinishReason::Length));
+        assert_eq!(resp.choices[1].index, 1);
+        assert_eq!(resp.choices[1].message.content.as_deref(), Some("Bonjour"));
+        assert_eq!(resp.choices[1].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn test_tee() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hel"), None)),
+            Ok(chunk(Some("lo"), Some(FinishReason::Stop))),
+        ]));
+
+        let (stream, response) = tee(inner);
+        let live: Vec<_> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(live.len(), 2);
+        let resp = response.await.unwrap();
+        assert_eq!(resp.choices[0].message.content.as_deref(), Some("Hello"));
+        assert_eq!(resp.choices[0].finish_reason, Some(FinishReason::Stop));
+
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hel"), None)),
+            Ok(chunk(Some("lo"), None)),
+        ]));
+        let (mut stream, response) = tee(inner);
+        stream.next().await;
+        drop(stream);
+        assert!(matches!(response.await, Err(CompositeLlmError::Other(_))));
+
+        // The stream keeps the original error; the future gets a copy.
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hel"), None)),
+            Err(CompositeLlmError::RateLimited),
+        ]));
+        let (stream, response) = tee(inner);
+        let live: Vec<_> = stream.collect().await;
+        assert!(matches!(live[1], Err(CompositeLlmError::RateLimited)));
+        let err = response.await.unwrap_err();
+        assert!(matches!(
+            err,
+            CompositeLlmError::Shared(ref e) if matches!(**e, CompositeLlmError::RateLimited)
+        ));
+        assert_eq!(err.to_string(), "Rate limit exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_completion_stream() {
+        let inner = futures_util::stream::iter(vec![Ok(chunk(Some("Hi"), None))]);
+        let mut typed = CompletionStream::new(inner);
+        assert_eq!(typed.size_hint(), (1, Some(1)));
+        assert!(typed.next().await.unwrap().is_ok());
+        assert!(typed.into_boxed().next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancellable() {
+        let inner: ChatCompletionStream = Box::pin(
+            futures_util::stream::iter(vec![Ok(chunk(Some("Hi"), None))])
+                .chain(futures_util::stream::pending()),
+        );
+        let token = CancellationToken::new();
+        let mut stream = cancellable(inner, token.clone());
+
+        assert!(stream.next().await.unwrap().is_ok());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel();
+        });
+        assert!(stream.next().await.is_none());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_stream_to() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hello, "), None)),
+            Ok(chunk(Some("world"), None)),
+            Ok(chunk(None, Some(FinishReason::Stop))),
+        ]));
+
+        let mut out = Vec::new();
+        write_stream_to(inner, &mut out).await.unwrap();
+        assert_eq!(out, b"Hello, world");
+    }
+
+    #[tokio::test]
+    async fn test_write_stream_sse_to() {
+        let inner: ChatCompletionStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(chunk(Some("Hi"), None)),
+            Ok(chunk(None, Some(FinishReason::Stop))),
+        ]));
+
+        let mut out = Vec::new();
+        write_stream_sse_to(inner, &mut out).await.unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let frames: Vec<&str> = text.split_terminator("\n\n").collect();
+        assert_eq!(frames.len(), 3);
+        let first: CreateChatCompletionStreamResponse =
+            serde_json::from_str(frames[0].strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(first.choices[0].delta.content.as_deref(), Some("Hi"));
+        assert_eq!(frames[2], "data: [DONE]");
+    }
+}